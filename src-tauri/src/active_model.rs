@@ -0,0 +1,307 @@
+//! The Whisper model recordings are transcribed with.
+//!
+//! Nothing is loaded at startup: the model is loaded the first time it is
+//! needed, and a missing or broken file only puts the app into a degraded state
+//! that the frontend can show. Pointing at another file or finishing a download
+//! swaps the model in without a restart.
+
+use crate::inference::InferenceWorker;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", content = "message", rename_all = "camelCase")]
+pub enum ModelState {
+    /// The file is there and gets loaded on first use
+    NotLoaded,
+    /// No file at the configured path
+    Missing,
+    Loading,
+    Loaded,
+    /// The last load failed; the next use tries again
+    Failed(String),
+}
+
+/// Payload of `get_model_status` and the `model-status` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelStatus {
+    #[serde(flatten)]
+    pub state: ModelState,
+    pub path: String,
+}
+
+type Loader = Arc<dyn Fn(PathBuf) -> Result<InferenceWorker> + Send + Sync>;
+type Listener = Box<dyn Fn(ModelStatus) + Send + Sync>;
+
+struct Slot {
+    path: PathBuf,
+    worker: Option<InferenceWorker>,
+    loading: bool,
+    error: Option<String>,
+}
+
+pub struct ActiveModel {
+    slot: Mutex<Slot>,
+    /// Held for the whole of a load so concurrent users wait for a single load
+    load_lock: tokio::sync::Mutex<()>,
+    loader: Loader,
+    on_change: Listener,
+}
+
+impl ActiveModel {
+    /// Use the model at `path`; `on_change` is told about every status change
+    pub fn new(path: PathBuf, on_change: impl Fn(ModelStatus) + Send + Sync + 'static) -> Self {
+        Self::with_loader(path, InferenceWorker::load, on_change)
+    }
+
+    fn with_loader(
+        path: PathBuf,
+        loader: impl Fn(PathBuf) -> Result<InferenceWorker> + Send + Sync + 'static,
+        on_change: impl Fn(ModelStatus) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            slot: Mutex::new(Slot {
+                path,
+                worker: None,
+                loading: false,
+                error: None,
+            }),
+            load_lock: tokio::sync::Mutex::new(()),
+            loader: Arc::new(loader),
+            on_change: Box::new(on_change),
+        }
+    }
+
+    pub fn status(&self) -> ModelStatus {
+        let slot = self.slot.lock().unwrap();
+        let state = if slot.loading {
+            ModelState::Loading
+        } else if slot.worker.is_some() {
+            ModelState::Loaded
+        } else if let Some(error) = &slot.error {
+            ModelState::Failed(error.clone())
+        } else if !slot.path.is_file() {
+            ModelState::Missing
+        } else {
+            ModelState::NotLoaded
+        };
+
+        ModelStatus {
+            state,
+            path: slot.path.to_string_lossy().to_string(),
+        }
+    }
+
+    /// The worker, if the model is already loaded
+    pub fn worker(&self) -> Option<InferenceWorker> {
+        self.slot.lock().unwrap().worker.clone()
+    }
+
+    /// The worker, loading the model first if needed
+    pub async fn load(&self) -> Result<InferenceWorker> {
+        let _guard = self.load_lock.lock().await;
+        if let Some(worker) = self.worker() {
+            return Ok(worker);
+        }
+
+        let path = self.slot.lock().unwrap().path.clone();
+        let result = self.load_file(path.clone()).await;
+        {
+            let mut slot = self.slot.lock().unwrap();
+            match &result {
+                Ok(worker) => {
+                    slot.worker = Some(worker.clone());
+                    slot.error = None;
+                }
+                // A missing file already shows as `Missing`
+                Err(e) if path.is_file() => slot.error = Some(format!("{:#}", e)),
+                Err(_) => slot.error = None,
+            }
+        }
+        self.notify();
+        result
+    }
+
+    /// Load the model at `path` and use it from now on.
+    ///
+    /// The current model stays active if the new one cannot be loaded.
+    pub async fn switch_to(&self, path: PathBuf) -> Result<InferenceWorker> {
+        let _guard = self.load_lock.lock().await;
+        let result = self.load_file(path.clone()).await;
+        if let Ok(worker) = &result {
+            let mut slot = self.slot.lock().unwrap();
+            slot.path = path;
+            slot.worker = Some(worker.clone());
+            slot.error = None;
+        }
+        self.notify();
+        result
+    }
+
+    /// Run the loader for `path` off the async runtime, with the status showing `Loading`
+    async fn load_file(&self, path: PathBuf) -> Result<InferenceWorker> {
+        if !path.is_file() {
+            anyhow::bail!(
+                "No Whisper model at {}. Download one in Settings or choose a model file",
+                path.display()
+            );
+        }
+
+        self.slot.lock().unwrap().loading = true;
+        self.notify();
+
+        let loader = self.loader.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || loader(path))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+
+        self.slot.lock().unwrap().loading = false;
+        result
+    }
+
+    fn notify(&self) {
+        (self.on_change)(self.status());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::temp_dir;
+    use crate::inference::{Engine, ProgressFn};
+    use crate::transcription::{Detection, Segment, TranscriptionOptions};
+    use std::fs;
+    use std::path::Path;
+
+    struct SilentEngine;
+
+    impl Engine for SilentEngine {
+        fn run(
+            &mut self,
+            _samples: &[f32],
+            _options: &TranscriptionOptions,
+            _single_segment: bool,
+            _progress: Option<ProgressFn>,
+        ) -> Result<(Vec<Segment>, Detection)> {
+            Ok((Vec::new(), Detection::UNKNOWN))
+        }
+    }
+
+    /// Loads any file except ones named `broken.bin`
+    fn fake_loader(path: PathBuf) -> Result<InferenceWorker> {
+        if path.file_name().is_some_and(|name| name == "broken.bin") {
+            anyhow::bail!("invalid model file");
+        }
+        InferenceWorker::spawn(SilentEngine, path)
+    }
+
+    fn model(path: &Path) -> (ActiveModel, Arc<Mutex<Vec<ModelState>>>) {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let sink = changes.clone();
+        let model = ActiveModel::with_loader(path.to_path_buf(), fake_loader, move |status| {
+            sink.lock().unwrap().push(status.state)
+        });
+        (model, changes)
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_loads_once_it_appears() {
+        let dir = temp_dir("active-model-appears");
+        let path = dir.join("ggml-small.bin");
+        let (model, _) = model(&path);
+
+        assert_eq!(model.status().state, ModelState::Missing);
+        assert!(model.load().await.is_err());
+        assert_eq!(model.status().state, ModelState::Missing);
+
+        // e.g. a download finished in the meantime
+        fs::write(&path, b"model").unwrap();
+        assert_eq!(model.status().state, ModelState::NotLoaded);
+        assert!(model.load().await.is_ok());
+        assert_eq!(model.status().state, ModelState::Loaded);
+        assert!(model.worker().is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_load_reports_progress_and_reuses_worker() {
+        let dir = temp_dir("active-model-reuse");
+        let path = dir.join("ggml-small.bin");
+        fs::write(&path, b"model").unwrap();
+        let (model, changes) = model(&path);
+
+        assert!(model.worker().is_none());
+        model.load().await.unwrap();
+        model.load().await.unwrap();
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![ModelState::Loading, ModelState::Loaded]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_load_is_retried() {
+        let dir = temp_dir("active-model-retry");
+        let path = dir.join("broken.bin");
+        fs::write(&path, b"garbage").unwrap();
+        let (model, _) = model(&path);
+
+        assert!(model.load().await.is_err());
+        assert_eq!(
+            model.status().state,
+            ModelState::Failed("invalid model file".to_string())
+        );
+
+        let good = dir.join("ggml-base.bin");
+        fs::write(&good, b"model").unwrap();
+        model.switch_to(good.clone()).await.unwrap();
+        let status = model.status();
+        assert_eq!(status.state, ModelState::Loaded);
+        assert_eq!(status.path, good.to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_switch_keeps_current_model() {
+        let dir = temp_dir("active-model-switch");
+        let path = dir.join("ggml-small.bin");
+        fs::write(&path, b"model").unwrap();
+        let broken = dir.join("broken.bin");
+        fs::write(&broken, b"garbage").unwrap();
+        let (model, _) = model(&path);
+        model.load().await.unwrap();
+
+        assert!(model.switch_to(broken).await.is_err());
+        assert!(model.switch_to(dir.join("absent.bin")).await.is_err());
+        let status = model.status();
+        assert_eq!(status.state, ModelState::Loaded);
+        assert_eq!(status.path, path.to_string_lossy());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_status_serialization() {
+        let status = ModelStatus {
+            state: ModelState::Failed("bad magic".to_string()),
+            path: "model.bin".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            serde_json::json!({ "state": "failed", "message": "bad magic", "path": "model.bin" })
+        );
+
+        let status = ModelStatus {
+            state: ModelState::Missing,
+            path: "model.bin".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            serde_json::json!({ "state": "missing", "path": "model.bin" })
+        );
+    }
+}
@@ -1,13 +1,120 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Host, SampleFormat, Stream, StreamConfig};
+use cpal::{Device, Host, SampleFormat, SizedSample, Stream, StreamConfig};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+/// Longest recording accepted by default (the advertised 5 minutes)
+pub const DEFAULT_MAX_DURATION_SECS: u32 = 300;
+
+/// Hard cap on buffered samples regardless of format (128 MB of f32)
+pub const MAX_BUFFER_SAMPLES: usize = 32 * 1024 * 1024;
+
+/// Bounds on how much audio a single recording may hold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureLimits {
+    pub max_duration_secs: u32,
+    pub max_buffer_samples: usize,
+}
+
+impl CaptureLimits {
+    /// Interleaved samples a recording in the given format may hold (whole frames)
+    pub fn capacity(&self, sample_rate: u32, channels: u16) -> usize {
+        let channels = channels.max(1) as usize;
+        let by_duration = self.max_duration_secs as usize * sample_rate as usize * channels;
+        by_duration.min(self.max_buffer_samples) / channels * channels
+    }
+}
+
+impl Default for CaptureLimits {
+    fn default() -> Self {
+        Self {
+            max_duration_secs: DEFAULT_MAX_DURATION_SECS,
+            max_buffer_samples: MAX_BUFFER_SAMPLES,
+        }
+    }
+}
+
+/// Captured audio together with the format it was recorded in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Interleaved samples normalized to [-1.0, 1.0]
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Recording {
+    /// Number of frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Recording length in seconds
+    pub fn duration_secs(&self) -> f32 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Read-only view of the buffer being filled by an active recording
+#[derive(Clone)]
+pub struct CaptureTap {
+    data: Arc<std::sync::Mutex<Vec<f32>>>,
+    limit_reached: Arc<AtomicBool>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples the buffer may hold before capture stops
+    pub capacity: usize,
+}
+
+impl CaptureTap {
+    /// Number of interleaved samples captured so far
+    pub fn len(&self) -> usize {
+        self.data.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy whole frames captured after interleaved sample `offset`
+    pub fn read_from(&self, offset: usize) -> Vec<f32> {
+        let data = self.data.lock().unwrap();
+        if offset >= data.len() {
+            return Vec::new();
+        }
+        let channels = self.channels.max(1) as usize;
+        let whole = (data.len() - offset) / channels * channels;
+        data[offset..offset + whole].to_vec()
+    }
+
+    /// Whether the buffer is full and newer audio is being dropped
+    pub fn limit_reached(&self) -> bool {
+        self.limit_reached.load(Ordering::SeqCst)
+    }
+
+    /// Seconds of audio that still fit in the buffer
+    pub fn remaining_secs(&self) -> f32 {
+        let remaining = self.capacity.saturating_sub(self.len());
+        remaining as f32 / (self.sample_rate.max(1) as f32 * self.channels.max(1) as f32)
+    }
+}
+
 pub struct AudioCapture {
     host: Host,
     device: Option<Device>,
     stream: Option<Stream>,
+    config: Option<StreamConfig>,
+    limits: CaptureLimits,
+    capacity: usize,
+    limit_reached: Arc<AtomicBool>,
     is_recording: Arc<AtomicBool>,
     audio_data: Arc<std::sync::Mutex<Vec<f32>>>,
 }
@@ -20,6 +127,10 @@ impl AudioCapture {
             host,
             device: None,
             stream: None,
+            config: None,
+            limits: CaptureLimits::default(),
+            capacity: 0,
+            limit_reached: Arc::new(AtomicBool::new(false)),
             is_recording: Arc::new(AtomicBool::new(false)),
             audio_data: Arc::new(std::sync::Mutex::new(Vec::new())),
         })
@@ -34,6 +145,21 @@ impl AudioCapture {
         Ok(devices)
     }
 
+    /// Name of the system default input device
+    pub fn default_device_name(&self) -> Option<String> {
+        self.host.default_input_device().and_then(|d| d.name().ok())
+    }
+
+    /// Name of the explicitly selected device, if any
+    pub fn selected_device_name(&self) -> Option<String> {
+        self.device.as_ref().and_then(|d| d.name().ok())
+    }
+
+    /// Follow the system default input device again
+    pub fn use_default_device(&mut self) {
+        self.device = None;
+    }
+
     pub fn select_device(&mut self, name: &str) -> Result<()> {
         let device = self
             .host
@@ -45,79 +171,149 @@ impl AudioCapture {
         Ok(())
     }
 
+    pub fn limits(&self) -> CaptureLimits {
+        self.limits
+    }
+
+    /// Limits apply from the next recording on
+    pub fn set_limits(&mut self, limits: CaptureLimits) {
+        self.limits = limits;
+    }
+
     pub fn start_recording(&mut self) -> Result<()> {
-        let device = self
-            .device
-            .as_ref()
-            .or(self.host.default_input_device().as_ref())
-            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
-
-        let supported_config = device
-            .default_input_config()
-            .unwrap_or_else(|_| cpal::StreamConfig {
-                channels: 1,
-                sample_rate: cpal::SampleRate(16000),
-                buffer_size: cpal::BufferSize::Default,
-            });
-
-        let config = StreamConfig {
-            channels: supported_config.channels,
-            sample_rate: supported_config.sample_rate,
-            buffer_size: supported_config.buffer_size,
+        let device = match &self.device {
+            Some(device) => device.clone(),
+            None => self
+                .host
+                .default_input_device()
+                .ok_or_else(|| anyhow::anyhow!("No input device available"))?,
         };
 
-        self.is_recording.store(true, Ordering::SeqCst);
+        let (config, sample_format) = match device.default_input_config() {
+            Ok(supported) => (supported.config(), supported.sample_format()),
+            Err(_) => (
+                StreamConfig {
+                    channels: 1,
+                    sample_rate: cpal::SampleRate(16000),
+                    buffer_size: cpal::BufferSize::Default,
+                },
+                SampleFormat::F32,
+            ),
+        };
+
+        log::info!(
+            "Opening input stream: {} Hz, {} channel(s), {:?}",
+            config.sample_rate.0,
+            config.channels,
+            sample_format
+        );
+
+        self.capacity = self.limits.capacity(config.sample_rate.0, config.channels);
+        self.limit_reached.store(false, Ordering::SeqCst);
         self.audio_data.lock().unwrap().clear();
 
+        let stream = start_flagged(&self.is_recording, || {
+            let stream = match sample_format {
+                SampleFormat::F32 => self.build_input_stream(&device, &config, f32_to_f32)?,
+                SampleFormat::I16 => self.build_input_stream(&device, &config, i16_to_f32)?,
+                SampleFormat::U16 => self.build_input_stream(&device, &config, u16_to_f32)?,
+                other => anyhow::bail!("Unsupported input sample format: {:?}", other),
+            };
+            stream.play()?;
+            Ok(stream)
+        })?;
+        self.stream = Some(stream);
+        self.config = Some(config);
+
+        log::info!("Recording started");
+        Ok(())
+    }
+
+    /// Build an input stream for sample type `T`, converting every sample to f32
+    fn build_input_stream<T>(
+        &self,
+        device: &Device,
+        config: &StreamConfig,
+        convert: fn(T) -> f32,
+    ) -> Result<Stream>
+    where
+        T: SizedSample + Send + 'static,
+    {
         let is_recording = self.is_recording.clone();
         let audio_data = self.audio_data.clone();
+        let limit_reached = self.limit_reached.clone();
+        let capacity = self.capacity;
 
         let stream = device.build_input_stream(
-            &config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
                 if is_recording.load(Ordering::SeqCst) {
                     let mut buffer = audio_data.lock().unwrap();
-                    buffer.extend_from_slice(data);
+                    if !append_converted(&mut buffer, data, convert, capacity) {
+                        limit_reached.store(true, Ordering::SeqCst);
+                    }
                 }
             },
             |err| log::error!("Audio stream error: {}", err),
             None,
         )?;
 
-        stream.play()?;
-        self.stream = Some(stream);
-
-        log::info!("Recording started");
-        Ok(())
+        Ok(stream)
     }
 
-    pub fn stop_recording(&mut self) -> Result<Vec<f32>> {
+    pub fn stop_recording(&mut self) -> Result<Recording> {
         self.is_recording.store(false, Ordering::SeqCst);
         self.stream = None;
 
-        let data = self.audio_data.lock().unwrap().clone();
-        log::info!("Recording stopped, {} samples captured", data.len());
+        let config = self
+            .config
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Recording was not started"))?;
+
+        let samples = std::mem::take(&mut *self.audio_data.lock().unwrap());
+        let recording = Recording {
+            samples,
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+        };
+        log::info!(
+            "Recording stopped, {} samples captured ({:.1}s at {} Hz, {} channel(s))",
+            recording.samples.len(),
+            recording.duration_secs(),
+            recording.sample_rate,
+            recording.channels
+        );
 
-        Ok(data)
+        Ok(recording)
     }
 
     pub fn is_recording(&self) -> bool {
         self.is_recording.load(Ordering::SeqCst)
     }
 
-    pub fn save_to_wav(&self, samples: &[f32], path: &str) -> Result<()> {
-        // Simple WAV file writer
-        // For production, consider using the `hound` crate
+    /// Handle for reading audio while the recording is still running
+    pub fn tap(&self) -> Option<CaptureTap> {
+        let config = self.config.as_ref()?;
+        Some(CaptureTap {
+            data: self.audio_data.clone(),
+            limit_reached: self.limit_reached.clone(),
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            capacity: self.capacity,
+        })
+    }
+
+    pub fn save_to_wav(&self, recording: &Recording, path: &str) -> Result<()> {
         let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate: 16000,
+            channels: recording.channels,
+            sample_rate: recording.sample_rate,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
 
         let mut writer = hound::WavWriter::create(path, spec)?;
-        for sample in samples {
-            let amplitude = (sample * 32767.0) as i16;
+        for sample in &recording.samples {
+            let amplitude = (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
             writer.write_sample(amplitude)?;
         }
         writer.finalize()?;
@@ -127,8 +323,177 @@ impl AudioCapture {
     }
 }
 
+/// Append `data` to `buffer`, normalizing each sample with `convert`.
+///
+/// Never grows the buffer past `capacity`; returns false if samples were dropped.
+fn append_converted<T: Copy>(
+    buffer: &mut Vec<f32>,
+    data: &[T],
+    convert: fn(T) -> f32,
+    capacity: usize,
+) -> bool {
+    let room = capacity.saturating_sub(buffer.len());
+    let take = data.len().min(room);
+    buffer.reserve(take);
+    buffer.extend(data[..take].iter().map(|&sample| convert(sample)));
+    take == data.len()
+}
+
+/// Raise `is_recording` while `start` opens the stream, so the first callbacks
+/// are kept, and lower it again if opening fails
+fn start_flagged<T>(is_recording: &AtomicBool, start: impl FnOnce() -> Result<T>) -> Result<T> {
+    is_recording.store(true, Ordering::SeqCst);
+    let result = start();
+    if result.is_err() {
+        is_recording.store(false, Ordering::SeqCst);
+    }
+    result
+}
+
+fn f32_to_f32(sample: f32) -> f32 {
+    sample.clamp(-1.0, 1.0)
+}
+
+fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.0
+}
+
+fn u16_to_f32(sample: u16) -> f32 {
+    (sample as f32 - 32768.0) / 32768.0
+}
+
 impl Default for AudioCapture {
     fn default() -> Self {
         Self::new().expect("Failed to initialize audio capture")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_duration() {
+        let recording = Recording {
+            samples: vec![0.0; 44100 * 2],
+            sample_rate: 44100,
+            channels: 2,
+        };
+        assert_eq!(recording.frames(), 44100);
+        assert!((recording.duration_secs() - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_f32_conversion() {
+        assert_eq!(f32_to_f32(0.25), 0.25);
+        assert_eq!(f32_to_f32(-0.75), -0.75);
+        assert_eq!(f32_to_f32(1.5), 1.0);
+        assert_eq!(f32_to_f32(-1.5), -1.0);
+    }
+
+    #[test]
+    fn test_i16_conversion() {
+        assert_eq!(i16_to_f32(0), 0.0);
+        assert_eq!(i16_to_f32(i16::MIN), -1.0);
+        assert_eq!(i16_to_f32(16384), 0.5);
+        assert!((i16_to_f32(i16::MAX) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_u16_conversion() {
+        assert_eq!(u16_to_f32(32768), 0.0);
+        assert_eq!(u16_to_f32(0), -1.0);
+        assert_eq!(u16_to_f32(49152), 0.5);
+        assert!((u16_to_f32(u16::MAX) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_append_converted() {
+        let mut buffer = vec![0.1];
+        assert!(append_converted(&mut buffer, &[i16::MIN, 0, 16384], i16_to_f32, 16));
+        assert_eq!(buffer, vec![0.1, -1.0, 0.0, 0.5]);
+
+        assert!(append_converted(&mut buffer, &[0u16, 32768], u16_to_f32, 16));
+        assert_eq!(buffer, vec![0.1, -1.0, 0.0, 0.5, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_append_stops_at_capacity() {
+        let mut buffer = Vec::new();
+        assert!(append_converted(&mut buffer, &[0.1f32, 0.2], f32_to_f32, 3));
+        assert!(!append_converted(&mut buffer, &[0.3f32, 0.4], f32_to_f32, 3));
+        assert_eq!(buffer, vec![0.1, 0.2, 0.3]);
+        assert!(!append_converted(&mut buffer, &[0.5f32], f32_to_f32, 3));
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[test]
+    fn test_failed_start_is_not_recording() {
+        let is_recording = AtomicBool::new(false);
+        let failed: Result<()> = start_flagged(&is_recording, || {
+            // Callbacks may already see the flag while the stream starts
+            assert!(is_recording.load(Ordering::SeqCst));
+            anyhow::bail!("Device disappeared")
+        });
+        assert!(failed.is_err());
+        assert!(!is_recording.load(Ordering::SeqCst));
+
+        assert_eq!(start_flagged(&is_recording, || Ok(7)).unwrap(), 7);
+        assert!(is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_capacity_from_limits() {
+        let limits = CaptureLimits::default();
+        assert_eq!(limits.capacity(48000, 2), 300 * 48000 * 2);
+
+        // The hard buffer cap wins for high-rate multichannel devices
+        let capacity = limits.capacity(192000, 6);
+        assert!(capacity <= MAX_BUFFER_SAMPLES);
+        assert_eq!(capacity % 6, 0);
+
+        let short = CaptureLimits {
+            max_duration_secs: 10,
+            ..CaptureLimits::default()
+        };
+        assert_eq!(short.capacity(16000, 1), 160000);
+    }
+
+    #[test]
+    fn test_tap_reads_whole_frames() {
+        let tap = CaptureTap {
+            data: Arc::new(std::sync::Mutex::new(vec![0.1, 0.2, 0.3, 0.4, 0.5])),
+            limit_reached: Arc::new(AtomicBool::new(false)),
+            sample_rate: 48000,
+            channels: 2,
+            capacity: 96000,
+        };
+        assert_eq!(tap.read_from(0), vec![0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(tap.read_from(2), vec![0.3, 0.4]);
+        assert!(tap.read_from(6).is_empty());
+        assert!((tap.remaining_secs() - (96000 - 5) as f32 / 96000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_save_to_wav_keeps_format() {
+        let capture = AudioCapture::new().unwrap();
+        let recording = Recording {
+            samples: vec![0.5, -0.5, 0.25, -0.25],
+            sample_rate: 44100,
+            channels: 2,
+        };
+        let dir = crate::fs_util::temp_dir("audio-wav");
+        let path = dir.join("recording.wav");
+        capture
+            .save_to_wav(&recording, path.to_str().unwrap())
+            .unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.channels, 2);
+        assert_eq!(reader.len(), 4);
+        drop(reader);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,592 @@
+//! Offline clean-up of transcripts with fixed rules.
+//!
+//! Used when no language model is available. It drops hesitations and filler
+//! words, collapses stuttered repetitions, adds the commas Polish requires
+//! before common conjunctions, capitalizes sentences and ends the text with a
+//! full stop or question mark. The rules are deterministic, so a transcript
+//! always comes out the same way.
+
+use crate::glossary::split_punctuation;
+use crate::postprocess::{PostProcessor, Request};
+use crate::transcription::Language;
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Hesitation sounds that are not words in any language, after collapsing
+/// repeated letters ("yyy" → "y")
+const HESITATIONS: &[&str] = &["e", "y", "m", "ehm", "yhm", "hm", "mhm", "äh", "ähm"];
+
+/// Words per language that need context to be recognised as fillers
+struct Lexicon {
+    /// Hesitation sounds that are words in other languages ("um" and "er" in German)
+    hesitations: &'static [&'static str],
+    /// Fillers at the start of a sentence or between commas
+    discourse: &'static [&'static str],
+    /// Conjunctions that take a comma before them
+    comma_before: &'static [&'static str],
+    /// Words the comma goes in front of instead of the conjunction ("mimo że")
+    comma_moves_before: &'static [&'static str],
+    /// Conjunctions that already join the clauses, so no comma is added after them
+    joining: &'static [&'static str],
+    /// "czy" only takes a comma after a verb; these are the common ones
+    czy_after: &'static [&'static str],
+    greetings: &'static [&'static [&'static str]],
+    /// Words that make a sentence a question when they start it
+    questions: &'static [&'static str],
+    /// Words that make a sentence a question when they start it followed by a subject
+    auxiliaries: &'static [&'static str],
+    subjects: &'static [&'static str],
+    /// Words that are always capitalized
+    proper: &'static [&'static str],
+    /// The pronoun "I" and its contractions
+    capitalize_i: bool,
+}
+
+const POLISH: Lexicon = Lexicon {
+    hesitations: &["em"],
+    discourse: &["no", "więc", "właśnie", "znaczy", "jakby", "generalnie"],
+    comma_before: &[
+        "że",
+        "żeby",
+        "aby",
+        "bo",
+        "ale",
+        "lecz",
+        "ponieważ",
+        "gdyż",
+        "jeśli",
+        "jeżeli",
+        "gdy",
+        "chociaż",
+        "choć",
+        "który",
+        "która",
+        "które",
+        "którzy",
+        "którego",
+        "której",
+        "któremu",
+        "którym",
+        "których",
+        "którą",
+    ],
+    comma_moves_before: &[
+        "mimo",
+        "chyba",
+        "tylko",
+        "zwłaszcza",
+        "w",
+        "we",
+        "z",
+        "ze",
+        "na",
+        "do",
+        "o",
+        "od",
+        "po",
+        "przy",
+        "za",
+        "przez",
+        "dla",
+        "bez",
+        "u",
+        "pod",
+        "nad",
+        "przed",
+        "między",
+    ],
+    joining: &["i", "a", "oraz", "lub", "albo", "ani"],
+    czy_after: &[
+        "wiem", "wiesz", "wie", "pytam", "pyta", "sprawdź", "zobacz", "powiedz", "ciekawe", "się",
+    ],
+    greetings: &[
+        &["cześć"],
+        &["hej"],
+        &["witam"],
+        &["witaj"],
+        &["siema"],
+        &["dzień", "dobry"],
+        &["dobry", "wieczór"],
+    ],
+    questions: &["czy", "dlaczego", "czemu", "gdzie", "kto", "ile"],
+    auxiliaries: &[],
+    subjects: &[],
+    proper: &[],
+    capitalize_i: false,
+};
+
+const ENGLISH: Lexicon = Lexicon {
+    hesitations: &["um", "uh", "er", "erm"],
+    discourse: &["so", "basically", "like", "well"],
+    comma_before: &[],
+    comma_moves_before: &[],
+    joining: &[],
+    czy_after: &[],
+    greetings: &[
+        &["hi"],
+        &["hello"],
+        &["hey"],
+        &["good", "morning"],
+        &["good", "afternoon"],
+        &["good", "evening"],
+    ],
+    questions: &["who", "what", "where", "why", "how", "which", "whose"],
+    auxiliaries: &[
+        "is", "are", "am", "was", "were", "do", "does", "did", "can", "could", "would", "will",
+        "should", "shall", "have", "has",
+    ],
+    // Not "it" or "this": "do it now" is an instruction
+    subjects: &[
+        "i", "you", "we", "they", "he", "she", "there", "anyone", "someone",
+    ],
+    // "march" and "may" are left out, they are ordinary words too
+    proper: &[
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+        "january",
+        "february",
+        "april",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ],
+    capitalize_i: true,
+};
+
+impl Lexicon {
+    fn for_language(language: Language) -> Option<&'static Lexicon> {
+        match language.code() {
+            "pl" => Some(&POLISH),
+            "en" => Some(&ENGLISH),
+            _ => None,
+        }
+    }
+}
+
+/// Cleans transcripts with `clean`; needs no network and never fails.
+/// Translation and output templates need a language model and are ignored.
+pub struct RuleCleaner;
+
+#[async_trait]
+impl PostProcessor for RuleCleaner {
+    fn name(&self) -> &str {
+        "offline cleaner"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    async fn process(&self, request: &Request<'_>) -> Result<String> {
+        Ok(clean(request.text, request.language))
+    }
+}
+
+/// A word with the punctuation around it
+#[derive(Debug, Clone, Default)]
+struct Token {
+    lead: String,
+    word: String,
+    trail: String,
+}
+
+impl Token {
+    fn lower(&self) -> String {
+        self.word.to_lowercase()
+    }
+
+    fn ends_sentence(&self) -> bool {
+        self.trail.contains(['.', '?', '!', '…'])
+    }
+
+    fn has_comma(&self) -> bool {
+        self.trail.contains(',')
+    }
+
+    fn is_word(&self) -> bool {
+        self.word.chars().any(char::is_alphabetic)
+    }
+}
+
+/// Clean up a transcript in `language`; unknown languages only get the
+/// language-independent rules
+pub fn clean(text: &str, language: Language) -> String {
+    let lexicon = Lexicon::for_language(language);
+    let mut tokens = remove_fillers(tokenize(text), lexicon);
+    collapse_repetitions(&mut tokens);
+    if let Some(lexicon) = lexicon {
+        add_commas(&mut tokens, lexicon);
+    }
+    finish(&mut tokens, lexicon);
+    capitalize(&mut tokens, lexicon);
+
+    tokens
+        .iter()
+        .map(|t| format!("{}{}{}", t.lead, t.word, t.trail))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for chunk in text.split_whitespace() {
+        let (lead, word, trail) = split_punctuation(chunk);
+        // Punctuation Whisper left standing on its own belongs to the word before it
+        let detached = word.is_empty() && chunk.chars().all(|c| ",.?!;:…".contains(c));
+        match tokens.last_mut() {
+            Some(last) if detached => last.trail.push_str(chunk),
+            _ => tokens.push(Token {
+                lead: lead.to_string(),
+                word: word.to_string(),
+                trail: trail.to_string(),
+            }),
+        }
+    }
+    tokens
+}
+
+fn is_hesitation(word: &str, lexicon: Option<&Lexicon>) -> bool {
+    let mut collapsed = String::new();
+    for c in word.to_lowercase().chars() {
+        if !collapsed.ends_with(c) {
+            collapsed.push(c);
+        }
+    }
+    let collapsed = collapsed.as_str();
+    let single_letter = collapsed.chars().count() == 1;
+    let known = HESITATIONS.contains(&collapsed)
+        || lexicon.is_some_and(|l| l.hesitations.contains(&collapsed));
+    known && (!single_letter || word.chars().count() > 1)
+}
+
+fn is_discourse(word: &str, lexicon: Option<&Lexicon>) -> bool {
+    lexicon.is_some_and(|l| l.discourse.contains(&word.to_lowercase().as_str()))
+}
+
+/// Drop hesitations anywhere and discourse fillers where the context marks them as such
+fn remove_fillers(tokens: Vec<Token>, lexicon: Option<&Lexicon>) -> Vec<Token> {
+    let mut kept: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut sentence_start = true;
+    // A filler was just dropped from the start of the sentence
+    let mut in_fillers = false;
+    let mut pending_lead = String::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let next_is_filler = tokens
+            .get(i + 1)
+            .is_some_and(|n| is_hesitation(&n.word, lexicon) || is_discourse(&n.word, lexicon));
+        let previous_comma = kept.last().is_some_and(|t| t.has_comma());
+
+        let filler = is_hesitation(&token.word, lexicon)
+            || (is_discourse(&token.word, lexicon)
+                && if sentence_start {
+                    token.has_comma() || in_fillers || next_is_filler
+                } else {
+                    token.has_comma() && previous_comma
+                });
+
+        if !filler {
+            let mut token = token.clone();
+            token.lead = std::mem::take(&mut pending_lead) + &token.lead;
+            sentence_start = token.ends_sentence();
+            in_fillers = false;
+            kept.push(token);
+            continue;
+        }
+
+        pending_lead.push_str(&token.lead);
+        if token.ends_sentence() {
+            // Keep the sentence end the filler carried
+            if let Some(last) = kept.last_mut().filter(|t| !t.ends_sentence()) {
+                last.trail.retain(|c| c != ',');
+                last.trail.extend(token.trail.chars().filter(|c| *c != ','));
+            }
+            sentence_start = true;
+            in_fillers = false;
+        } else if sentence_start {
+            in_fillers = true;
+        } else if token.has_comma() && previous_comma {
+            // The commas only set the filler off
+            if let Some(last) = kept.last_mut() {
+                last.trail.retain(|c| c != ',');
+            }
+        }
+    }
+    kept
+}
+
+/// Remove stuttered repetitions of one to three words ("we need we need to")
+fn collapse_repetitions(tokens: &mut Vec<Token>) {
+    'restart: loop {
+        for n in 1..=3 {
+            for i in 0..(tokens.len() + 1).saturating_sub(2 * n) {
+                let first = &tokens[i..i + n];
+                let second = &tokens[i + n..i + 2 * n];
+                let repeated = first
+                    .iter()
+                    .zip(second)
+                    .all(|(a, b)| a.is_word() && a.lower() == b.lower() && !a.ends_sentence());
+                // "20 20" may be a year
+                let numeric = n == 1 && first[0].word.chars().any(|c| c.is_ascii_digit());
+                if repeated && !numeric {
+                    let lead = std::mem::take(&mut tokens[i].lead);
+                    tokens.drain(i..i + n);
+                    tokens[i].lead = lead + &tokens[i].lead;
+                    continue 'restart;
+                }
+            }
+        }
+        return;
+    }
+}
+
+/// Commas before subordinating conjunctions and after greetings
+fn add_commas(tokens: &mut [Token], lexicon: &Lexicon) {
+    for i in 1..tokens.len() {
+        let word = tokens[i].lower();
+        let previous = tokens[i - 1].lower();
+        let takes_comma = lexicon.comma_before.contains(&word.as_str())
+            || (word == "czy"
+                && (previous.ends_with('ć') || lexicon.czy_after.contains(&previous.as_str())));
+        if !takes_comma {
+            continue;
+        }
+
+        let at = if lexicon.comma_moves_before.contains(&previous.as_str()) {
+            match i.checked_sub(2) {
+                Some(at) => at,
+                None => continue,
+            }
+        } else {
+            i - 1
+        };
+        let target = tokens[at].lower();
+        if tokens[at].trail.is_empty()
+            && tokens[at].is_word()
+            && !lexicon.joining.contains(&target.as_str())
+            && !lexicon.comma_before.contains(&target.as_str())
+        {
+            tokens[at].trail.push(',');
+        }
+    }
+
+    for start in sentence_starts(tokens) {
+        let Some(len) = greeting_len(&tokens[start..], lexicon) else {
+            continue;
+        };
+        let last = start + len - 1;
+        if last + 1 < tokens.len() && tokens[last].trail.is_empty() {
+            tokens[last].trail.push(',');
+        }
+    }
+}
+
+/// Indices of the tokens that start a sentence
+fn sentence_starts(tokens: &[Token]) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| t.ends_sentence() && i + 1 < tokens.len())
+            .map(|(i, _)| i + 1),
+    );
+    starts.retain(|&i| i < tokens.len());
+    starts
+}
+
+/// Number of words of the greeting `tokens` start with
+fn greeting_len(tokens: &[Token], lexicon: &Lexicon) -> Option<usize> {
+    lexicon
+        .greetings
+        .iter()
+        .find(|greeting| {
+            greeting.len() <= tokens.len()
+                && greeting
+                    .iter()
+                    .zip(tokens)
+                    .all(|(word, token)| token.lower() == *word)
+        })
+        .map(|greeting| greeting.len())
+}
+
+/// End the text with a full stop, or a question mark for a question
+fn finish(tokens: &mut [Token], lexicon: Option<&Lexicon>) {
+    let Some(start) = sentence_starts(tokens).last().copied() else {
+        return;
+    };
+    let question = lexicon.is_some_and(|l| is_question(&tokens[start..], l));
+    let Some(last) = tokens.last_mut() else {
+        return;
+    };
+    if last.ends_sentence() {
+        return;
+    }
+
+    last.trail.retain(|c| !",;:-".contains(c));
+    let mark = if question { '?' } else { '.' };
+    // Before closing quotes and brackets
+    match last.trail.find([')', '"', '”', '»', '\'']) {
+        Some(at) => last.trail.insert(at, mark),
+        None => last.trail.push(mark),
+    }
+}
+
+fn is_question(sentence: &[Token], lexicon: &Lexicon) -> bool {
+    let skip = greeting_len(sentence, lexicon).unwrap_or(0);
+    let mut words = sentence[skip..].iter().map(Token::lower);
+    let Some(first) = words.next() else {
+        return false;
+    };
+    lexicon.questions.contains(&first.as_str())
+        || (lexicon.auxiliaries.contains(&first.as_str())
+            && words
+                .next()
+                .is_some_and(|next| lexicon.subjects.contains(&next.as_str())))
+}
+
+fn capitalize(tokens: &mut [Token], lexicon: Option<&Lexicon>) {
+    for start in sentence_starts(tokens) {
+        if let Some(token) = tokens[start..].iter_mut().find(|t| t.is_word()) {
+            token.word = capitalized(&token.word);
+        }
+    }
+
+    let Some(lexicon) = lexicon else {
+        return;
+    };
+    for token in tokens.iter_mut() {
+        let lower = token.lower();
+        let pronoun = lexicon.capitalize_i
+            && (lower == "i"
+                || ["i'", "i’"]
+                    .iter()
+                    .any(|p| lower.starts_with(p) && lower.chars().count() <= 4));
+        if pronoun || lexicon.proper.contains(&lower.as_str()) {
+            token.word = capitalized(&token.word);
+        }
+    }
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Golden files: `<language>/<case>.txt` is a raw transcript and
+    /// `<case>.golden.txt` what the cleaner should make of it. Run with
+    /// `UPDATE_GOLDEN=1` to rewrite the golden files after changing a rule.
+    fn golden_cases() -> Vec<(Language, PathBuf, PathBuf)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/cleaner");
+        let mut cases = Vec::new();
+        for dir in fs::read_dir(&root).unwrap() {
+            let dir = dir.unwrap().path();
+            let language = Language::from_code(&dir.file_name().unwrap().to_string_lossy());
+            for file in fs::read_dir(&dir).unwrap() {
+                let input = file.unwrap().path();
+                let name = input.file_name().unwrap().to_string_lossy().into_owned();
+                if let Some(case) = name
+                    .strip_suffix(".txt")
+                    .filter(|n| !n.ends_with(".golden"))
+                {
+                    cases.push((
+                        language,
+                        input.clone(),
+                        dir.join(format!("{}.golden.txt", case)),
+                    ));
+                }
+            }
+        }
+        cases.sort_by(|a, b| a.1.cmp(&b.1));
+        cases
+    }
+
+    #[test]
+    fn test_golden_files() {
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let cases = golden_cases();
+        assert!(!cases.is_empty());
+
+        for (language, input, golden) in cases {
+            let cleaned = clean(fs::read_to_string(&input).unwrap().trim(), language);
+            if update {
+                fs::write(&golden, format!("{}\n", cleaned)).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&golden)
+                .unwrap_or_else(|_| panic!("missing {}", golden.display()));
+            assert_eq!(cleaned, expected.trim(), "{}", input.display());
+        }
+    }
+
+    #[test]
+    fn test_clean_is_idempotent() {
+        for (language, input, _) in golden_cases() {
+            let once = clean(fs::read_to_string(&input).unwrap().trim(), language);
+            assert_eq!(clean(&once, language), once, "{}", input.display());
+        }
+    }
+
+    #[test]
+    fn test_hesitations() {
+        for word in ["ee", "eee", "Yyy", "hmm", "mhm", "ehm", "ähm"] {
+            assert!(is_hesitation(word, None), "{}", word);
+        }
+        for word in [
+            "e", "y", "i", "yes", "umbrella", "he", "me", "um", "er", "em",
+        ] {
+            assert!(!is_hesitation(word, None), "{}", word);
+        }
+
+        let english = Lexicon::for_language(Language::Known("en"));
+        for word in ["um", "ummm", "uh", "er", "erm"] {
+            assert!(is_hesitation(word, english), "{}", word);
+        }
+        let polish = Lexicon::for_language(Language::Known("pl"));
+        assert!(is_hesitation("em", polish));
+        assert!(!is_hesitation("um", polish));
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert_eq!(clean("", Language::Known("pl")), "");
+        assert_eq!(clean("eee yyy", Language::Known("pl")), "");
+        assert_eq!(clean("  ok  ", Language::Unknown), "Ok.");
+        assert_eq!(clean("gotowe ,", Language::Known("pl")), "Gotowe.");
+        assert_eq!(clean("(tak tak", Language::Known("pl")), "(Tak.");
+    }
+
+    #[tokio::test]
+    async fn test_cleaner_ignores_translation_and_template() {
+        let template = crate::templates::builtin()
+            .into_iter()
+            .find(|t| t.id == "bullet-notes")
+            .unwrap();
+        let request = Request {
+            text: "eee dobra",
+            language: Language::Known("pl"),
+            translate_to: Some(Language::Known("en")),
+            template: &template,
+        };
+        assert!(RuleCleaner.is_available());
+        assert_eq!(RuleCleaner.process(&request).await.unwrap(), "Dobra.");
+    }
+}
@@ -0,0 +1,953 @@
+//! Tauri commands invoked from the frontend.
+//!
+//! Every command returns `Result<_, CommandError>`; the error serializes to
+//! `{ "kind": "...", "message": "..." }` so the UI can branch on `kind`.
+
+use crate::active_model::{ModelState, ModelStatus};
+use crate::autostart;
+use crate::foreground::ForegroundApp;
+use crate::glossary::{self, CompiledGlossary, Glossary};
+use crate::hotkey::{self, RecordingState};
+use crate::models::{self, ModelEntry, ModelInfo, ModelManager};
+use crate::pipeline::PipelineState;
+use crate::postprocess::{PostProcessors, Provider};
+use crate::settings::{Settings, SettingsStore};
+use crate::shortcut::{self, Hotkey, ShortcutError};
+use crate::templates::{Template, Templates, DEFAULT_TEMPLATE};
+use crate::transcription::Language;
+use crate::tray;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Device id the frontend uses for "follow the system default"
+const DEFAULT_DEVICE_ID: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum CommandError {
+    /// The request itself was malformed
+    InvalidInput(String),
+    /// Audio devices could not be enumerated or opened
+    Audio(String),
+    DeviceNotFound(String),
+    /// The command does not apply in the current recording state
+    Recording(String),
+    Autostart(String),
+    /// Settings could not be validated or saved
+    Settings(String),
+    /// The secret store could not be read or written
+    Credentials(String),
+    /// A shortcut could not be parsed or registered
+    Shortcut(ShortcutError),
+    /// A Whisper model could not be downloaded or loaded
+    Model(String),
+    /// The app in the foreground could not be detected
+    Foreground(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            CommandError::Audio(msg) => write!(f, "Audio error: {}", msg),
+            CommandError::DeviceNotFound(name) => write!(f, "Device not found: {}", name),
+            CommandError::Recording(msg) => write!(f, "Recording error: {}", msg),
+            CommandError::Autostart(msg) => write!(f, "Autostart error: {}", msg),
+            CommandError::Settings(msg) => write!(f, "Settings error: {}", msg),
+            CommandError::Credentials(msg) => write!(f, "Credential storage error: {}", msg),
+            CommandError::Shortcut(e) => write!(f, "{}", e),
+            CommandError::Model(msg) => write!(f, "Model error: {}", msg),
+            CommandError::Foreground(msg) => write!(f, "App detection error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<ShortcutError> for CommandError {
+    fn from(e: ShortcutError) -> Self {
+        CommandError::Shortcut(e)
+    }
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+type RecordingStateHandle<'a> = State<'a, Arc<Mutex<RecordingState>>>;
+type SettingsHandle<'a> = State<'a, Arc<Mutex<SettingsStore>>>;
+type ModelsHandle<'a> = State<'a, Arc<ModelManager>>;
+type PostProcessorsHandle<'a> = State<'a, Arc<Mutex<PostProcessors>>>;
+type TemplatesHandle<'a> = State<'a, Arc<Mutex<Templates>>>;
+
+/// Persist a partial settings update and tell every window about it
+fn persist_settings(
+    app: &AppHandle,
+    store: &Mutex<SettingsStore>,
+    patch: Value,
+) -> CommandResult<Settings> {
+    let settings = store
+        .lock()
+        .unwrap()
+        .update(&patch)
+        .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+    let _ = app.emit("settings-changed", &settings);
+    Ok(settings)
+}
+
+/// Register `hotkey` in place of the current recording shortcut, then save it
+fn rebind_hotkey(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    store: &Mutex<SettingsStore>,
+    hotkey: &str,
+) -> CommandResult<Settings> {
+    let new: Hotkey = hotkey.parse()?;
+    shortcut::rebind(app, state, &new)?;
+    persist_settings(app, store, serde_json::json!({ "hotkey": new.to_string() }))
+}
+
+/// Register the template shortcuts in `hotkeys` in place of the current ones, then save them
+fn rebind_template_hotkeys(
+    app: &AppHandle,
+    store: &Mutex<SettingsStore>,
+    hotkeys: &BTreeMap<String, String>,
+) -> CommandResult<Settings> {
+    let canonical: BTreeMap<String, String> = shortcut::parse_template_hotkeys(hotkeys)?
+        .into_iter()
+        .map(|(id, hotkey)| (id, hotkey.to_string()))
+        .collect();
+    let patch = serde_json::json!({ "templateHotkeys": canonical });
+
+    // Catch clashes with the recording shortcut before touching the registered ones
+    let current = store.lock().unwrap().get().clone();
+    current
+        .merged(&patch)
+        .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+    shortcut::rebind_templates(app, &current.template_hotkeys, &canonical)?;
+
+    persist_settings(app, store, patch)
+}
+
+/// Steps to undo the side effects of a settings update that failed halfway
+#[derive(Default)]
+struct Rollback(Vec<Box<dyn FnOnce()>>);
+
+impl Rollback {
+    fn push(&mut self, undo: impl FnOnce() + 'static) {
+        self.0.push(Box::new(undo));
+    }
+
+    /// Undo the steps, last one first
+    fn run(self) {
+        for undo in self.0.into_iter().rev() {
+            undo();
+        }
+    }
+}
+
+/// Register `hotkey` as the recording shortcut, and the template shortcuts and
+/// autostart entry of `updated` in place of those of `current`; `rollback`
+/// learns how to restore each one
+fn register_changes(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    hotkey: Option<Hotkey>,
+    current: &Settings,
+    updated: &Settings,
+    rollback: &mut Rollback,
+) -> CommandResult<()> {
+    // Compared with the registered shortcut, which differs from the saved one
+    // if that was unavailable at startup
+    let registered = state.lock().unwrap().hotkey;
+    if let Some(new) = hotkey.filter(|new| registered != Some(*new)) {
+        shortcut::rebind(app, state, &new)?;
+        let (app, state) = (app.clone(), state.clone());
+        rollback.push(move || match registered {
+            Some(old) => {
+                if let Err(e) = shortcut::rebind(&app, &state, &old) {
+                    log::warn!("Failed to restore recording shortcut {}: {}", old, e);
+                }
+            }
+            None => shortcut::unregister(&app, &state),
+        });
+    }
+
+    if updated.template_hotkeys != current.template_hotkeys {
+        shortcut::rebind_templates(app, &current.template_hotkeys, &updated.template_hotkeys)?;
+        let app = app.clone();
+        let (old, new) = (current.template_hotkeys.clone(), updated.template_hotkeys.clone());
+        rollback.push(move || {
+            if let Err(e) = shortcut::rebind_templates(&app, &new, &old) {
+                log::warn!("Failed to restore template shortcuts: {}", e);
+            }
+        });
+    }
+
+    if let Some(enabled) = updated.autostart.filter(|_| updated.autostart != current.autostart) {
+        toggle_autostart(enabled)?;
+        rollback.push(move || {
+            if let Err(e) = toggle_autostart(!enabled) {
+                log::warn!("Failed to restore autostart: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Make `id` the template recordings are post-processed with.
+///
+/// Used by the settings window, the tray menu and the template shortcuts.
+pub fn activate_template(app: &AppHandle, id: &str) -> CommandResult<Settings> {
+    let templates = app.state::<Arc<Mutex<Templates>>>();
+    if templates.lock().unwrap().get(id).is_none() {
+        return Err(CommandError::InvalidInput(format!("Unknown template: {}", id)));
+    }
+
+    let store = app.state::<Arc<Mutex<SettingsStore>>>();
+    let settings = persist_settings(app, &store, serde_json::json!({ "template": id }))?;
+    let state = app.state::<Arc<Mutex<RecordingState>>>();
+    state.lock().unwrap().template = settings.template.clone();
+    tray::refresh(app);
+
+    log::info!("Output template: {}", id);
+    Ok(settings)
+}
+
+fn find_model(id: &str) -> CommandResult<&'static ModelInfo> {
+    models::find(id)
+        .ok_or_else(|| CommandError::InvalidInput(format!("Unknown model: {}", id)))
+}
+
+/// Load the model at `path`, make it the one recordings use and save `patch`.
+///
+/// Jobs already queued on the old worker still finish with the old model. If
+/// the new model cannot be loaded the old one stays active.
+async fn switch_model(
+    app: &AppHandle,
+    state: &Mutex<RecordingState>,
+    settings: &Mutex<SettingsStore>,
+    path: PathBuf,
+    patch: Value,
+) -> CommandResult<ModelStatus> {
+    let model = state.lock().unwrap().model.clone();
+    model
+        .switch_to(path)
+        .await
+        .map_err(|e| CommandError::Model(format!("{:#}", e)))?;
+
+    persist_settings(app, settings, patch)?;
+    Ok(model.status())
+}
+
+/// Switch to a model from the catalog
+async fn activate_model(
+    app: &AppHandle,
+    state: &Mutex<RecordingState>,
+    models: &ModelManager,
+    settings: &Mutex<SettingsStore>,
+    info: &'static ModelInfo,
+) -> CommandResult<ModelStatus> {
+    let patch = serde_json::json!({ "model": info.id, "modelPath": null });
+    switch_model(app, state, settings, models.path(info), patch).await
+}
+
+/// Tell the frontend about a model switch nobody awaits; the current model stays loaded
+fn report_switch_failure(app: &AppHandle, path: &Path, error: &CommandError) {
+    log::warn!("Failed to switch model: {}", error);
+    let message = match error {
+        CommandError::Model(message) => message.clone(),
+        other => other.to_string(),
+    };
+    let status = ModelStatus {
+        state: ModelState::Failed(message),
+        path: path.to_string_lossy().to_string(),
+    };
+    let _ = app.emit("model-status", status);
+}
+
+/// Add or remove the system autostart entry
+fn toggle_autostart(enabled: bool) -> CommandResult<()> {
+    let result = if enabled {
+        autostart::enable_autostart()
+    } else {
+        autostart::disable_autostart()
+    };
+    result.map_err(|e| CommandError::Autostart(e.to_string()))
+}
+
+/// Validate and store an API key for `provider`; empty clears it
+fn store_api_key(
+    processors: &Mutex<PostProcessors>,
+    provider: Provider,
+    key: &str,
+) -> CommandResult<ApiKeyStatus> {
+    let key = key.trim();
+    if key.chars().any(char::is_whitespace) {
+        return Err(CommandError::InvalidInput(
+            "API key must not contain whitespace".to_string(),
+        ));
+    }
+
+    if matches!(provider, Provider::None | Provider::Local) {
+        return Err(CommandError::InvalidInput(format!(
+            "{:?} post-processing does not use an API key",
+            provider
+        )));
+    }
+
+    let mut processors = processors.lock().unwrap();
+    processors
+        .set_api_key(provider, key)
+        .map_err(|e| CommandError::Credentials(format!("{:#}", e)))?;
+    Ok(ApiKeyStatus {
+        configured: processors.has_api_key(provider),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDeviceList {
+    pub devices: Vec<AudioDevice>,
+    /// Id of the device recordings currently use
+    pub selected: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectDeviceRequest {
+    /// Device name, or `"default"` to follow the system default
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyRequest {
+    /// New key; empty clears it
+    pub api_key: String,
+    /// Provider the key is for; DeepSeek if not given
+    #[serde(default)]
+    pub provider: Option<Provider>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyStatusRequest {
+    pub provider: Provider,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyStatus {
+    pub configured: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyRequest {
+    /// Shortcut such as "Ctrl+Alt+D"
+    pub hotkey: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyStatus {
+    /// Canonical form of the registered shortcut
+    pub hotkey: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateRequest {
+    /// Template id such as "email"
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStatus {
+    pub is_recording: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelRequest {
+    /// Catalog id such as "small" or "large-v3-q5_0"
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPathRequest {
+    /// A ggml model file anywhere on disk
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutostartRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutostartStatus {
+    pub enabled: bool,
+}
+
+/// List input devices, marking the system default
+#[tauri::command]
+pub fn list_audio_devices(state: RecordingStateHandle<'_>) -> CommandResult<AudioDeviceList> {
+    let audio_capture = state.lock().unwrap().audio_capture.clone();
+    let audio_capture = audio_capture.lock().unwrap();
+
+    let default_name = audio_capture.default_device_name();
+    let devices = audio_capture
+        .list_devices()
+        .map_err(|e| CommandError::Audio(e.to_string()))?
+        .into_iter()
+        .map(|name| AudioDevice {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            id: name.clone(),
+            name,
+        })
+        .collect();
+
+    Ok(AudioDeviceList {
+        devices,
+        selected: audio_capture
+            .selected_device_name()
+            .unwrap_or_else(|| DEFAULT_DEVICE_ID.to_string()),
+    })
+}
+
+/// Choose the input device used by the next recording
+#[tauri::command]
+pub fn select_audio_device(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: SelectDeviceRequest,
+) -> CommandResult<()> {
+    {
+        let recording_state = state.lock().unwrap();
+        if recording_state.is_recording() {
+            return Err(CommandError::Recording(
+                "Cannot change microphone while recording".to_string(),
+            ));
+        }
+
+        let mut audio_capture = recording_state.audio_capture.lock().unwrap();
+        if request.id == DEFAULT_DEVICE_ID {
+            audio_capture.use_default_device();
+        } else {
+            audio_capture
+                .select_device(&request.id)
+                .map_err(|_| CommandError::DeviceNotFound(request.id.clone()))?;
+        }
+    }
+
+    persist_settings(&app, &settings, serde_json::json!({ "microphone": request.id }))?;
+    Ok(())
+}
+
+/// Set or clear a provider's API key (kept in the secret store, never in settings)
+#[tauri::command]
+pub fn set_api_key(
+    processors: PostProcessorsHandle<'_>,
+    request: ApiKeyRequest,
+) -> CommandResult<ApiKeyStatus> {
+    let provider = request.provider.unwrap_or(Provider::DeepSeek);
+    store_api_key(&processors, provider, &request.api_key)
+}
+
+/// Whether a provider has an API key; without a request, the selected provider
+#[tauri::command]
+pub fn get_api_key_status(
+    processors: PostProcessorsHandle<'_>,
+    request: Option<ApiKeyStatusRequest>,
+) -> CommandResult<ApiKeyStatus> {
+    let processors = processors.lock().unwrap();
+    let provider = request.map_or(processors.provider(), |r| r.provider);
+    Ok(ApiKeyStatus {
+        configured: processors.has_api_key(provider),
+    })
+}
+
+#[tauri::command]
+pub fn start_recording(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+) -> CommandResult<RecordingStatus> {
+    let mut recording_state = state.lock().unwrap();
+    hotkey::request_start(&app, &state, &mut recording_state)
+        .map_err(|e| CommandError::Recording(e.to_string()))?;
+
+    Ok(RecordingStatus {
+        is_recording: recording_state.is_recording(),
+    })
+}
+
+/// Stop recording and run the transcription pipeline
+#[tauri::command]
+pub fn stop_recording(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+) -> CommandResult<RecordingStatus> {
+    let mut recording_state = state.lock().unwrap();
+    hotkey::stop_recording(&app, &state, &mut recording_state)
+        .map_err(|e| CommandError::Recording(e.to_string()))?;
+
+    Ok(RecordingStatus {
+        is_recording: recording_state.is_recording(),
+    })
+}
+
+/// Stop the active recording and discard the audio
+#[tauri::command]
+pub fn cancel_recording(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+) -> CommandResult<RecordingStatus> {
+    let mut recording_state = state.lock().unwrap();
+    hotkey::cancel_recording(&app, &state, &mut recording_state);
+
+    Ok(RecordingStatus {
+        is_recording: recording_state.is_recording(),
+    })
+}
+
+#[tauri::command]
+pub fn get_recording_status(state: RecordingStateHandle<'_>) -> CommandResult<RecordingStatus> {
+    Ok(RecordingStatus {
+        is_recording: state.lock().unwrap().is_recording(),
+    })
+}
+
+/// Current stage of the record → transcribe → deliver pipeline
+#[tauri::command]
+pub fn get_pipeline_state(state: RecordingStateHandle<'_>) -> CommandResult<PipelineState> {
+    Ok(state.lock().unwrap().pipeline.state().clone())
+}
+
+/// Codes of every language Whisper can transcribe
+#[tauri::command]
+pub fn list_languages() -> CommandResult<Vec<Language>> {
+    Ok(Language::all())
+}
+
+/// The user's glossary
+#[tauri::command]
+pub fn get_glossary(state: RecordingStateHandle<'_>) -> CommandResult<Glossary> {
+    Ok(state.lock().unwrap().glossary.source().clone())
+}
+
+/// Replace the glossary; used from the next recording on
+#[tauri::command]
+pub fn set_glossary(state: RecordingStateHandle<'_>, request: Glossary) -> CommandResult<Glossary> {
+    let compiled = CompiledGlossary::new(request)
+        .map_err(|e| CommandError::InvalidInput(format!("{:#}", e)))?;
+    glossary::save(&glossary::default_path(), compiled.source())
+        .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+
+    let glossary = compiled.source().clone();
+    state.lock().unwrap().glossary = Arc::new(compiled);
+    Ok(glossary)
+}
+
+/// Output templates, the default one first
+#[tauri::command]
+pub fn list_templates(templates: TemplatesHandle<'_>) -> CommandResult<Vec<Template>> {
+    Ok(templates.lock().unwrap().all().to_vec())
+}
+
+/// Create or replace a template; a new one (empty id) gets an id from its name
+#[tauri::command]
+pub fn save_template(
+    app: AppHandle,
+    templates: TemplatesHandle<'_>,
+    mut request: Template,
+) -> CommandResult<Template> {
+    {
+        let mut templates = templates.lock().unwrap();
+        if request.id.is_empty() {
+            request.id = templates.unused_id(&request.name);
+        }
+        request
+            .validate()
+            .map_err(|e| CommandError::InvalidInput(format!("{:#}", e)))?;
+        templates
+            .save(request.clone())
+            .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+    }
+
+    tray::refresh(&app);
+    Ok(request)
+}
+
+/// Delete a template along with its shortcut. Deleting the active template
+/// switches to the default one.
+#[tauri::command]
+pub fn delete_template(
+    app: AppHandle,
+    templates: TemplatesHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: TemplateRequest,
+) -> CommandResult<Settings> {
+    templates
+        .lock()
+        .unwrap()
+        .delete(&request.id)
+        .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+
+    let mut updated = settings.lock().unwrap().get().clone();
+    if updated.template_hotkeys.contains_key(&request.id) {
+        let mut hotkeys = updated.template_hotkeys.clone();
+        hotkeys.remove(&request.id);
+        updated = rebind_template_hotkeys(&app, &settings, &hotkeys)?;
+    }
+    if updated.template == request.id {
+        updated = activate_template(&app, DEFAULT_TEMPLATE)?;
+    } else {
+        tray::refresh(&app);
+    }
+    Ok(updated)
+}
+
+/// Switch the template recordings are post-processed with
+#[tauri::command]
+pub fn select_template(app: AppHandle, request: TemplateRequest) -> CommandResult<Settings> {
+    activate_template(&app, &request.id)
+}
+
+/// The app in the foreground right now, for writing app rules
+#[tauri::command]
+pub async fn get_foreground_app(
+    state: RecordingStateHandle<'_>,
+) -> CommandResult<Option<ForegroundApp>> {
+    let detector = state.lock().unwrap().foreground.clone();
+    tauri::async_runtime::spawn_blocking(move || detector.foreground_app())
+        .await
+        .map_err(|e| CommandError::Foreground(e.to_string()))?
+        .map_err(|e| CommandError::Foreground(format!("{:#}", e)))
+}
+
+/// Whether the Whisper model is loaded, missing or failed to load
+#[tauri::command]
+pub fn get_model_status(state: RecordingStateHandle<'_>) -> CommandResult<ModelStatus> {
+    Ok(state.lock().unwrap().model.status())
+}
+
+/// Load the model now instead of on first use, e.g. to retry after a failure
+#[tauri::command]
+pub async fn load_model(state: RecordingStateHandle<'_>) -> CommandResult<ModelStatus> {
+    let model = state.lock().unwrap().model.clone();
+    model
+        .load()
+        .await
+        .map_err(|e| CommandError::Model(format!("{:#}", e)))?;
+    Ok(model.status())
+}
+
+/// Use a model file outside the catalog
+#[tauri::command]
+pub async fn set_model_path(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: ModelPathRequest,
+) -> CommandResult<ModelStatus> {
+    let path = request.path.trim();
+    if path.is_empty() {
+        return Err(CommandError::InvalidInput(
+            "Model path must not be empty".to_string(),
+        ));
+    }
+
+    let patch = serde_json::json!({ "modelPath": path });
+    switch_model(&app, &state, &settings, PathBuf::from(path), patch).await
+}
+
+/// The model catalog with what is already downloaded
+#[tauri::command]
+pub fn list_models(models: ModelsHandle<'_>) -> CommandResult<Vec<ModelEntry>> {
+    Ok(models.list())
+}
+
+/// Download a model (resuming an earlier attempt), verify it and switch to it
+#[tauri::command]
+pub async fn download_model(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    models: ModelsHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: ModelRequest,
+) -> CommandResult<ModelStatus> {
+    let info = find_model(&request.id)?;
+    let progress_app = app.clone();
+    models
+        .download(info, move |progress| {
+            let _ = progress_app.emit("model-download-progress", progress);
+        })
+        .await
+        .map_err(|e| CommandError::Model(format!("{:#}", e)))?;
+
+    activate_model(&app, &state, &models, &settings, info).await
+}
+
+/// Stop the running download; returns whether one was running
+#[tauri::command]
+pub fn cancel_model_download(models: ModelsHandle<'_>) -> CommandResult<bool> {
+    Ok(models.cancel_download())
+}
+
+/// Switch to an already downloaded model without restarting
+#[tauri::command]
+pub async fn select_model(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    models: ModelsHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: ModelRequest,
+) -> CommandResult<ModelStatus> {
+    let info = find_model(&request.id)?;
+    activate_model(&app, &state, &models, &settings, info).await
+}
+
+#[tauri::command]
+pub fn get_settings(settings: SettingsHandle<'_>) -> CommandResult<Settings> {
+    Ok(settings.lock().unwrap().get().clone())
+}
+
+/// Merge a partial update (camelCase keys) into the settings, save and apply it.
+///
+/// Nothing changes unless the whole patch can be applied: it is validated
+/// first, and if registering a shortcut or the autostart entry fails, the ones
+/// already registered are put back. The rest of the patch is then saved at
+/// once. An `apiKey` entry (sent when migrating old frontend settings) goes to
+/// the secret store instead. A `model` or `modelPath` change loads the model in
+/// the background and is saved once it has loaded; a failed load is reported
+/// as a `failed` `model-status`.
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    processors: PostProcessorsHandle<'_>,
+    settings: SettingsHandle<'_>,
+    models: ModelsHandle<'_>,
+    mut patch: Value,
+) -> CommandResult<Settings> {
+    let api_key = match patch.as_object_mut().and_then(|p| p.remove("apiKey")) {
+        Some(Value::String(key)) => Some(key),
+        Some(_) => {
+            return Err(CommandError::InvalidInput("API key must be a string".to_string()))
+        }
+        None => None,
+    };
+
+    let object = patch.as_object();
+    if object.is_some_and(|p| p.contains_key("model") && p.contains_key("modelPath")) {
+        return Err(CommandError::InvalidInput(
+            "Set either a catalog model or a model path, not both".to_string(),
+        ));
+    }
+    let current = settings.lock().unwrap().get().clone();
+    let mut merged = current
+        .merged(&patch)
+        .map_err(|e| CommandError::Settings(format!("{:#}", e)))?;
+    if object.is_some_and(|p| p.contains_key("template")) {
+        let templates = app.state::<Arc<Mutex<Templates>>>();
+        if templates.lock().unwrap().get(&merged.template).is_none() {
+            return Err(CommandError::InvalidInput(format!(
+                "Unknown template: {}",
+                merged.template
+            )));
+        }
+    }
+
+    // Save shortcuts the way they are displayed
+    merged.hotkey = merged.hotkey.parse::<Hotkey>()?.to_string();
+    merged.template_hotkeys = shortcut::parse_template_hotkeys(&merged.template_hotkeys)?
+        .into_iter()
+        .map(|(id, hotkey)| (id, hotkey.to_string()))
+        .collect();
+    if let Some(object) = patch.as_object_mut() {
+        if object.contains_key("hotkey") {
+            object.insert("hotkey".to_string(), Value::from(merged.hotkey.clone()));
+        }
+        if object.contains_key("templateHotkeys") {
+            let hotkeys = serde_json::to_value(&merged.template_hotkeys).unwrap_or_default();
+            object.insert("templateHotkeys".to_string(), hotkeys);
+        }
+    }
+
+    let hotkey = match patch.get("hotkey") {
+        Some(_) => Some(merged.hotkey.parse::<Hotkey>()?),
+        None => None,
+    };
+    let model = patch.as_object_mut().and_then(|p| p.remove("model"));
+    let model_path = patch.as_object_mut().and_then(|p| p.remove("modelPath"));
+
+    let mut rollback = Rollback::default();
+    let result = register_changes(&app, &state, hotkey, &current, &merged, &mut rollback)
+        .and_then(|()| match api_key.filter(|key| !key.trim().is_empty()) {
+            Some(key) => store_api_key(&processors, Provider::DeepSeek, &key).map(|_| ()),
+            None => Ok(()),
+        })
+        .and_then(|()| persist_settings(&app, &settings, patch));
+    let updated = match result {
+        Ok(updated) => updated,
+        Err(e) => {
+            rollback.run();
+            return Err(e);
+        }
+    };
+    state.lock().unwrap().apply_settings(&updated);
+    if updated.template != current.template {
+        tray::refresh(&app);
+        log::info!("Output template: {}", updated.template);
+    }
+
+    if let Some(model) = model {
+        let info = find_model(model.as_str().unwrap_or_default())?;
+        if current.model != info.id {
+            let path = models.path(info);
+            let app = app.clone();
+            let state = state.inner().clone();
+            let models = models.inner().clone();
+            let settings = settings.inner().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = activate_model(&app, &state, &models, &settings, info).await {
+                    report_switch_failure(&app, &path, &e);
+                }
+            });
+        }
+    }
+
+    if let Some(model_path) = model_path {
+        let path = match model_path.as_str() {
+            Some(path) => PathBuf::from(path.trim()),
+            // Back to the model picked from the catalog
+            None => models.path(find_model(&current.model)?),
+        };
+        let patch = serde_json::json!({ "modelPath": model_path.as_str().map(str::trim) });
+
+        let app = app.clone();
+        let state = state.inner().clone();
+        let settings = settings.inner().clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = switch_model(&app, &state, &settings, path.clone(), patch).await {
+                report_switch_failure(&app, &path, &e);
+            }
+        });
+    }
+
+    Ok(updated)
+}
+
+/// Change the recording shortcut; the old one stays active if this fails
+#[tauri::command]
+pub fn set_hotkey(
+    app: AppHandle,
+    state: RecordingStateHandle<'_>,
+    settings: SettingsHandle<'_>,
+    request: HotkeyRequest,
+) -> CommandResult<HotkeyStatus> {
+    let updated = rebind_hotkey(&app, &state, &settings, &request.hotkey)?;
+    Ok(HotkeyStatus {
+        hotkey: updated.hotkey,
+    })
+}
+
+#[tauri::command]
+pub fn get_autostart() -> CommandResult<AutostartStatus> {
+    Ok(AutostartStatus {
+        enabled: autostart::is_autostart_enabled(),
+    })
+}
+
+/// Turn starting with the system on or off; the choice is saved so startup keeps it
+#[tauri::command]
+pub fn set_autostart(
+    app: AppHandle,
+    settings: SettingsHandle<'_>,
+    request: AutostartRequest,
+) -> CommandResult<AutostartStatus> {
+    toggle_autostart(request.enabled)?;
+    persist_settings(&app, &settings, serde_json::json!({ "autostart": request.enabled }))?;
+
+    Ok(AutostartStatus {
+        enabled: autostart::is_autostart_enabled(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_serialization() {
+        let error = CommandError::DeviceNotFound("USB Mic".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "kind": "deviceNotFound", "message": "USB Mic" })
+        );
+        assert_eq!(error.to_string(), "Device not found: USB Mic");
+    }
+
+    #[test]
+    fn test_shortcut_error_serialization() {
+        let error = CommandError::from(ShortcutError::Conflict("Ctrl+Alt+D".to_string()));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "shortcut",
+                "message": { "kind": "conflict", "message": "Ctrl+Alt+D" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_deserialization() {
+        let request: ApiKeyRequest =
+            serde_json::from_value(serde_json::json!({ "apiKey": "sk-test" })).unwrap();
+        assert_eq!(request.api_key, "sk-test");
+        assert_eq!(request.provider, None);
+
+        let request: ApiKeyRequest = serde_json::from_value(
+            serde_json::json!({ "apiKey": "sk-test", "provider": "openAiCompatible" }),
+        )
+        .unwrap();
+        assert_eq!(request.provider, Some(Provider::OpenAiCompatible));
+
+        let request: SelectDeviceRequest =
+            serde_json::from_value(serde_json::json!({ "id": "default" })).unwrap();
+        assert_eq!(request.id, DEFAULT_DEVICE_ID);
+
+        let request: Template = serde_json::from_value(
+            serde_json::json!({ "id": "", "name": "Standup", "prompt": "Summarize {{input}}" }),
+        )
+        .unwrap();
+        assert_eq!(request.name, "Standup");
+    }
+
+    #[test]
+    fn test_response_serialization() {
+        let status = RecordingStatus { is_recording: true };
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            serde_json::json!({ "isRecording": true })
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! The application in the foreground, and the user's rules mapping apps to
+//! output templates.
+//!
+//! Windows is asked through Win32. On Linux Hyprland and Sway are asked
+//! through their IPC tools and everything else through the X server, which
+//! also sees XWayland windows; other Wayland compositors do not tell.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// The focused window's application
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForegroundApp {
+    /// Executable name without directory or ".exe", e.g. "thunderbird"
+    pub process: Option<String>,
+    /// Window class: WM_CLASS on X11, the app id on Wayland, the class name on Windows
+    pub class: Option<String>,
+}
+
+impl fmt::Display for ForegroundApp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.process, &self.class) {
+            (Some(process), Some(class)) => write!(f, "{} ({})", process, class),
+            (Some(name), None) | (None, Some(name)) => write!(f, "{}", name),
+            (None, None) => write!(f, "unknown app"),
+        }
+    }
+}
+
+pub trait ForegroundDetector: Send + Sync {
+    /// Platform name for logs
+    fn name(&self) -> &'static str;
+
+    /// The focused app; `None` if no window has the focus
+    fn foreground_app(&self) -> Result<Option<ForegroundApp>>;
+}
+
+/// Picks the output template while an app is in the foreground
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppRule {
+    /// Process or window class names, e.g. "outlook" or "Code"
+    pub apps: Vec<String>,
+    /// Template id; `None` pastes the raw transcript
+    pub template: Option<String>,
+}
+
+impl AppRule {
+    pub fn validate(&self) -> Result<()> {
+        if self.apps.is_empty() || self.apps.iter().any(|app| app.trim().is_empty()) {
+            anyhow::bail!("App rules need at least one app name, none of them empty");
+        }
+        if let Some(template) = &self.template {
+            if !crate::templates::is_valid_id(template) {
+                anyhow::bail!("Invalid template id in app rule: {}", template);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether one of `apps` is the process or window class of `app`, ignoring
+    /// case and ".exe"
+    pub fn matches(&self, app: &ForegroundApp) -> bool {
+        let names = [&app.process, &app.class];
+        self.apps.iter().any(|wanted| {
+            let wanted = normalize(wanted);
+            names
+                .iter()
+                .any(|name| name.as_deref().map(normalize).as_deref() == Some(wanted.as_str()))
+        })
+    }
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// The first rule matching the app in the foreground. Detection failures are
+/// logged and treated as no match.
+pub fn matching_rule<'a>(
+    detector: &dyn ForegroundDetector,
+    rules: &'a [AppRule],
+) -> Option<&'a AppRule> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    match detector.foreground_app() {
+        Ok(Some(app)) => {
+            let rule = rules.iter().find(|rule| rule.matches(&app));
+            log::info!(
+                "Foreground app: {}, {}",
+                app,
+                if rule.is_some() {
+                    "rule applies"
+                } else {
+                    "no rule"
+                }
+            );
+            rule
+        }
+        Ok(None) => None,
+        Err(e) => {
+            log::warn!(
+                "Cannot tell the foreground app ({}): {:#}",
+                detector.name(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// The detector for this platform
+pub fn detector() -> Arc<dyn ForegroundDetector> {
+    #[cfg(windows)]
+    return Arc::new(win32::Win32Detector);
+    #[cfg(target_os = "linux")]
+    return Arc::new(linux::LinuxDetector);
+    #[cfg(not(any(windows, target_os = "linux")))]
+    return Arc::new(Unsupported);
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+struct Unsupported;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+impl ForegroundDetector for Unsupported {
+    fn name(&self) -> &'static str {
+        "unsupported"
+    }
+
+    fn foreground_app(&self) -> Result<Option<ForegroundApp>> {
+        anyhow::bail!("Foreground app detection is not supported on this platform")
+    }
+}
+
+/// Reports a fixed app
+#[cfg(test)]
+pub struct MockDetector(pub Result<Option<ForegroundApp>, String>);
+
+#[cfg(test)]
+impl ForegroundDetector for MockDetector {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn foreground_app(&self) -> Result<Option<ForegroundApp>> {
+        self.0.clone().map_err(anyhow::Error::msg)
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use super::{ForegroundApp, ForegroundDetector};
+    use anyhow::Result;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetClassNameW, GetForegroundWindow, GetWindowThreadProcessId,
+    };
+
+    pub struct Win32Detector;
+
+    impl ForegroundDetector for Win32Detector {
+        fn name(&self) -> &'static str {
+            "Win32"
+        }
+
+        fn foreground_app(&self) -> Result<Option<ForegroundApp>> {
+            // SAFETY: plain Win32 calls; every buffer outlives the call it is passed to
+            // and its length is passed along
+            unsafe {
+                let window = GetForegroundWindow();
+                if window.is_null() {
+                    return Ok(None);
+                }
+
+                let mut class = [0u16; 256];
+                let len = GetClassNameW(window, class.as_mut_ptr(), class.len() as i32);
+                let class = (len > 0).then(|| String::from_utf16_lossy(&class[..len as usize]));
+
+                let mut pid = 0;
+                GetWindowThreadProcessId(window, &mut pid);
+                let process = process_name(pid);
+                if let Err(e) = &process {
+                    log::debug!("No process name for pid {}: {:#}", pid, e);
+                }
+
+                Ok(Some(ForegroundApp {
+                    process: process.ok(),
+                    class,
+                }))
+            }
+        }
+    }
+
+    /// Executable name of a process, without ".exe"
+    unsafe fn process_name(pid: u32) -> Result<String> {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            anyhow::bail!("Cannot open process: {}", std::io::Error::last_os_error());
+        }
+
+        let mut path = [0u16; 1024];
+        let mut len = path.len() as u32;
+        let ok =
+            QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len);
+        let error = std::io::Error::last_os_error();
+        CloseHandle(process);
+        if ok == 0 {
+            anyhow::bail!("Cannot query the executable: {}", error);
+        }
+
+        let path = String::from_utf16_lossy(&path[..len as usize]);
+        Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow::anyhow!("Unexpected executable path {}", path))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{ForegroundApp, ForegroundDetector};
+    use anyhow::{Context, Result};
+    use serde_json::Value;
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+
+    /// Asks Hyprland or Sway when running under them, the X server otherwise
+    pub struct LinuxDetector;
+
+    impl ForegroundDetector for LinuxDetector {
+        fn name(&self) -> &'static str {
+            "Linux"
+        }
+
+        fn foreground_app(&self) -> Result<Option<ForegroundApp>> {
+            if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+                let output = run("hyprctl", &["activewindow", "-j"])?;
+                return Ok(parse_hyprland(&output).map(with_process));
+            }
+            if env::var_os("SWAYSOCK").is_some() {
+                let output = run("swaymsg", &["-t", "get_tree"])?;
+                let tree = serde_json::from_str(&output).context("Unexpected swaymsg output")?;
+                return Ok(parse_sway(&tree).map(with_process));
+            }
+            if env::var_os("DISPLAY").is_some() {
+                let root = run("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+                let Some(window) = parse_active_window(&root) else {
+                    return Ok(None);
+                };
+                let output = run("xprop", &["-id", &window, "WM_CLASS", "_NET_WM_PID"])?;
+                return Ok(Some(with_process(parse_xprop_window(&output))));
+            }
+            anyhow::bail!("Neither X11, Hyprland nor Sway is running")
+        }
+    }
+
+    /// What the compositor or X server knows about the focused window
+    #[derive(Debug, PartialEq)]
+    pub struct Window {
+        pub class: Option<String>,
+        pub pid: Option<u32>,
+    }
+
+    fn run(program: &str, args: &[&str]) -> Result<String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to run {}", program))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn with_process(window: Window) -> ForegroundApp {
+        ForegroundApp {
+            process: window.pid.and_then(process_name),
+            class: window.class,
+        }
+    }
+
+    /// Executable name of a process; `comm` is cut to 15 characters, so the
+    /// executable's file name comes first
+    fn process_name(pid: u32) -> Option<String> {
+        fs::read_link(format!("/proc/{}/exe", pid))
+            .ok()
+            .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+            .or_else(|| {
+                let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+                Some(comm.trim().to_string())
+            })
+    }
+
+    /// `hyprctl activewindow -j`; `{}` or "Invalid" without a focused window
+    pub fn parse_hyprland(output: &str) -> Option<Window> {
+        let window: Value = serde_json::from_str(output).ok()?;
+        let class = window["class"]
+            .as_str()
+            .filter(|class| !class.is_empty())
+            .map(str::to_string);
+        let pid = window["pid"]
+            .as_u64()
+            .and_then(|pid| u32::try_from(pid).ok());
+        (class.is_some() || pid.is_some()).then_some(Window { class, pid })
+    }
+
+    /// The focused window in `swaymsg -t get_tree`
+    pub fn parse_sway(node: &Value) -> Option<Window> {
+        if node["focused"].as_bool() == Some(true) {
+            // A focused workspace means no window has the focus
+            if !matches!(node["type"].as_str(), Some("con" | "floating_con")) {
+                return None;
+            }
+            let class = node["app_id"]
+                .as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .map(str::to_string);
+            let pid = node["pid"].as_u64().and_then(|pid| u32::try_from(pid).ok());
+            return Some(Window { class, pid });
+        }
+
+        ["nodes", "floating_nodes"]
+            .iter()
+            .filter_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(parse_sway)
+    }
+
+    /// Window id in `xprop -root _NET_ACTIVE_WINDOW`, e.g.
+    /// `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`
+    pub fn parse_active_window(output: &str) -> Option<String> {
+        let id = output.split('#').nth(1)?.split(',').next()?.trim();
+        (id.starts_with("0x") && id != "0x0").then(|| id.to_string())
+    }
+
+    /// `xprop -id <window> WM_CLASS _NET_WM_PID`, e.g.
+    /// `WM_CLASS(STRING) = "code", "Code"`; the second WM_CLASS string is the class
+    pub fn parse_xprop_window(output: &str) -> Window {
+        let mut window = Window {
+            class: None,
+            pid: None,
+        };
+        for line in output.lines() {
+            let Some((name, value)) = line.split_once(" = ") else {
+                continue;
+            };
+            if name.starts_with("WM_CLASS") {
+                let strings: Vec<&str> = value
+                    .split(',')
+                    .map(|s| s.trim().trim_matches('"'))
+                    .collect();
+                window.class = strings.last().map(|s| s.to_string());
+            } else if name.starts_with("_NET_WM_PID") {
+                window.pid = value.trim().parse().ok();
+            }
+        }
+        window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(process: Option<&str>, class: Option<&str>) -> ForegroundApp {
+        ForegroundApp {
+            process: process.map(str::to_string),
+            class: class.map(str::to_string),
+        }
+    }
+
+    fn rule(apps: &[&str], template: Option<&str>) -> AppRule {
+        AppRule {
+            apps: apps.iter().map(|app| app.to_string()).collect(),
+            template: template.map(str::to_string),
+        }
+    }
+
+    fn rules() -> Vec<AppRule> {
+        vec![
+            rule(&["WindowsTerminal", "gnome-terminal"], None),
+            rule(&["thunderbird", "OUTLOOK.EXE"], Some("email")),
+            rule(&["code", "idea64"], Some("code-comment")),
+        ]
+    }
+
+    #[test]
+    fn test_rules_match_process_or_class() {
+        let rules = rules();
+        let find = |app: ForegroundApp| {
+            let detector = MockDetector(Ok(Some(app)));
+            matching_rule(&detector, &rules).map(|rule| rule.template.clone())
+        };
+
+        assert_eq!(
+            find(app(Some("OUTLOOK"), None)),
+            Some(Some("email".to_string()))
+        );
+        assert_eq!(
+            find(app(Some("Code.exe"), Some("Chrome_WidgetWin_1"))),
+            Some(Some("code-comment".to_string()))
+        );
+        assert_eq!(
+            find(app(Some("gnome-terminal-server"), Some("Gnome-terminal"))),
+            Some(None)
+        );
+        assert_eq!(find(app(Some("firefox"), Some("firefox"))), None);
+        // Names are compared whole
+        assert_eq!(find(app(Some("vscode"), None)), None);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let mut rules = rules();
+        rules.push(rule(&["thunderbird"], Some("bullet-notes")));
+        let detector = MockDetector(Ok(Some(app(Some("thunderbird"), None))));
+        assert_eq!(
+            matching_rule(&detector, &rules)
+                .unwrap()
+                .template
+                .as_deref(),
+            Some("email")
+        );
+    }
+
+    #[test]
+    fn test_no_window_or_detection_failure() {
+        let rules = rules();
+        assert_eq!(matching_rule(&MockDetector(Ok(None)), &rules), None);
+        let failing = MockDetector(Err("xprop not installed".to_string()));
+        assert_eq!(matching_rule(&failing, &rules), None);
+        // Without rules the detector is not asked
+        let detector = MockDetector(Ok(Some(app(Some("thunderbird"), None))));
+        assert_eq!(matching_rule(&detector, &[]), None);
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        assert!(rule(&["code"], Some("code-comment")).validate().is_ok());
+        assert!(rule(&["wt"], None).validate().is_ok());
+        assert!(rule(&[], None).validate().is_err());
+        assert!(rule(&["code", " "], None).validate().is_err());
+        assert!(rule(&["code"], Some("Code Comment")).validate().is_err());
+
+        let parsed: AppRule =
+            serde_json::from_value(serde_json::json!({ "apps": ["kitty"], "template": null }))
+                .unwrap();
+        assert_eq!(parsed, rule(&["kitty"], None));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(app(Some("code"), Some("Code")).to_string(), "code (Code)");
+        assert_eq!(app(None, Some("foot")).to_string(), "foot");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_xprop() {
+        use linux::{parse_active_window, parse_xprop_window, Window};
+
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n").as_deref(),
+            Some("0x3a00007")
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"),
+            None
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW:  not found.\n"),
+            None
+        );
+
+        let output = "WM_CLASS(STRING) = \"gnome-terminal-server\", \"Gnome-terminal\"\n\
+                      _NET_WM_PID(CARDINAL) = 4242\n";
+        assert_eq!(
+            parse_xprop_window(output),
+            Window {
+                class: Some("Gnome-terminal".to_string()),
+                pid: Some(4242),
+            }
+        );
+        let output = "WM_CLASS:  not found.\n_NET_WM_PID:  not found.\n";
+        assert_eq!(
+            parse_xprop_window(output),
+            Window {
+                class: None,
+                pid: None
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_hyprland() {
+        use linux::{parse_hyprland, Window};
+
+        let output = r#"{"address": "0x55d1", "class": "kitty", "title": "~", "pid": 1812}"#;
+        assert_eq!(
+            parse_hyprland(output),
+            Some(Window {
+                class: Some("kitty".to_string()),
+                pid: Some(1812),
+            })
+        );
+        assert_eq!(parse_hyprland("{}"), None);
+        assert_eq!(parse_hyprland("Invalid"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_sway() {
+        use linux::{parse_sway, Window};
+
+        let tree = serde_json::json!({
+            "type": "root", "focused": false,
+            "nodes": [{
+                "type": "output", "focused": false,
+                "nodes": [{
+                    "type": "workspace", "focused": false,
+                    "nodes": [
+                        { "type": "con", "focused": false, "app_id": "foot", "pid": 10 }
+                    ],
+                    "floating_nodes": [{
+                        "type": "floating_con", "focused": true, "app_id": null, "pid": 11,
+                        "window_properties": { "class": "thunderbird" }
+                    }]
+                }]
+            }]
+        });
+        assert_eq!(
+            parse_sway(&tree),
+            Some(Window {
+                class: Some("thunderbird".to_string()),
+                pid: Some(11),
+            })
+        );
+
+        let empty_workspace = serde_json::json!({
+            "type": "root", "focused": false,
+            "nodes": [{ "type": "workspace", "focused": true, "nodes": [] }]
+        });
+        assert_eq!(parse_sway(&empty_workspace), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! File helpers: atomic writes for the settings, glossary and template files,
+//! and scratch directories for the tests that write files.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+#[cfg(test)]
+use std::path::PathBuf;
+
+/// Write a file atomically: the bytes go to `<name>.tmp` next to it, which then
+/// replaces `path`, so a crash never leaves a half-written file behind
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+
+    let mut tmp_name = path
+        .file_name()
+        .with_context(|| format!("Not a file path: {:?}", path))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut file = fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {:?}", tmp_path))?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// A fresh, empty directory for a test; the process id keeps parallel runs apart
+#[cfg(test)]
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("valleyflow-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = temp_dir("fs-util");
+        let path = dir.join("nested").join("settings.json");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!dir.join("nested").join("settings.json.tmp").exists());
+        assert!(write_atomic(&dir.join(".."), b"x").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
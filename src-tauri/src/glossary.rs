@@ -0,0 +1,453 @@
+//! User glossary of names and terms Whisper keeps getting wrong.
+//!
+//! The terms are put into Whisper's initial prompt so the decoder is biased
+//! towards their spelling. Whatever still comes out wrong is fixed
+//! deterministically before post-processing: first near-misses of the terms,
+//! then the user's own replacement rules.
+
+use crate::fs_util;
+use crate::transcription::TranscriptionOptions;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const GLOSSARY_FILE: &str = "glossary.json";
+
+/// Whisper only looks at the last ~224 tokens of the prompt
+const MAX_PROMPT_CHARS: usize = 600;
+
+/// One typo allowed per this many characters of a term; shorter terms are only
+/// fixed when they differ in case or spacing
+const CHARS_PER_TYPO: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Glossary {
+    /// Spellings to bias Whisper towards, e.g. "ValleyFlow" or "Kasia Nowak"
+    pub terms: Vec<String>,
+    /// Also fix near-misses of the terms, not just their case and spacing.
+    /// Off for users whose language inflects names ("z Kasią Nowak").
+    pub fuzzy: bool,
+    /// Applied to every transcript in order
+    pub replacements: Vec<Replacement>,
+}
+
+impl Default for Glossary {
+    fn default() -> Self {
+        Self {
+            terms: Vec::new(),
+            fuzzy: true,
+            replacements: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replacement {
+    /// Whole words matched case-insensitively, or a regex if `regex` is set
+    pub pattern: String,
+    /// May refer to regex groups as `$1`
+    pub replacement: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+/// A glossary ready to be applied
+#[derive(Debug, Clone, Default)]
+pub struct CompiledGlossary {
+    source: Glossary,
+    terms: Vec<String>,
+    rules: Vec<(Regex, String)>,
+}
+
+impl CompiledGlossary {
+    /// Check and compile `glossary`; fails on an empty or invalid pattern
+    pub fn new(glossary: Glossary) -> Result<Self> {
+        let terms = glossary
+            .terms
+            .iter()
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|t| !t.is_empty())
+            .collect();
+
+        let mut rules = Vec::with_capacity(glossary.replacements.len());
+        for replacement in &glossary.replacements {
+            if replacement.pattern.trim().is_empty() {
+                anyhow::bail!("Replacement pattern must not be empty");
+            }
+            let pattern = if replacement.regex {
+                replacement.pattern.clone()
+            } else {
+                format!(r"(?i)\b{}\b", regex::escape(replacement.pattern.trim()))
+            };
+            let regex = Regex::new(&pattern)
+                .with_context(|| format!("Invalid pattern: {}", replacement.pattern))?;
+            rules.push((regex, replacement.replacement.clone()));
+        }
+
+        Ok(Self {
+            source: glossary,
+            terms,
+            rules,
+        })
+    }
+
+    pub fn source(&self) -> &Glossary {
+        &self.source
+    }
+
+    /// The initial prompt for Whisper: the user's own prompt followed by the terms
+    pub fn prompt(&self, base: Option<&str>) -> Option<String> {
+        let base = base.unwrap_or_default().trim();
+        let mut terms = Vec::new();
+        let mut len = base.len() + 1;
+        for term in &self.terms {
+            len += term.len() + 2;
+            if len > MAX_PROMPT_CHARS {
+                log::warn!(
+                    "Glossary too long for the Whisper prompt, {} terms left out",
+                    self.terms.len() - terms.len()
+                );
+                break;
+            }
+            terms.push(term.as_str());
+        }
+
+        let prompt = match (base.is_empty(), terms.is_empty()) {
+            (_, true) => base.to_string(),
+            (true, false) => format!("{}.", terms.join(", ")),
+            (false, false) => format!("{} {}.", base, terms.join(", ")),
+        };
+        (!prompt.is_empty()).then_some(prompt)
+    }
+
+    /// `options` with the glossary added to the initial prompt
+    pub fn bias(&self, options: &TranscriptionOptions) -> TranscriptionOptions {
+        TranscriptionOptions {
+            initial_prompt: self.prompt(options.prompt()),
+            ..options.clone()
+        }
+    }
+
+    /// Fix misspelled terms, then apply the replacement rules
+    pub fn correct(&self, text: &str) -> String {
+        let mut text = self.correct_terms(text);
+        for (regex, replacement) in &self.rules {
+            text = regex.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        text
+    }
+
+    /// Replace word runs that are close to a term with the term's spelling
+    fn correct_terms(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for term in &self.terms {
+            text = self.correct_term(&text, term);
+        }
+        text
+    }
+
+    /// Replace the word runs close to `term`; whitespace outside them is kept as is
+    fn correct_term(&self, text: &str, term: &str) -> String {
+        let words = word_spans(text);
+        let term_words = term.split(' ').count();
+        let mut corrected = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut i = 0;
+        while i < words.len() {
+            // "Valley Flow" for "ValleyFlow" spans one word more than the term. Only
+            // an exact match counts there, or a typo could swallow the next word.
+            let matched = [term_words, term_words + 1].into_iter().find_map(|n| {
+                let window = words.get(i..i + n)?;
+                let span = window[0].start..window[n - 1].end;
+                let (lead, core, trail) = split_punctuation(&text[span.clone()]);
+                let fuzzy = self.source.fuzzy && n == term_words;
+                is_near(core, term, fuzzy).then_some((n, span, lead, trail))
+            });
+            match matched {
+                Some((n, span, lead, trail)) => {
+                    corrected.push_str(&text[copied..span.start]);
+                    corrected.push_str(lead);
+                    corrected.push_str(term);
+                    corrected.push_str(trail);
+                    copied = span.end;
+                    i += n;
+                }
+                None => i += 1,
+            }
+        }
+        corrected.push_str(&text[copied..]);
+        corrected
+    }
+}
+
+/// Byte ranges of the whitespace-separated words in `text`
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(word_start)) => {
+                spans.push(word_start..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        spans.push(word_start..text.len());
+    }
+    spans
+}
+
+/// Split leading and trailing punctuation off `s`
+pub fn split_punctuation(s: &str) -> (&str, &str, &str) {
+    let core_start = s.find(|c: char| c.is_alphanumeric()).unwrap_or(s.len());
+    let core_end = s
+        .rfind(|c: char| c.is_alphanumeric())
+        .map_or(core_start, |i| {
+            i + s[i..].chars().next().map_or(1, char::len_utf8)
+        });
+    (&s[..core_start], &s[core_start..core_end], &s[core_end..])
+}
+
+/// Whether `candidate` is `term` with different case or spacing, or with a few
+/// typos if `fuzzy` is set
+fn is_near(candidate: &str, term: &str, fuzzy: bool) -> bool {
+    let normalize = |s: &str| -> Vec<char> {
+        s.chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let candidate = normalize(candidate);
+    let term = normalize(term);
+    if candidate.is_empty() || term.is_empty() {
+        return false;
+    }
+    if candidate == term {
+        return true;
+    }
+    fuzzy && edit_distance(&candidate, &term) <= term.len() / CHARS_PER_TYPO
+}
+
+/// Levenshtein distance between two character sequences
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Default location of the glossary file
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ValleyFlow")
+        .join(GLOSSARY_FILE)
+}
+
+/// Load the glossary at `path`; a missing or unusable file gives an empty glossary
+pub fn load(path: &Path) -> CompiledGlossary {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to read glossary from {:?}: {}", path, e);
+            }
+            return CompiledGlossary::default();
+        }
+    };
+
+    let compiled = serde_json::from_str(&json)
+        .context("Glossary file is not valid")
+        .and_then(CompiledGlossary::new);
+    match compiled {
+        Ok(glossary) => glossary,
+        Err(e) => {
+            log::warn!("Ignoring glossary at {:?}: {:#}", path, e);
+            CompiledGlossary::default()
+        }
+    }
+}
+
+/// Write the glossary atomically (temp file + rename)
+pub fn save(path: &Path, glossary: &Glossary) -> Result<()> {
+    let json = serde_json::to_string_pretty(glossary)?;
+    fs_util::write_atomic(path, json.as_bytes()).context("Failed to save glossary")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hand-written transcripts with the mistakes Whisper makes, and the glossary to fix them
+    #[derive(Deserialize)]
+    struct Fixture {
+        name: String,
+        glossary: Glossary,
+        transcript: String,
+        expected: String,
+        #[serde(default)]
+        prompt: Option<String>,
+    }
+
+    fn fixtures() -> Vec<Fixture> {
+        serde_json::from_str(include_str!("../fixtures/glossary/transcripts.json")).unwrap()
+    }
+
+    #[test]
+    fn test_fixture_transcripts_are_corrected() {
+        for fixture in fixtures() {
+            let glossary = CompiledGlossary::new(fixture.glossary).unwrap();
+            assert_eq!(
+                glossary.correct(&fixture.transcript),
+                fixture.expected,
+                "fixture {}",
+                fixture.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_prompts_of_fixtures() {
+        for fixture in fixtures() {
+            let Some(expected) = fixture.prompt else {
+                continue;
+            };
+            let glossary = CompiledGlossary::new(fixture.glossary).unwrap();
+            assert_eq!(
+                glossary.prompt(None).as_deref(),
+                Some(expected.as_str()),
+                "fixture {}",
+                fixture.name
+            );
+        }
+    }
+
+    fn glossary(terms: &[&str]) -> CompiledGlossary {
+        CompiledGlossary::new(Glossary {
+            terms: terms.iter().map(|t| t.to_string()).collect(),
+            ..Glossary::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_prompt_keeps_user_prompt_first() {
+        let glossary = glossary(&["ValleyFlow", "DeepSeek"]);
+        assert_eq!(
+            glossary.prompt(Some("Notatki z zebrania.")).as_deref(),
+            Some("Notatki z zebrania. ValleyFlow, DeepSeek.")
+        );
+        assert_eq!(CompiledGlossary::default().prompt(None), None);
+        assert_eq!(
+            CompiledGlossary::default().prompt(Some("Hello")).as_deref(),
+            Some("Hello")
+        );
+    }
+
+    #[test]
+    fn test_prompt_is_capped() {
+        let terms: Vec<String> = (0..200).map(|i| format!("Term{}", i)).collect();
+        let glossary = CompiledGlossary::new(Glossary {
+            terms,
+            ..Glossary::default()
+        })
+        .unwrap();
+        let prompt = glossary.prompt(None).unwrap();
+        assert!(prompt.len() <= MAX_PROMPT_CHARS);
+        assert!(prompt.starts_with("Term0, Term1"));
+    }
+
+    #[test]
+    fn test_bias_replaces_prompt() {
+        let options = TranscriptionOptions {
+            initial_prompt: Some("Hello".to_string()),
+            beam_size: 5,
+            ..TranscriptionOptions::default()
+        };
+        let biased = glossary(&["ValleyFlow"]).bias(&options);
+        assert_eq!(biased.initial_prompt.as_deref(), Some("Hello ValleyFlow."));
+        assert_eq!(biased.beam_size, 5);
+    }
+
+    #[test]
+    fn test_whitespace_is_kept() {
+        let glossary = glossary(&["ValleyFlow", "Kasia Nowak"]);
+        assert_eq!(
+            glossary.correct("valley flow  działa.\n\nNapisz do kasia nowak,\tproszę.\n"),
+            "ValleyFlow  działa.\n\nNapisz do Kasia Nowak,\tproszę.\n"
+        );
+    }
+
+    #[test]
+    fn test_short_terms_are_not_fuzzy() {
+        let glossary = glossary(&["Jira", "API"]);
+        assert_eq!(glossary.correct("jura api"), "jura API");
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected() {
+        for pattern in ["", "  ", "(unclosed"] {
+            let glossary = Glossary {
+                replacements: vec![Replacement {
+                    pattern: pattern.to_string(),
+                    replacement: "x".to_string(),
+                    regex: true,
+                }],
+                ..Glossary::default()
+            };
+            assert!(CompiledGlossary::new(glossary).is_err(), "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("kubernetes"), &chars("kubernetes")), 0);
+        assert_eq!(edit_distance(&chars("cubernetis"), &chars("kubernetes")), 2);
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+        assert_eq!(edit_distance(&chars("łódź"), &chars("lodz")), 3);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = crate::fs_util::temp_dir("glossary");
+        let path = dir.join(GLOSSARY_FILE);
+
+        assert_eq!(load(&path).source(), &Glossary::default());
+
+        let glossary = Glossary {
+            terms: vec!["ValleyFlow".to_string()],
+            fuzzy: false,
+            replacements: vec![Replacement {
+                pattern: "kay eight s".to_string(),
+                replacement: "k8s".to_string(),
+                regex: false,
+            }],
+        };
+        save(&path, &glossary).unwrap();
+        assert_eq!(load(&path).source(), &glossary);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(load(&path).source(), &Glossary::default());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,132 +1,637 @@
-use crate::audio::AudioCapture;
+use crate::active_model::{ActiveModel, ModelState};
+use crate::audio::{AudioCapture, CaptureLimits, Recording};
 use crate::clipboard::ClipboardManager;
-use crate::deepseek::DeepSeekClient;
+use crate::foreground::{self, AppRule, ForegroundDetector};
+use crate::glossary::CompiledGlossary;
+use crate::inference::JobOptions;
+use crate::monitor::{MonitorConfig, RecordingMonitor};
+use crate::pipeline::{Pipeline, PipelineState, StartBehaviour};
+use crate::postprocess::{PostProcessors, Request};
+use crate::settings::Settings;
+use crate::shortcut::Hotkey;
 use crate::sound::SoundPlayer;
-use crate::transcription::{resample_to_16k_mono, Transcriber};
+use crate::streaming::StreamingSession;
+use crate::templates::{Template, Templates};
+use crate::transcription::{
+    resample_to_16k_mono, Detection, TranscriptionOptions, WHISPER_SAMPLE_RATE,
+};
+use crate::translation::Translation;
+use crate::trigger::{HotkeyAction, HotkeyTrigger, RecordingMode};
+use crate::vad;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tauri::{AppHandle, Emitter};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Shortcut, ShortcutState};
+use tokio::sync::Notify;
 
 pub struct RecordingState {
-    pub is_recording: bool,
+    pub pipeline: Pipeline,
     pub audio_capture: Arc<Mutex<AudioCapture>>,
-    pub transcriber: Option<Arc<Mutex<Transcriber>>>,
-    pub deepseek_client: Arc<Mutex<DeepSeekClient>>,
+    /// The Whisper model, loaded on first use
+    pub model: Arc<ActiveModel>,
+    /// Language model providers that clean up transcripts
+    pub post_processors: Arc<Mutex<PostProcessors>>,
     pub clipboard: Arc<Mutex<ClipboardManager>>,
     pub sound_player: Arc<Mutex<SoundPlayer>>,
+    /// Transcribe incrementally while recording instead of after it stops
+    pub streaming_enabled: bool,
+    pub streaming_session: Option<StreamingSession>,
+    /// Whisper decoding options for the next recording
+    pub transcription: TranscriptionOptions,
+    /// Terms Whisper is biased towards and corrections applied to its output
+    pub glossary: Arc<CompiledGlossary>,
+    /// Translate recordings instead of pasting them in the spoken language
+    pub translation: Option<Translation>,
+    /// Output templates for post-processing
+    pub templates: Arc<Mutex<Templates>>,
+    /// Id of the template the next recording is post-processed with
+    pub template: String,
+    /// Tells which app the transcript is pasted into
+    pub foreground: Arc<dyn ForegroundDetector>,
+    /// Templates picked by the app in the foreground instead of `template`
+    pub app_rules: Vec<AppRule>,
+    pub monitor_config: MonitorConfig,
+    pub monitor: Option<RecordingMonitor>,
+    /// Let Escape cancel an active recording
+    pub cancel_on_escape: bool,
+    /// Interprets recording shortcut events according to the recording mode
+    pub trigger: HotkeyTrigger,
+    /// The recording shortcut actually registered; the default one if the
+    /// configured one was unavailable at startup
+    pub hotkey: Option<Hotkey>,
+    /// Signalled to skip post-processing of the recording in flight
+    pub skip_post_processing: Option<Arc<Notify>>,
 }
 
-pub fn toggle_recording(app: &AppHandle, state: &Arc<Mutex<RecordingState>>) {
+impl RecordingState {
+    /// Apply the recording-related parts of `settings`; takes effect from the next recording
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let mut audio_capture = self.audio_capture.lock().unwrap();
+        if settings.microphone == "default" {
+            audio_capture.use_default_device();
+        } else if let Err(e) = audio_capture.select_device(&settings.microphone) {
+            log::warn!("{}, falling back to the default microphone", e);
+            audio_capture.use_default_device();
+        }
+        let limits = CaptureLimits {
+            max_duration_secs: settings.max_recording_secs,
+            ..audio_capture.limits()
+        };
+        audio_capture.set_limits(limits);
+
+        self.post_processors.lock().unwrap().configure(&settings.post_processor);
+
+        self.streaming_enabled = settings.streaming;
+        self.transcription = settings.transcription.clone();
+        self.translation = settings.translate_to.as_deref().and_then(Translation::to);
+        self.template = settings.template.clone();
+        self.app_rules = settings.app_rules.clone();
+        self.monitor_config.auto_stop_silence_secs = settings.auto_stop_silence_secs;
+        self.cancel_on_escape = settings.cancel_on_escape;
+        self.trigger.set_mode(settings.recording_mode);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.pipeline.is_recording()
+    }
+
+    /// Whisper options for the next job: the settings plus glossary and translation
+    fn transcription_options(&self) -> TranscriptionOptions {
+        TranscriptionOptions {
+            translate: self.translation.is_some_and(|t| t.whisper_translates()),
+            ..self.glossary.bias(&self.transcription)
+        }
+    }
+}
+
+/// Move the pipeline to `next` and tell the frontend; illegal transitions are logged and ignored
+fn set_pipeline_state(app: &AppHandle, recording_state: &mut RecordingState, next: PipelineState) {
+    match recording_state.pipeline.transition(next) {
+        Ok(()) => {
+            let _ = app.emit("pipeline-state", recording_state.pipeline.state());
+        }
+        Err(e) => log::error!("{}", e),
+    }
+}
+
+/// Advance the pipeline from the processing task, then start a queued recording if there is one
+fn advance(app: &AppHandle, state: &Arc<Mutex<RecordingState>>, next: PipelineState) {
     let mut recording_state = state.lock().unwrap();
+    set_pipeline_state(app, &mut recording_state, next);
 
-    if recording_state.is_recording {
-        // Stop recording
-        let samples = recording_state.audio_capture.lock().unwrap().stop_recording();
+    if recording_state.pipeline.state().start_behaviour() == StartBehaviour::Start {
+        recording_state.skip_post_processing = None;
+        if recording_state.pipeline.take_queued_start() {
+            log::info!("Starting queued recording");
+            let _ = start_recording(app, state, &mut recording_state);
+        }
+    }
+}
 
-        match samples {
-            Ok(samples) => {
-                log::info!("Recording stopped, {} samples captured", samples.len());
+/// Handle a press or release of the recording shortcut
+pub fn handle_hotkey(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    shortcut_state: ShortcutState,
+) {
+    let mut recording_state = state.lock().unwrap();
+    let is_recording = recording_state.is_recording();
+    let action = recording_state
+        .trigger
+        .on_event(shortcut_state, Instant::now(), is_recording);
 
-                // Emit event to frontend
-                let _ = app.emit("recording-state", false);
-                let _ = app.emit("recording-processing", true);
+    // Failures are already reported to the frontend via `pipeline-state`
+    let _ = match action {
+        HotkeyAction::Start => request_start(app, state, &mut recording_state),
+        HotkeyAction::Stop => stop_recording(app, state, &mut recording_state),
+        HotkeyAction::Ignore => {
+            // Letting go of push-to-talk withdraws a press queued while busy
+            if shortcut_state == ShortcutState::Released
+                && recording_state.trigger.mode() == RecordingMode::PushToTalk
+            {
+                recording_state.pipeline.clear_queue();
+            }
+            Ok(())
+        }
+    };
+}
+
+/// Ask to start recording; what happens depends on the pipeline state
+pub fn request_start(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    recording_state: &mut RecordingState,
+) -> anyhow::Result<()> {
+    match recording_state.pipeline.state().start_behaviour() {
+        StartBehaviour::Start => start_recording(app, state, recording_state),
+        StartBehaviour::Queue => {
+            log::info!("Recording queued until the current result is delivered");
+            recording_state.pipeline.queue_start();
+            Ok(())
+        }
+        StartBehaviour::Cancel => {
+            log::info!("Skipping post-processing, delivering the raw transcript");
+            if let Some(skip) = recording_state.skip_post_processing.take() {
+                skip.notify_one();
+            }
+            Ok(())
+        }
+        StartBehaviour::Reject => {
+            let current = recording_state.pipeline.state();
+            log::info!("Start request rejected while {:?}", current);
+            let _ = app.emit("pipeline-busy", current);
+            anyhow::bail!("Cannot start recording now ({:?})", current)
+        }
+    }
+}
 
-                // Process the recording
-                let transcriber = recording_state.transcriber.clone();
-                let deepseek_client = recording_state.deepseek_client.clone();
-                let clipboard = recording_state.clipboard.clone();
-                let sound_player = recording_state.sound_player.clone();
-                let app_handle = app.clone();
+/// Stop a recording on behalf of the monitor, unless it was already stopped
+pub fn auto_stop(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    monitor_stopped: &AtomicBool,
+    reason: &str,
+) {
+    let mut recording_state = state.lock().unwrap();
+    if monitor_stopped.load(Ordering::SeqCst) || !recording_state.is_recording() {
+        return;
+    }
 
-                // Spawn async task for processing
+    log::info!("Stopping recording automatically: {}", reason);
+    let _ = app.emit("recording-auto-stopped", reason);
+    let _ = stop_recording(app, state, &mut recording_state);
+}
+
+/// Stop the active recording and throw the audio away without transcribing it
+pub fn cancel_recording(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    recording_state: &mut RecordingState,
+) {
+    if !recording_state.is_recording() {
+        return;
+    }
+
+    if let Some(monitor) = recording_state.monitor.take() {
+        monitor.stop();
+    }
+    if let Some(session) = recording_state.streaming_session.take() {
+        session.cancel();
+    }
+    unregister_cancel_shortcut(app, state);
+
+    match recording_state.audio_capture.lock().unwrap().stop_recording() {
+        Ok(recording) => log::info!(
+            "Recording cancelled, discarded {:.1}s of audio",
+            recording.duration_secs()
+        ),
+        Err(e) => log::warn!("Failed to stop cancelled recording: {}", e),
+    }
+
+    set_pipeline_state(app, recording_state, PipelineState::Idle);
+    let _ = app.emit("recording-cancelled", ());
+}
+
+/// Whether Escape should currently be grabbed
+static CANCEL_SHORTCUT_WANTED: AtomicBool = AtomicBool::new(false);
+
+/// Serializes registration changes for the Escape shortcut
+static CANCEL_SHORTCUT_SYNC: Mutex<()> = Mutex::new(());
+
+fn cancel_shortcut() -> Shortcut {
+    Shortcut::new(None, Code::Escape)
+}
+
+/// Grab Escape only while recording so it keeps working everywhere else
+fn register_cancel_shortcut(app: &AppHandle, state: &Arc<Mutex<RecordingState>>) {
+    CANCEL_SHORTCUT_WANTED.store(true, Ordering::SeqCst);
+    sync_cancel_shortcut(app, state.clone());
+}
+
+fn unregister_cancel_shortcut(app: &AppHandle, state: &Arc<Mutex<RecordingState>>) {
+    CANCEL_SHORTCUT_WANTED.store(false, Ordering::SeqCst);
+    sync_cancel_shortcut(app, state.clone());
+}
+
+/// Bring the Escape registration in line with `CANCEL_SHORTCUT_WANTED`.
+///
+/// The global shortcut plugin holds its handler lock while a handler runs, so
+/// (un)registering from inside the hotkey handler would deadlock; do it on a
+/// separate thread instead.
+fn sync_cancel_shortcut(app: &AppHandle, state: Arc<Mutex<RecordingState>>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let _guard = CANCEL_SHORTCUT_SYNC.lock().unwrap();
+        let shortcuts = app.global_shortcut();
+        let shortcut = cancel_shortcut();
+        let wanted = CANCEL_SHORTCUT_WANTED.load(Ordering::SeqCst);
+        let registered = shortcuts.is_registered(shortcut);
+
+        let result = if wanted && !registered {
+            shortcuts.on_shortcut(shortcut, move |app, _shortcut, event| {
+                if event.state() == ShortcutState::Pressed {
+                    log::info!("Escape pressed, cancelling recording");
+                    cancel_recording(app, &state, &mut state.lock().unwrap());
+                }
+            })
+        } else if !wanted && registered {
+            shortcuts.unregister(shortcut)
+        } else {
+            Ok(())
+        };
+
+        if let Err(e) = result {
+            log::warn!("Failed to update Escape shortcut: {}", e);
+        }
+    });
+}
+
+pub fn start_recording(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    recording_state: &mut RecordingState,
+) -> anyhow::Result<()> {
+    if recording_state.pipeline.state().start_behaviour() != StartBehaviour::Start {
+        anyhow::bail!("Pipeline is busy");
+    }
+
+    // Without a model the recording could never be transcribed
+    let model_status = recording_state.model.status();
+    if model_status.state == ModelState::Missing {
+        let message = format!("No Whisper model at {}", model_status.path);
+        set_pipeline_state(app, recording_state, PipelineState::Error(message.clone()));
+        anyhow::bail!(message);
+    }
+
+    let started = recording_state.audio_capture.lock().unwrap().start_recording();
+    if let Err(e) = started {
+        log::error!("Failed to start recording: {}", e);
+        set_pipeline_state(app, recording_state, PipelineState::Error(e.to_string()));
+        return Err(e);
+    }
+    set_pipeline_state(app, recording_state, PipelineState::Recording);
+    log::info!("Recording started");
+
+    if let Some(tap) = recording_state.audio_capture.lock().unwrap().tap() {
+        // Start transcribing while the user is still speaking
+        match recording_state.model.worker() {
+            Some(inference) if recording_state.streaming_enabled => {
+                recording_state.streaming_session = Some(StreamingSession::start(
+                    app.clone(),
+                    tap.clone(),
+                    inference,
+                    recording_state.transcription_options(),
+                ));
+            }
+            Some(_) => {}
+            // First use: load while the user is speaking
+            None => {
+                let model = recording_state.model.clone();
                 tauri::async_runtime::spawn(async move {
-                    if let Err(e) = process_recording(
-                        samples,
-                        transcriber,
-                        deepseek_client,
-                        clipboard,
-                        sound_player,
-                        &app_handle,
-                    )
-                    .await
-                    {
-                        log::error!("Failed to process recording: {}", e);
-                        let _ = app_handle.emit("recording-error", e.to_string());
+                    if let Err(e) = model.load().await {
+                        log::warn!("Failed to load Whisper model: {:#}", e);
                     }
-                    let _ = app_handle.emit("recording-processing", false);
                 });
             }
-            Err(e) => {
-                log::error!("Failed to stop recording: {}", e);
-                let _ = app.emit("recording-error", e.to_string());
-            }
-        }
-        recording_state.is_recording = false;
-    } else {
-        // Start recording
-        if let Err(e) = recording_state.audio_capture.lock().unwrap().start_recording() {
-            log::error!("Failed to start recording: {}", e);
-            let _ = app.emit("recording-error", e.to_string());
-            return;
         }
-        recording_state.is_recording = true;
-        log::info!("Recording started");
 
-        // Emit event to frontend
-        let _ = app.emit("recording-state", true);
+        recording_state.monitor = Some(RecordingMonitor::start(
+            app.clone(),
+            state.clone(),
+            tap,
+            recording_state.monitor_config.clone(),
+        ));
     }
+
+    if recording_state.cancel_on_escape {
+        register_cancel_shortcut(app, state);
+    }
+
+    Ok(())
 }
 
-async fn process_recording(
-    samples: Vec<f32>,
-    transcriber: Option<Arc<Mutex<Transcriber>>>,
-    deepseek_client: Arc<Mutex<DeepSeekClient>>,
+pub fn stop_recording(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    recording_state: &mut RecordingState,
+) -> anyhow::Result<()> {
+    if !recording_state.is_recording() {
+        anyhow::bail!("Not recording");
+    }
+
+    if let Some(monitor) = recording_state.monitor.take() {
+        monitor.stop();
+    }
+    unregister_cancel_shortcut(app, state);
+
+    let recording = recording_state.audio_capture.lock().unwrap().stop_recording();
+
+    match recording {
+        Ok(recording) => {
+            log::info!(
+                "Recording stopped, {:.1}s captured",
+                recording.duration_secs()
+            );
+
+            set_pipeline_state(app, recording_state, PipelineState::Transcribing);
+            let skip_post_processing = Arc::new(Notify::new());
+            recording_state.skip_post_processing = Some(skip_post_processing.clone());
+
+            // Process the recording
+            let job = Job {
+                model: recording_state.model.clone(),
+                transcription: recording_state.transcription_options(),
+                glossary: recording_state.glossary.clone(),
+                translation: recording_state.translation,
+                templates: recording_state.templates.clone(),
+                template_id: recording_state.template.clone(),
+                foreground: recording_state.foreground.clone(),
+                app_rules: recording_state.app_rules.clone(),
+                post_processors: recording_state.post_processors.clone(),
+                clipboard: recording_state.clipboard.clone(),
+                sound_player: recording_state.sound_player.clone(),
+                skip_post_processing,
+            };
+            let streaming_session = recording_state.streaming_session.take();
+            let app_handle = app.clone();
+            let state = state.clone();
+
+            // Spawn async task for processing
+            tauri::async_runtime::spawn(async move {
+                let result =
+                    process_recording(recording, streaming_session, job, &state, &app_handle).await;
+                match result {
+                    Ok(()) => advance(&app_handle, &state, PipelineState::Idle),
+                    Err(e) => {
+                        log::error!("Failed to process recording: {}", e);
+                        advance(&app_handle, &state, PipelineState::Error(e.to_string()));
+                    }
+                }
+            });
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to stop recording: {}", e);
+            set_pipeline_state(app, recording_state, PipelineState::Error(e.to_string()));
+            Err(e)
+        }
+    }
+}
+
+/// What a stopped recording is processed with, taken from the state when it
+/// stops so that settings changed meanwhile apply to the next recording
+struct Job {
+    model: Arc<ActiveModel>,
+    transcription: TranscriptionOptions,
+    glossary: Arc<CompiledGlossary>,
+    translation: Option<Translation>,
+    templates: Arc<Mutex<Templates>>,
+    /// Id of the selected template
+    template_id: String,
+    foreground: Arc<dyn ForegroundDetector>,
+    app_rules: Vec<AppRule>,
+    post_processors: Arc<Mutex<PostProcessors>>,
     clipboard: Arc<Mutex<ClipboardManager>>,
     sound_player: Arc<Mutex<SoundPlayer>>,
+    skip_post_processing: Arc<Notify>,
+}
+
+async fn process_recording(
+    recording: Recording,
+    streaming_session: Option<StreamingSession>,
+    job: Job,
+    state: &Arc<Mutex<RecordingState>>,
     app: &AppHandle,
 ) -> anyhow::Result<()> {
-    // Resample to 16kHz mono (Whisper requirement)
-    // TODO: Get actual sample rate from audio capture
-    let resampled = resample_to_16k_mono(&samples, 48000, 1);
-    log::info!("Resampled to {} samples at 16kHz mono", resampled.len());
+    // Before transcribing, while the target app still has the focus
+    let template = output_template(&job).await;
 
-    // Transcribe with Whisper
-    let (raw_text, language) = if let Some(transcriber) = transcriber {
-        let mut t = transcriber.lock().unwrap();
-        t.transcribe(&resampled)?
-    } else {
-        anyhow::bail!("Transcriber not initialized");
+    let app_handle = app.clone();
+    let options = JobOptions {
+        transcription: job.transcription.clone(),
+        single_segment: true,
+        progress: Some(Box::new(move |percent| {
+            let _ = app_handle.emit("transcription-progress", percent);
+        })),
+        ..JobOptions::default()
     };
 
+    // Most of the audio is already transcribed when streaming
+    let (raw_text, detection) = match streaming_session {
+        Some(session) => session.finish(&recording, options).await?,
+        None => transcribe_recording(recording, &job.model, options).await?,
+    };
+    let raw_text = job.glossary.correct(&raw_text);
+    let _ = app.emit("transcription-language", detection);
+
+    deliver(raw_text, detection, template.as_ref(), &job, state, app).await
+}
+
+/// The template an app rule picks for the app in the foreground, else the
+/// selected one; `None` when the raw transcription should be pasted
+async fn output_template(job: &Job) -> Option<Template> {
+    let foreground = job.foreground.clone();
+    let app_rules = job.app_rules.clone();
+    let rule = tauri::async_runtime::spawn_blocking(move || {
+        foreground::matching_rule(foreground.as_ref(), &app_rules).cloned()
+    })
+    .await
+    .unwrap_or_else(|e| {
+        log::warn!("Foreground app detection failed: {}", e);
+        None
+    });
+
+    let templates = job.templates.lock().unwrap();
+    match rule {
+        Some(rule) => rule.template.map(|id| templates.resolve(&id)),
+        None => Some(templates.resolve(&job.template_id)),
+    }
+}
+
+async fn transcribe_recording(
+    recording: Recording,
+    model: &ActiveModel,
+    options: JobOptions,
+) -> anyhow::Result<(String, Detection)> {
+    let inference = model.load().await?;
+    let speech = tauri::async_runtime::spawn_blocking(move || prepare_speech(&recording)).await??;
+
+    // Transcribe with Whisper
+    let transcript = inference.submit(speech, options).result().await?;
+    Ok((transcript.text(), transcript.detection))
+}
+
+/// Resample a recording for Whisper and cut the silence around the speech
+fn prepare_speech(recording: &Recording) -> anyhow::Result<Vec<f32>> {
+    // Resample to 16kHz mono (Whisper requirement)
+    let resampled =
+        resample_to_16k_mono(&recording.samples, recording.sample_rate, recording.channels);
+    log::info!(
+        "Resampled {} Hz/{}ch to {} samples at 16kHz mono",
+        recording.sample_rate,
+        recording.channels,
+        resampled.len()
+    );
+
+    // Leading/trailing silence is where Whisper hallucinates
+    let speech = vad::trim_silence(&resampled, WHISPER_SAMPLE_RATE)
+        .ok_or_else(|| anyhow::anyhow!("No speech detected"))?;
+    log::info!(
+        "Trimmed silence: {} of {} samples contain speech",
+        speech.len(),
+        resampled.len()
+    );
+
+    Ok(speech.to_vec())
+}
+
+/// Post-process the raw transcription and hand it to the user; without a
+/// template the raw transcription is pasted
+async fn deliver(
+    raw_text: String,
+    detection: Detection,
+    template: Option<&Template>,
+    job: &Job,
+    state: &Arc<Mutex<RecordingState>>,
+    app: &AppHandle,
+) -> anyhow::Result<()> {
+    if raw_text.trim().is_empty() {
+        anyhow::bail!("No speech detected");
+    }
+
     log::info!("Raw transcription: {}", raw_text);
     let _ = app.emit("transcription-raw", &raw_text);
 
-    // Post-process with DeepSeek
-    let final_text = {
-        let client = deepseek_client.lock().unwrap();
-        if client.has_api_key() {
-            match client.process_text(&raw_text, language.into()).await {
-                Ok(processed) => {
-                    log::info!("Processed text: {}", processed);
-                    processed
-                }
-                Err(e) => {
-                    log::warn!("DeepSeek processing failed: {}, using raw text", e);
-                    raw_text
-                }
-            }
-        } else {
-            log::info!("No DeepSeek API key, using raw transcription");
+    let final_text = match template {
+        Some(template) => post_process(&raw_text, detection, template, job, state, app).await?,
+        None => {
+            log::info!("An app rule asks for the raw transcription");
             raw_text
         }
     };
 
+    advance(app, state, PipelineState::Delivering);
+
     // Copy to clipboard
-    clipboard.lock().unwrap().copy_text(&final_text)?;
+    job.clipboard.lock().unwrap().copy_text(&final_text)?;
     let _ = app.emit("transcription-complete", &final_text);
 
     // Play success sound
-    sound_player.lock().unwrap().play_success()?;
+    job.sound_player.lock().unwrap().play_success()?;
 
     log::info!("Transcription pipeline complete!");
     Ok(())
 }
+
+/// Rewrite the transcription with the template, falling back to the offline cleaner
+async fn post_process(
+    raw_text: &str,
+    detection: Detection,
+    template: &Template,
+    job: &Job,
+    state: &Arc<Mutex<RecordingState>>,
+    app: &AppHandle,
+) -> anyhow::Result<String> {
+    let translation = job.translation;
+    let language = translation.map_or(detection.language, |t| {
+        t.transcript_language(detection.language)
+    });
+    let request = Request {
+        text: raw_text,
+        language,
+        translate_to: translation.and_then(|t| t.post_processing_target(language)),
+        template,
+    };
+
+    // Take the processors out so the lock is not held across the request
+    let (processor, fallback) = {
+        let post_processors = job.post_processors.lock().unwrap();
+        (post_processors.current(), post_processors.fallback())
+    };
+    // Show the text while the model writes it
+    let on_partial = |text: &str| {
+        let _ = app.emit("postprocess-partial", text);
+    };
+    let final_text = {
+        if processor.is_available() {
+            advance(app, state, PipelineState::PostProcessing);
+            // Skipping drops the request, which closes a streamed response
+            tokio::select! {
+                result = processor.process_streaming(&request, &on_partial) => match result {
+                    Ok(processed) => {
+                        log::info!("Processed text: {}", processed);
+                        processed
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Post-processing with {} failed: {}, falling back to {}",
+                            processor.name(),
+                            e,
+                            fallback.name()
+                        );
+                        fallback.process(&request).await?
+                    }
+                },
+                _ = job.skip_post_processing.notified() => {
+                    log::info!("Post-processing skipped, using raw transcription");
+                    raw_text.to_string()
+                }
+            }
+        } else {
+            if let Some(target) = request.translate_to {
+                log::warn!(
+                    "Translating into {} needs a language model, pasting untranslated",
+                    target.code()
+                );
+            }
+            log::info!(
+                "No language model available ({}), post-processing with {}",
+                processor.name(),
+                fallback.name()
+            );
+            fallback.process(&request).await?
+        }
+    };
+    Ok(final_text)
+}
@@ -0,0 +1,349 @@
+//! Whisper inference on a dedicated thread.
+//!
+//! A single worker thread owns the model and runs jobs from a queue in the
+//! order they were submitted. Async code awaits a [`Pending`] result instead of
+//! locking the model, so a multi-second Whisper pass never blocks a runtime
+//! worker or the hotkey handler.
+
+use crate::transcription::{join_segments, Detection, Segment, Transcriber, TranscriptionOptions};
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use tokio::sync::oneshot;
+
+/// Anything that turns 16 kHz mono audio into segments; [`Transcriber`] in the app
+pub trait Engine {
+    fn run(
+        &mut self,
+        samples: &[f32],
+        options: &TranscriptionOptions,
+        single_segment: bool,
+        progress: Option<ProgressFn>,
+    ) -> Result<(Vec<Segment>, Detection)>;
+}
+
+impl Engine for Transcriber {
+    fn run(
+        &mut self,
+        samples: &[f32],
+        options: &TranscriptionOptions,
+        single_segment: bool,
+        progress: Option<ProgressFn>,
+    ) -> Result<(Vec<Segment>, Detection)> {
+        self.run_with_progress(samples, options, single_segment, progress)
+    }
+}
+
+/// Returned (as the error) by jobs that were cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transcription cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Shared flag to cancel a job that is queued or running.
+///
+/// A queued job is skipped. A running Whisper pass cannot be interrupted, so it
+/// finishes but its result is thrown away.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Receives the progress of a running job in percent
+pub type ProgressFn = Box<dyn FnMut(i32) + Send>;
+
+#[derive(Default)]
+pub struct JobOptions {
+    pub transcription: TranscriptionOptions,
+    /// Return the whole input as one segment (final transcription)
+    pub single_segment: bool,
+    pub cancel: CancelToken,
+    pub progress: Option<ProgressFn>,
+}
+
+/// Output of a finished job
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript {
+    pub segments: Vec<Segment>,
+    pub detection: Detection,
+}
+
+impl Transcript {
+    pub fn text(&self) -> String {
+        join_segments(&self.segments)
+    }
+}
+
+struct Job {
+    samples: Vec<f32>,
+    options: JobOptions,
+    reply: oneshot::Sender<Result<Transcript>>,
+}
+
+/// Result of a submitted job
+pub struct Pending(oneshot::Receiver<Result<Transcript>>);
+
+impl Pending {
+    /// Wait for the result from async code
+    pub async fn result(self) -> Result<Transcript> {
+        self.0
+            .await
+            .map_err(|_| anyhow::anyhow!("Inference worker stopped"))?
+    }
+
+    /// Wait for the result from a plain thread
+    pub fn wait(self) -> Result<Transcript> {
+        self.0
+            .blocking_recv()
+            .map_err(|_| anyhow::anyhow!("Inference worker stopped"))?
+    }
+}
+
+/// Handle to the inference thread; cheap to clone
+#[derive(Clone)]
+pub struct InferenceWorker {
+    jobs: mpsc::Sender<Job>,
+    model_path: PathBuf,
+}
+
+impl InferenceWorker {
+    /// Move `engine` onto a new worker thread. The thread exits once every handle is dropped.
+    pub fn spawn<E: Engine + Send + 'static>(engine: E, model_path: PathBuf) -> Result<Self> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        std::thread::Builder::new()
+            .name("whisper-inference".to_string())
+            .spawn(move || run_jobs(engine, queue))?;
+
+        Ok(Self { jobs, model_path })
+    }
+
+    /// Load the model at `model_path` and start a worker for it
+    pub fn load(model_path: PathBuf) -> Result<Self> {
+        let transcriber = Transcriber::new(model_path.clone())?;
+        Self::spawn(transcriber, model_path)
+    }
+
+    /// Queue `samples` (16 kHz mono) for transcription
+    pub fn submit(&self, samples: Vec<f32>, options: JobOptions) -> Pending {
+        let (reply, result) = oneshot::channel();
+        let job = Job {
+            samples,
+            options,
+            reply,
+        };
+        if let Err(mpsc::SendError(job)) = self.jobs.send(job) {
+            let _ = job
+                .reply
+                .send(Err(anyhow::anyhow!("Inference worker stopped")));
+        }
+        Pending(result)
+    }
+
+    pub fn model_path(&self) -> &Path {
+        &self.model_path
+    }
+}
+
+fn run_jobs<E: Engine>(mut engine: E, queue: mpsc::Receiver<Job>) {
+    for job in queue {
+        let Job {
+            samples,
+            options,
+            reply,
+        } = job;
+
+        if options.cancel.is_cancelled() {
+            let _ = reply.send(Err(Cancelled.into()));
+            continue;
+        }
+
+        let result = engine.run(
+            &samples,
+            &options.transcription,
+            options.single_segment,
+            options.progress,
+        );
+
+        let result = if options.cancel.is_cancelled() {
+            Err(Cancelled.into())
+        } else {
+            result.map(|(segments, detection)| Transcript {
+                segments,
+                detection,
+            })
+        };
+        // The caller may have stopped waiting
+        let _ = reply.send(result);
+    }
+    log::info!("Inference worker stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::Language;
+    use std::sync::Mutex;
+
+    /// Input lengths in the order the engine saw them
+    type Seen = Arc<Mutex<Vec<usize>>>;
+
+    /// Signals when a job starts, then waits to be released
+    struct Gate {
+        started: mpsc::Sender<()>,
+        release: mpsc::Receiver<()>,
+    }
+
+    /// Transcribes every input as its length
+    struct FakeEngine {
+        gate: Option<Gate>,
+        seen: Seen,
+    }
+
+    impl Engine for FakeEngine {
+        fn run(
+            &mut self,
+            samples: &[f32],
+            _options: &TranscriptionOptions,
+            _single_segment: bool,
+            progress: Option<ProgressFn>,
+        ) -> Result<(Vec<Segment>, Detection)> {
+            if let Some(gate) = &self.gate {
+                gate.started.send(()).unwrap();
+                gate.release.recv().unwrap();
+            }
+            self.seen.lock().unwrap().push(samples.len());
+            if let Some(mut progress) = progress {
+                progress(50);
+                progress(100);
+            }
+            Ok((
+                vec![Segment {
+                    text: samples.len().to_string(),
+                    start: 0.0,
+                    end: 1.0,
+                }],
+                Detection {
+                    language: Language::Known("en"),
+                    confidence: Some(0.9),
+                },
+            ))
+        }
+    }
+
+    fn worker(gate: Option<Gate>) -> (InferenceWorker, Seen) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let engine = FakeEngine {
+            gate,
+            seen: seen.clone(),
+        };
+        let worker = InferenceWorker::spawn(engine, PathBuf::from("fake.bin")).unwrap();
+        (worker, seen)
+    }
+
+    #[test]
+    fn test_jobs_run_in_order() {
+        let (worker, seen) = worker(None);
+        let first = worker.submit(vec![0.0; 3], JobOptions::default());
+        let second = worker.submit(vec![0.0; 5], JobOptions::default());
+
+        assert_eq!(second.wait().unwrap().text(), "5");
+        assert_eq!(first.wait().unwrap().text(), "3");
+        assert_eq!(*seen.lock().unwrap(), vec![3, 5]);
+    }
+
+    #[test]
+    fn test_progress_is_reported() {
+        let (worker, _) = worker(None);
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let options = JobOptions {
+            progress: Some(Box::new(move |p| sink.lock().unwrap().push(p))),
+            ..JobOptions::default()
+        };
+
+        worker.submit(vec![0.0; 1], options).wait().unwrap();
+        assert_eq!(*reported.lock().unwrap(), vec![50, 100]);
+    }
+
+    /// A worker whose jobs block until released, with the channels driving it
+    fn gated_worker() -> (InferenceWorker, Seen, mpsc::Receiver<()>, mpsc::Sender<()>) {
+        let (started, on_start) = mpsc::channel();
+        let (release, released) = mpsc::channel();
+        let gate = Gate {
+            started,
+            release: released,
+        };
+        let (worker, seen) = worker(Some(gate));
+        (worker, seen, on_start, release)
+    }
+
+    #[test]
+    fn test_cancelled_job_is_skipped() {
+        let (worker, seen, on_start, release) = gated_worker();
+
+        let running = worker.submit(vec![0.0; 1], JobOptions::default());
+        on_start.recv().unwrap();
+        let cancel = CancelToken::default();
+        let queued = worker.submit(
+            vec![0.0; 2],
+            JobOptions {
+                cancel: cancel.clone(),
+                ..JobOptions::default()
+            },
+        );
+        cancel.cancel();
+        release.send(()).unwrap();
+
+        assert!(running.wait().is_ok());
+        assert!(queued.wait().unwrap_err().is::<Cancelled>());
+        assert_eq!(*seen.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_result_of_job_cancelled_while_running_is_discarded() {
+        let (worker, seen, on_start, release) = gated_worker();
+
+        let cancel = CancelToken::default();
+        let running = worker.submit(
+            vec![0.0; 4],
+            JobOptions {
+                cancel: cancel.clone(),
+                ..JobOptions::default()
+            },
+        );
+        on_start.recv().unwrap();
+        cancel.cancel();
+        release.send(()).unwrap();
+
+        assert!(running.wait().unwrap_err().is::<Cancelled>());
+        assert_eq!(*seen.lock().unwrap(), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_result_can_be_awaited() {
+        let (worker, _) = worker(None);
+        let transcript = worker
+            .submit(vec![0.0; 7], JobOptions::default())
+            .result()
+            .await
+            .unwrap();
+        assert_eq!(transcript.detection.language, Language::Known("en"));
+        assert_eq!(transcript.text(), "7");
+    }
+}
@@ -2,23 +2,53 @@
 //!
 //! Main entry point for the Tauri application.
 
+mod active_model;
 mod audio;
 mod autostart;
+mod cleaner;
 mod clipboard;
+mod commands;
 mod deepseek;
+mod foreground;
+mod fs_util;
+mod glossary;
 mod hotkey;
+mod inference;
+mod models;
+mod monitor;
+mod pipeline;
+mod postprocess;
+mod resample;
+mod secrets;
+mod settings;
+mod shortcut;
 mod sound;
+mod sse;
+mod streaming;
+mod templates;
 mod tray;
 mod transcription;
+mod translation;
+mod trigger;
+mod vad;
 
+use active_model::{ActiveModel, ModelState};
 use audio::AudioCapture;
 use clipboard::ClipboardManager;
-use deepseek::DeepSeekClient;
 use hotkey::RecordingState;
+use models::ModelManager;
+use monitor::MonitorConfig;
+use pipeline::Pipeline;
+use postprocess::PostProcessors;
+use settings::{Settings, SettingsStore};
+use shortcut::Hotkey;
 use sound::SoundPlayer;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tauri::Manager;
-use transcription::Transcriber;
+use tauri::{AppHandle, Emitter, Manager};
+use templates::Templates;
+use trigger::HotkeyTrigger;
 
 fn main() {
     // Initialize logger
@@ -27,7 +57,7 @@ fn main() {
 
     log::info!("Starting ValleyFlow...");
 
-    // Load environment variables from .env file
+    // Older versions read the API key from .env; it is migrated into the secret store
     if let Err(e) = dotenvy::from_filename(".env") {
         log::debug!("No .env file found: {}", e);
     }
@@ -35,21 +65,79 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .invoke_handler(tauri::generate_handler![
+            commands::list_audio_devices,
+            commands::select_audio_device,
+            commands::set_api_key,
+            commands::get_api_key_status,
+            commands::start_recording,
+            commands::stop_recording,
+            commands::cancel_recording,
+            commands::get_recording_status,
+            commands::get_pipeline_state,
+            commands::list_languages,
+            commands::get_glossary,
+            commands::set_glossary,
+            commands::list_templates,
+            commands::save_template,
+            commands::delete_template,
+            commands::select_template,
+            commands::get_foreground_app,
+            commands::get_model_status,
+            commands::load_model,
+            commands::set_model_path,
+            commands::list_models,
+            commands::download_model,
+            commands::cancel_model_download,
+            commands::select_model,
+            commands::get_settings,
+            commands::update_settings,
+            commands::set_hotkey,
+            commands::get_autostart,
+            commands::set_autostart,
+        ])
         .setup(|app| {
+            // Load settings before anything that depends on them
+            let mut settings_store = SettingsStore::load(settings::default_path());
+            let settings = settings_store.get().clone();
+            let secrets = secrets::open_default();
+            migrate_api_key(&mut settings_store, secrets.as_ref());
+            apply_autostart(&mut settings_store);
+
             // Initialize audio capture
             let audio_capture = Arc::new(Mutex::new(AudioCapture::new()?));
 
-            // Initialize transcriber (will fail gracefully if model not found)
-            let transcriber = match Transcriber::new(None) {
-                Ok(t) => Some(Arc::new(Mutex::new(t))),
-                Err(e) => {
-                    log::warn!("Transcriber not initialized: {}. Download model first.", e);
-                    None
+            // The Whisper model is loaded on first use; until then (or if it is missing) the
+            // frontend sees its status through `model-status`
+            let model_manager = Arc::new(ModelManager::new(models::default_dir()));
+            let model_path = match &settings.model_path {
+                Some(path) => PathBuf::from(path),
+                None => {
+                    let info = models::find(&settings.model)
+                        .or_else(|| models::find(models::DEFAULT_MODEL))
+                        .expect("default model is in the catalog");
+                    model_manager.path(info)
                 }
             };
+            let status_app = app.handle().clone();
+            let model = Arc::new(ActiveModel::new(model_path, move |status| {
+                let _ = status_app.emit("model-status", status);
+            }));
+            if model.status().state == ModelState::Missing {
+                log::warn!(
+                    "No Whisper model at {}. Download one in Settings.",
+                    model.status().path
+                );
+            }
+
+            // Initialize post-processing providers
+            let post_processors = Arc::new(Mutex::new(PostProcessors::new(
+                secrets,
+                &settings.post_processor,
+            )));
 
-            // Initialize DeepSeek client
-            let deepseek_client = Arc::new(Mutex::new(DeepSeekClient::new()));
+            // Output templates; the built-in ones are written on first run
+            let templates = Arc::new(Mutex::new(Templates::load(&templates::default_dir())));
 
             // Initialize clipboard manager
             let clipboard = Arc::new(Mutex::new(ClipboardManager::new()?));
@@ -58,44 +146,45 @@ fn main() {
             let sound_player = Arc::new(Mutex::new(SoundPlayer::new()));
 
             // Initialize recording state
-            let recording_state = Arc::new(Mutex::new(RecordingState {
-                is_recording: false,
+            let mut recording_state = RecordingState {
+                pipeline: Pipeline::default(),
                 audio_capture: audio_capture.clone(),
-                transcriber: transcriber.clone(),
-                deepseek_client: deepseek_client.clone(),
+                model,
+                post_processors: post_processors.clone(),
                 clipboard: clipboard.clone(),
                 sound_player: sound_player.clone(),
-            }));
+                streaming_enabled: true,
+                streaming_session: None,
+                transcription: Default::default(),
+                glossary: Arc::new(glossary::load(&glossary::default_path())),
+                translation: None,
+                templates: templates.clone(),
+                template: templates::DEFAULT_TEMPLATE.to_string(),
+                foreground: foreground::detector(),
+                app_rules: Vec::new(),
+                monitor_config: MonitorConfig::default(),
+                monitor: None,
+                cancel_on_escape: true,
+                trigger: HotkeyTrigger::default(),
+                hotkey: None,
+                skip_post_processing: None,
+            };
+            recording_state.apply_settings(&settings);
+            let recording_state = Arc::new(Mutex::new(recording_state));
 
             // Store state in app
             app.manage(recording_state.clone());
-            app.manage(deepseek_client.clone());
+            app.manage(post_processors.clone());
+            app.manage(templates);
+            app.manage(Arc::new(Mutex::new(settings_store)));
+            app.manage(model_manager);
 
             // Setup system tray
             tray::setup_tray(app)?;
 
             // Setup global hotkey
-            let shortcut = Shortcut::new(
-                Some(
-                    tauri_plugin_global_shortcut::Modifiers::CONTROL
-                        | tauri_plugin_global_shortcut::Modifiers::SHIFT,
-                ),
-                tauri_plugin_global_shortcut::Code::Space,
-            );
-
-            let state_for_hotkey = recording_state.clone();
-            app.global_shortcut().on_shortcut(
-                shortcut,
-                move |_app, _shortcut, _event| {
-                    log::info!("Hotkey pressed: Ctrl+Shift+Space");
-                    hotkey::toggle_recording(&_app.app_handle(), &state_for_hotkey);
-                },
-            )?;
-
-            // Enable autostart by default
-            if let Err(e) = autostart::enable_autostart() {
-                log::warn!("Failed to enable autostart: {}", e);
-            }
+            register_recording_hotkey(app.handle(), &recording_state, &settings.hotkey);
+            register_template_hotkeys(app.handle(), &settings.template_hotkeys);
 
             // Hide main window on startup (tray-only app)
             if let Some(window) = app.get_webview_window("main") {
@@ -108,3 +197,82 @@ fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+/// One-time move of a plaintext API key (old settings file or `.env`) into the secret store.
+///
+/// Once it has run, a key cleared in the app is not brought back from `.env`.
+fn migrate_api_key(settings_store: &mut SettingsStore, secrets: &dyn secrets::SecretStore) {
+    let from_settings = settings_store.take_legacy_api_key();
+    if settings_store.get().api_key_migrated {
+        return;
+    }
+    let legacy = from_settings.or_else(|| std::env::var("DEEPSEEK_API_KEY").ok());
+
+    if let Err(e) = secrets::migrate_api_key(secrets, legacy.as_deref()) {
+        log::warn!("Failed to migrate API key to {}: {:#}", secrets.name(), e);
+        return;
+    }
+    // Also rewrites the settings file without the plaintext key
+    if let Err(e) = settings_store.update(&serde_json::json!({ "apiKeyMigrated": true })) {
+        log::warn!("Failed to rewrite settings: {:#}", e);
+    }
+}
+
+/// Make the system autostart entry match the settings.
+///
+/// The first run turns autostart on; settings from before the choice was saved
+/// keep whatever the system has.
+fn apply_autostart(settings_store: &mut SettingsStore) {
+    let saved = settings_store.get().autostart;
+    let enabled = match saved {
+        Some(enabled) => enabled,
+        None if settings_store.is_new() => true,
+        None => autostart::is_autostart_enabled(),
+    };
+
+    let result = if enabled {
+        autostart::enable_autostart()
+    } else {
+        autostart::disable_autostart()
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to update autostart: {}", e);
+    }
+
+    if saved.is_none() {
+        if let Err(e) = settings_store.update(&serde_json::json!({ "autostart": enabled })) {
+            log::warn!("Failed to save the autostart setting: {:#}", e);
+        }
+    }
+}
+
+/// Register the configured recording shortcut, falling back to the default one
+fn register_recording_hotkey(app: &AppHandle, state: &Arc<Mutex<RecordingState>>, hotkey: &str) {
+    let register = |hotkey: &str| {
+        hotkey
+            .parse::<Hotkey>()
+            .and_then(|hotkey| shortcut::register(app, state, &hotkey))
+    };
+
+    if let Err(e) = register(hotkey) {
+        log::error!("Recording shortcut {} unavailable: {}", hotkey, e);
+        let default = Settings::default().hotkey;
+        if hotkey != default {
+            if let Err(e) = register(&default) {
+                log::error!("Default shortcut {} unavailable too: {}", default, e);
+            }
+        }
+    }
+}
+
+/// Register the template shortcuts; one that is unavailable does not stop the others
+fn register_template_hotkeys(app: &AppHandle, hotkeys: &BTreeMap<String, String>) {
+    for (id, hotkey) in hotkeys {
+        let result = hotkey
+            .parse::<Hotkey>()
+            .and_then(|hotkey| shortcut::register_template(app, id, &hotkey));
+        if let Err(e) = result {
+            log::error!("Shortcut {} for template {} unavailable: {}", hotkey, id, e);
+        }
+    }
+}
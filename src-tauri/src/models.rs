@@ -0,0 +1,590 @@
+//! Whisper model catalog and downloads.
+//!
+//! Models come from the whisper.cpp repository on Hugging Face. Downloads go
+//! to a `.part` file that later attempts resume with a `Range` request, and a
+//! file only gets its final name once its SHA-256 matches the checksum the
+//! repository publishes for it.
+
+use crate::inference::{CancelToken, Cancelled};
+use anyhow::{Context, Result};
+use reqwest::header::RANGE;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Model used until the user picks another one
+pub const DEFAULT_MODEL: &str = "small";
+
+const DOWNLOAD_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// File listing with the SHA-256 of every LFS object in the repository
+const MANIFEST_URL: &str = "https://huggingface.co/api/models/ggerganov/whisper.cpp/tree/main";
+
+/// Downloaded bytes between two progress reports
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: &'static str,
+    pub file_name: &'static str,
+    /// Approximate download size
+    pub size_mb: u32,
+    /// Smaller and faster, slightly less accurate
+    pub quantized: bool,
+}
+
+const fn model(
+    id: &'static str,
+    file_name: &'static str,
+    size_mb: u32,
+    quantized: bool,
+) -> ModelInfo {
+    ModelInfo {
+        id,
+        file_name,
+        size_mb,
+        quantized,
+    }
+}
+
+pub const CATALOG: &[ModelInfo] = &[
+    model("tiny", "ggml-tiny.bin", 75, false),
+    model("tiny-q5_1", "ggml-tiny-q5_1.bin", 31, true),
+    model("base", "ggml-base.bin", 142, false),
+    model("base-q5_1", "ggml-base-q5_1.bin", 57, true),
+    model("small", "ggml-small.bin", 466, false),
+    model("small-q5_1", "ggml-small-q5_1.bin", 181, true),
+    model("medium", "ggml-medium.bin", 1500, false),
+    model("medium-q5_0", "ggml-medium-q5_0.bin", 514, true),
+    model("large-v3", "ggml-large-v3.bin", 2900, false),
+    model("large-v3-q5_0", "ggml-large-v3-q5_0.bin", 1080, true),
+];
+
+pub fn find(id: &str) -> Option<&'static ModelInfo> {
+    CATALOG.iter().find(|info| info.id == id)
+}
+
+/// Where models are stored
+pub fn default_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ValleyFlow")
+        .join("models")
+}
+
+/// A catalog entry with its local state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelEntry {
+    #[serde(flatten)]
+    pub info: ModelInfo,
+    pub downloaded: bool,
+}
+
+/// Payload of the `model-download-progress` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub id: String,
+    pub downloaded: u64,
+    /// `None` if the server did not send a length
+    pub total: Option<u64>,
+}
+
+/// One file in the repository listing
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    path: String,
+    lfs: Option<LfsObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsObject {
+    /// SHA-256 of the file contents
+    oid: String,
+}
+
+pub struct ModelManager {
+    client: Client,
+    dir: PathBuf,
+    download_url: String,
+    manifest_url: String,
+    /// Cancels the download in progress
+    download: Mutex<Option<CancelToken>>,
+}
+
+impl ModelManager {
+    pub fn new(dir: PathBuf) -> Self {
+        Self::with_urls(dir, DOWNLOAD_URL, MANIFEST_URL)
+    }
+
+    fn with_urls(dir: PathBuf, download_url: &str, manifest_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            dir,
+            download_url: download_url.to_string(),
+            manifest_url: manifest_url.to_string(),
+            download: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self, info: &ModelInfo) -> PathBuf {
+        self.dir.join(info.file_name)
+    }
+
+    fn part_path(&self, info: &ModelInfo) -> PathBuf {
+        self.dir.join(format!("{}.part", info.file_name))
+    }
+
+    pub fn list(&self) -> Vec<ModelEntry> {
+        CATALOG
+            .iter()
+            .map(|info| ModelEntry {
+                info: *info,
+                downloaded: self.path(info).exists(),
+            })
+            .collect()
+    }
+
+    /// Stop the running download; the partial file is kept for resuming
+    pub fn cancel_download(&self) -> bool {
+        match self.download.lock().unwrap().as_ref() {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Download `info` unless it is already present, reporting progress as it goes
+    pub async fn download(
+        &self,
+        info: &ModelInfo,
+        progress: impl FnMut(DownloadProgress),
+    ) -> Result<PathBuf> {
+        let target = self.path(info);
+        if target.exists() {
+            return Ok(target);
+        }
+
+        let cancel = {
+            let mut download = self.download.lock().unwrap();
+            if download.is_some() {
+                anyhow::bail!("Another model download is already running");
+            }
+            download.insert(CancelToken::default()).clone()
+        };
+
+        let result = self.fetch(info, &cancel, progress).await;
+        *self.download.lock().unwrap() = None;
+        result
+    }
+
+    async fn fetch(
+        &self,
+        info: &ModelInfo,
+        cancel: &CancelToken,
+        mut progress: impl FnMut(DownloadProgress),
+    ) -> Result<PathBuf> {
+        let expected = self.expected_sha256(info).await?;
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let part = self.part_path(info);
+        let mut downloaded = part.metadata().map(|m| m.len()).unwrap_or(0);
+
+        let url = format!("{}/{}", self.download_url, info.file_name);
+        let mut request = self.client.get(&url);
+        if downloaded > 0 {
+            log::info!("Resuming {} at {} bytes", info.file_name, downloaded);
+            request = request.header(RANGE, format!("bytes={}-", downloaded));
+        }
+        let mut response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to request {}", url))?;
+
+        let status = response.status();
+        // The partial file already holds everything
+        let complete = status == StatusCode::RANGE_NOT_SATISFIABLE && downloaded > 0;
+        if !complete {
+            if !status.is_success() {
+                anyhow::bail!("Model download failed: HTTP {}", status);
+            }
+
+            // A server that ignores `Range` sends the whole file again
+            let resumed = status == StatusCode::PARTIAL_CONTENT;
+            if !resumed {
+                downloaded = 0;
+            }
+            let total = response.content_length().map(|len| len + downloaded);
+
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed)
+                .truncate(!resumed)
+                .open(&part)
+                .with_context(|| format!("Failed to open {}", part.display()))?;
+
+            let mut reported = downloaded;
+            while let Some(chunk) = response
+                .chunk()
+                .await
+                .context("Model download interrupted")?
+            {
+                if cancel.is_cancelled() {
+                    return Err(Cancelled.into());
+                }
+                file.write_all(&chunk)?;
+                downloaded += chunk.len() as u64;
+
+                if downloaded - reported >= PROGRESS_STEP {
+                    reported = downloaded;
+                    progress(DownloadProgress {
+                        id: info.id.to_string(),
+                        downloaded,
+                        total,
+                    });
+                }
+            }
+            file.flush()?;
+
+            progress(DownloadProgress {
+                id: info.id.to_string(),
+                downloaded,
+                total,
+            });
+        }
+
+        let hashed = part.clone();
+        let actual = tauri::async_runtime::spawn_blocking(move || sha256_file(&hashed)).await??;
+        if actual != expected {
+            // Corrupt data cannot be resumed from
+            let _ = fs::remove_file(&part);
+            anyhow::bail!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                info.file_name,
+                expected,
+                actual
+            );
+        }
+
+        let target = self.path(info);
+        fs::rename(&part, &target)?;
+        log::info!("Model {} downloaded and verified", info.id);
+        Ok(target)
+    }
+
+    /// Look up the published SHA-256 of `info`
+    async fn expected_sha256(&self, info: &ModelInfo) -> Result<String> {
+        let response = self
+            .client
+            .get(&self.manifest_url)
+            .send()
+            .await
+            .context("Failed to fetch the model checksums")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to fetch the model checksums: HTTP {}",
+                response.status()
+            );
+        }
+
+        let manifest: Vec<ManifestEntry> = response
+            .json()
+            .await
+            .context("Failed to parse the model checksums")?;
+        manifest
+            .into_iter()
+            .find(|entry| entry.path == info.file_name)
+            .and_then(|entry| entry.lfs)
+            .map(|lfs| lfs.oid.to_lowercase())
+            .with_context(|| format!("No checksum published for {}", info.file_name))
+    }
+}
+
+/// Hex SHA-256 of a file, read in chunks
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::temp_dir;
+    use std::io::BufRead;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+
+    const FILE_NAME: &str = "ggml-tiny.bin";
+
+    /// Minimal HTTP server standing in for Hugging Face
+    struct TestServer {
+        url: String,
+        /// `Range` header of every model request, `None` when absent
+        ranges: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl TestServer {
+        /// Serve `body` as the tiny model and `checksum` in the manifest.
+        /// The first model response is cut off after `cut_at` bytes if set.
+        fn start(body: Vec<u8>, checksum: String, cut_at: Option<usize>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let ranges = Arc::new(Mutex::new(Vec::new()));
+            let seen = ranges.clone();
+
+            std::thread::spawn(move || {
+                let mut cut_at = cut_at;
+                for stream in listener.incoming() {
+                    let stream = stream.unwrap();
+                    handle(stream, &body, &checksum, &seen, &mut cut_at);
+                }
+            });
+
+            Self { url, ranges }
+        }
+
+        fn manager(&self, dir: &Path) -> ModelManager {
+            ModelManager::with_urls(
+                dir.to_path_buf(),
+                &format!("{}/resolve", self.url),
+                &format!("{}/tree", self.url),
+            )
+        }
+    }
+
+    fn handle(
+        mut stream: TcpStream,
+        body: &[u8],
+        checksum: &str,
+        ranges: &Mutex<Vec<Option<String>>>,
+        cut_at: &mut Option<usize>,
+    ) {
+        let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("")
+            .to_string();
+
+        let mut range = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("range") {
+                    range = Some(value.trim().to_string());
+                }
+            }
+        }
+
+        if path == "/tree" {
+            let manifest = serde_json::json!([
+                { "type": "file", "path": "README.md", "size": 10 },
+                { "type": "file", "path": FILE_NAME, "size": body.len(),
+                  "lfs": { "oid": checksum, "size": body.len() } },
+            ])
+            .to_string();
+            respond(&mut stream, "200 OK", &[], manifest.as_bytes(), None);
+            return;
+        }
+        if path != format!("/resolve/{}", FILE_NAME) {
+            respond(&mut stream, "404 Not Found", &[], b"", None);
+            return;
+        }
+
+        ranges.lock().unwrap().push(range.clone());
+        let start = range
+            .as_deref()
+            .and_then(|r| r.strip_prefix("bytes="))
+            .and_then(|r| r.strip_suffix('-'))
+            .and_then(|r| r.parse::<usize>().ok());
+        match start {
+            Some(start) if start >= body.len() => {
+                respond(&mut stream, "416 Range Not Satisfiable", &[], b"", None)
+            }
+            Some(start) => {
+                let header = format!(
+                    "Content-Range: bytes {}-{}/{}",
+                    start,
+                    body.len() - 1,
+                    body.len()
+                );
+                respond(
+                    &mut stream,
+                    "206 Partial Content",
+                    &[&header],
+                    &body[start..],
+                    cut_at.take(),
+                )
+            }
+            None => respond(&mut stream, "200 OK", &[], body, cut_at.take()),
+        }
+    }
+
+    fn respond(
+        stream: &mut TcpStream,
+        status: &str,
+        headers: &[&str],
+        body: &[u8],
+        cut_at: Option<usize>,
+    ) {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for header in headers {
+            head.push_str(header);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+
+        let _ = stream.write_all(head.as_bytes());
+        let sent = cut_at.unwrap_or(body.len()).min(body.len());
+        let _ = stream.write_all(&body[..sent]);
+    }
+
+    fn model_bytes() -> Vec<u8> {
+        (0..3 * PROGRESS_STEP as usize)
+            .map(|i| (i % 251) as u8)
+            .collect()
+    }
+
+    fn checksum(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    fn tiny() -> &'static ModelInfo {
+        find("tiny").unwrap()
+    }
+
+    #[test]
+    fn test_catalog_ids_and_files_are_unique() {
+        for (i, a) in CATALOG.iter().enumerate() {
+            for b in &CATALOG[i + 1..] {
+                assert_ne!(a.id, b.id);
+                assert_ne!(a.file_name, b.file_name);
+            }
+        }
+        assert!(find(DEFAULT_MODEL).is_some());
+        assert!(find("large-v3-q5_0").unwrap().quantized);
+    }
+
+    #[tokio::test]
+    async fn test_download_verifies_and_reports_progress() {
+        let body = model_bytes();
+        let server = TestServer::start(body.clone(), checksum(&body), None);
+        let dir = temp_dir("models-download");
+        let manager = server.manager(&dir);
+
+        let mut reports = Vec::new();
+        let path = manager.download(tiny(), |p| reports.push(p)).await.unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), body);
+        assert!(!manager.part_path(tiny()).exists());
+        assert!(manager
+            .list()
+            .iter()
+            .any(|e| e.info.id == "tiny" && e.downloaded));
+
+        let last = reports.last().unwrap();
+        assert_eq!(last.downloaded, body.len() as u64);
+        assert_eq!(last.total, Some(body.len() as u64));
+        assert!(reports.len() >= 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download_resumes() {
+        let body = model_bytes();
+        let cut = PROGRESS_STEP as usize + 123;
+        let server = TestServer::start(body.clone(), checksum(&body), Some(cut));
+        let dir = temp_dir("models-resume");
+        let manager = server.manager(&dir);
+
+        assert!(manager.download(tiny(), |_| {}).await.is_err());
+        assert_eq!(
+            fs::metadata(manager.part_path(tiny())).unwrap().len(),
+            cut as u64
+        );
+
+        let path = manager.download(tiny(), |_| {}).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), body);
+        assert_eq!(
+            *server.ranges.lock().unwrap(),
+            vec![None, Some(format!("bytes={}-", cut))]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_discards_download() {
+        let body = model_bytes();
+        let server = TestServer::start(body, checksum(b"something else"), None);
+        let dir = temp_dir("models-mismatch");
+        let manager = server.manager(&dir);
+
+        let error = manager.download(tiny(), |_| {}).await.unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"));
+        assert!(!manager.path(tiny()).exists());
+        assert!(!manager.part_path(tiny()).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_complete_part_file_is_only_verified() {
+        let body = model_bytes();
+        let server = TestServer::start(body.clone(), checksum(&body), None);
+        let dir = temp_dir("models-complete");
+        let manager = server.manager(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(manager.part_path(tiny()), &body).unwrap();
+
+        let path = manager.download(tiny(), |_| {}).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), body);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_entry_serialization() {
+        let entry = ModelEntry {
+            info: *tiny(),
+            downloaded: false,
+        };
+        assert_eq!(
+            serde_json::to_value(entry).unwrap(),
+            serde_json::json!({
+                "id": "tiny",
+                "fileName": "ggml-tiny.bin",
+                "sizeMb": 75,
+                "quantized": false,
+                "downloaded": false,
+            })
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Background watcher for an active recording.
+//!
+//! Polls the capture buffer while the user is recording and stops the
+//! recording through the normal hotkey path when a stop condition is met:
+//! the capture buffer filling up (maximum length) or optional auto-stop
+//! after a stretch of silence.
+
+use crate::audio::CaptureTap;
+use crate::hotkey::{self, RecordingState};
+use crate::resample::downmix_to_mono;
+use crate::vad::SilenceDetector;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Warn the frontend this long before the length limit stops the recording
+const LIMIT_WARNING_SECS: f32 = 30.0;
+
+/// Payload of the `recording-limit-warning` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitWarning {
+    pub remaining_secs: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MonitorConfig {
+    /// Stop after this many seconds of silence following speech
+    pub auto_stop_silence_secs: Option<f32>,
+}
+
+pub struct RecordingMonitor {
+    stopped: Arc<AtomicBool>,
+}
+
+impl RecordingMonitor {
+    pub fn start(
+        app: AppHandle,
+        state: Arc<Mutex<RecordingState>>,
+        tap: CaptureTap,
+        config: MonitorConfig,
+    ) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = stopped.clone();
+
+        std::thread::spawn(move || {
+            let mut detector = SilenceDetector::new(tap.sample_rate);
+            let mut read_offset = 0;
+            let mut warned = false;
+
+            while !worker_stopped.load(Ordering::SeqCst) {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let chunk = tap.read_from(read_offset);
+                read_offset += chunk.len();
+                detector.feed(&downmix_to_mono(&chunk, tap.channels));
+
+                if tap.limit_reached() {
+                    hotkey::auto_stop(&app, &state, &worker_stopped, "max-duration");
+                    break;
+                }
+
+                let remaining = tap.remaining_secs();
+                if !warned && remaining <= LIMIT_WARNING_SECS {
+                    warned = true;
+                    log::info!("Recording will stop in {:.0}s", remaining);
+                    let _ = app.emit(
+                        "recording-limit-warning",
+                        LimitWarning {
+                            remaining_secs: remaining.ceil() as u32,
+                        },
+                    );
+                }
+
+                if let Some(secs) = config.auto_stop_silence_secs {
+                    if detector.should_stop(secs) {
+                        hotkey::auto_stop(&app, &state, &worker_stopped, "silence");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self { stopped }
+    }
+
+    /// Stop watching. Safe to call while the monitor itself is stopping the recording.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,233 @@
+//! State of the record → transcribe → post-process → deliver pipeline.
+//!
+//! Only one recording goes through the pipeline at a time. Every change is
+//! checked against the transition table, and a start request (hotkey press or
+//! `start_recording` command) has a defined meaning in every state.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", content = "message", rename_all = "camelCase")]
+pub enum PipelineState {
+    Idle,
+    Recording,
+    Transcribing,
+    PostProcessing,
+    Delivering,
+    /// The last run failed; behaves like `Idle` for new recordings
+    Error(String),
+}
+
+/// What a request to start recording does in a given state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartBehaviour {
+    Start,
+    /// Start as soon as the current result has been delivered
+    Queue,
+    /// Ignore the request; the current step cannot be interrupted
+    Reject,
+    /// Skip the rest of post-processing and deliver the raw transcript
+    Cancel,
+}
+
+impl PipelineState {
+    /// Whether the pipeline may move from `self` to `next`
+    pub fn can_transition_to(&self, next: &PipelineState) -> bool {
+        use PipelineState::*;
+        matches!(
+            (self, next),
+            (Idle | Error(_), Recording)
+                // Capture failed to start
+                | (Idle | Error(_), Error(_))
+                | (Error(_), Idle)
+                // Stopped, cancelled, or the capture failed
+                | (Recording, Transcribing | Idle | Error(_))
+                // Post-processing is skipped without an API key
+                | (Transcribing, PostProcessing | Delivering | Error(_))
+                | (PostProcessing, Delivering | Error(_))
+                | (Delivering, Idle | Error(_))
+        )
+    }
+
+    pub fn start_behaviour(&self) -> StartBehaviour {
+        match self {
+            PipelineState::Idle | PipelineState::Error(_) => StartBehaviour::Start,
+            PipelineState::Recording | PipelineState::Transcribing => StartBehaviour::Reject,
+            PipelineState::PostProcessing => StartBehaviour::Cancel,
+            PipelineState::Delivering => StartBehaviour::Queue,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Pipeline {
+    state: PipelineState,
+    queued_start: bool,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            state: PipelineState::Idle,
+            queued_start: false,
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn state(&self) -> &PipelineState {
+        &self.state
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.state == PipelineState::Recording
+    }
+
+    /// Move to `next`, refusing transitions the table does not allow
+    pub fn transition(&mut self, next: PipelineState) -> anyhow::Result<()> {
+        if !self.state.can_transition_to(&next) {
+            anyhow::bail!(
+                "Illegal pipeline transition: {:?} -> {:?}",
+                self.state,
+                next
+            );
+        }
+        self.state = next;
+        Ok(())
+    }
+
+    pub fn queue_start(&mut self) {
+        self.queued_start = true;
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queued_start = false;
+    }
+
+    /// Consume a queued start once the pipeline can accept it
+    pub fn take_queued_start(&mut self) -> bool {
+        if self.state.start_behaviour() != StartBehaviour::Start {
+            return false;
+        }
+        std::mem::take(&mut self.queued_start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PipelineState::*;
+
+    fn all_states() -> Vec<PipelineState> {
+        vec![
+            Idle,
+            Recording,
+            Transcribing,
+            PostProcessing,
+            Delivering,
+            Error("failed".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_transition_table() {
+        let error = || Error("failed".to_string());
+        let allowed = [
+            (Idle, Recording),
+            (Idle, error()),
+            (Recording, Transcribing),
+            (Recording, Idle),
+            (Recording, error()),
+            (Transcribing, PostProcessing),
+            (Transcribing, Delivering),
+            (Transcribing, error()),
+            (PostProcessing, Delivering),
+            (PostProcessing, error()),
+            (Delivering, Idle),
+            (Delivering, error()),
+            (error(), Idle),
+            (error(), Recording),
+            (error(), error()),
+        ];
+
+        for from in all_states() {
+            for to in all_states() {
+                let expected = allowed.contains(&(from.clone(), to.clone()));
+                assert_eq!(
+                    from.can_transition_to(&to),
+                    expected,
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_start_behaviour_per_state() {
+        assert_eq!(Idle.start_behaviour(), StartBehaviour::Start);
+        assert_eq!(Recording.start_behaviour(), StartBehaviour::Reject);
+        assert_eq!(Transcribing.start_behaviour(), StartBehaviour::Reject);
+        assert_eq!(PostProcessing.start_behaviour(), StartBehaviour::Cancel);
+        assert_eq!(Delivering.start_behaviour(), StartBehaviour::Queue);
+        assert_eq!(
+            Error("x".to_string()).start_behaviour(),
+            StartBehaviour::Start
+        );
+    }
+
+    #[test]
+    fn test_full_run() {
+        let mut pipeline = Pipeline::default();
+        for state in [Recording, Transcribing, PostProcessing, Delivering, Idle] {
+            pipeline.transition(state).unwrap();
+        }
+        assert_eq!(pipeline.state(), &Idle);
+    }
+
+    #[test]
+    fn test_illegal_transition_keeps_state() {
+        let mut pipeline = Pipeline::default();
+        assert!(pipeline.transition(Delivering).is_err());
+        assert_eq!(pipeline.state(), &Idle);
+    }
+
+    #[test]
+    fn test_queued_start_waits_for_idle() {
+        let mut pipeline = Pipeline::default();
+        for state in [Recording, Transcribing, Delivering] {
+            pipeline.transition(state).unwrap();
+        }
+        pipeline.queue_start();
+        assert!(!pipeline.take_queued_start());
+
+        pipeline.transition(Idle).unwrap();
+        assert!(pipeline.take_queued_start());
+        assert!(!pipeline.take_queued_start());
+    }
+
+    #[test]
+    fn test_cleared_queue_does_not_start() {
+        let mut pipeline = Pipeline::default();
+        for state in [Recording, Transcribing, Delivering] {
+            pipeline.transition(state).unwrap();
+        }
+        pipeline.queue_start();
+        pipeline.clear_queue();
+        pipeline.transition(Idle).unwrap();
+        assert!(!pipeline.take_queued_start());
+    }
+
+    #[test]
+    fn test_state_serialization() {
+        assert_eq!(
+            serde_json::to_value(Recording).unwrap(),
+            serde_json::json!({ "state": "recording" })
+        );
+        assert_eq!(
+            serde_json::to_value(Error("No speech detected".to_string())).unwrap(),
+            serde_json::json!({ "state": "error", "message": "No speech detected" })
+        );
+    }
+}
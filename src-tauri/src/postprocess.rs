@@ -0,0 +1,924 @@
+//! Post-processing of raw transcripts by a language model.
+//!
+//! The pipeline talks to a `PostProcessor` and does not care which provider is
+//! behind it. DeepSeek, OpenAI-compatible APIs and local servers (Ollama,
+//! llama.cpp) all speak the OpenAI chat completions protocol, so they share
+//! `ChatClient`, which streams the response so it can be shown while the model
+//! writes it. When the selected model cannot be used the offline `RuleCleaner`
+//! stands in, or `NoOp` if that is turned off too.
+
+use crate::cleaner::RuleCleaner;
+use crate::deepseek::DeepSeekClient;
+use crate::secrets::{SecretStore, OPENAI_COMPATIBLE_API_KEY};
+use crate::sse::SseParser;
+use crate::templates::Template;
+use crate::transcription::Language;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+/// Ollama's OpenAI-compatible endpoint; llama.cpp's server listens on :8080/v1
+const DEFAULT_LOCAL_BASE_URL: &str = "http://localhost:11434/v1";
+const DEFAULT_LOCAL_MODEL: &str = "llama3.2";
+
+/// A transcript to clean up
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    pub text: &'a str,
+    /// Language of `text`; `Language::Unknown` keeps the language of the input
+    pub language: Language,
+    /// Translate the result into this language
+    pub translate_to: Option<Language>,
+    /// Output style the language model is asked for
+    pub template: &'a Template,
+}
+
+/// Receives the text produced so far while a response streams in
+pub type OnPartial<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+#[async_trait]
+pub trait PostProcessor: Send + Sync {
+    /// Provider name for logs
+    fn name(&self) -> &str;
+
+    /// Whether `process` can run; the raw transcript is pasted otherwise
+    fn is_available(&self) -> bool;
+
+    async fn process(&self, request: &Request<'_>) -> Result<String>;
+
+    /// Like `process`, passing the text produced so far to `on_partial` as it
+    /// arrives. Providers that cannot stream only return the result.
+    async fn process_streaming(
+        &self,
+        request: &Request<'_>,
+        on_partial: OnPartial<'_>,
+    ) -> Result<String> {
+        let _ = on_partial;
+        self.process(request).await
+    }
+}
+
+/// Which post-processor recordings go through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Provider {
+    /// No language model
+    None,
+    #[default]
+    DeepSeek,
+    /// Any server implementing the OpenAI chat completions API
+    OpenAiCompatible,
+    /// Ollama or a llama.cpp server on this machine
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PostProcessorSettings {
+    pub provider: Provider,
+    /// Base URL of the OpenAI-compatible API, without `/chat/completions`
+    pub openai_base_url: String,
+    pub openai_model: String,
+    /// Base URL of the local server's OpenAI-compatible API
+    pub local_base_url: String,
+    pub local_model: String,
+    /// Clean transcripts up offline when the provider is off or unavailable
+    pub offline_fallback: bool,
+}
+
+impl Default for PostProcessorSettings {
+    fn default() -> Self {
+        Self {
+            provider: Provider::default(),
+            openai_base_url: DEFAULT_OPENAI_BASE_URL.to_string(),
+            openai_model: DEFAULT_OPENAI_MODEL.to_string(),
+            local_base_url: DEFAULT_LOCAL_BASE_URL.to_string(),
+            local_model: DEFAULT_LOCAL_MODEL.to_string(),
+            offline_fallback: true,
+        }
+    }
+}
+
+impl PostProcessorSettings {
+    pub fn validate(&self) -> Result<()> {
+        for (url, model) in [
+            (&self.openai_base_url, &self.openai_model),
+            (&self.local_base_url, &self.local_model),
+        ] {
+            let parsed = Url::parse(url).with_context(|| format!("Invalid API URL: {}", url))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                anyhow::bail!("API URL must use http or https: {}", url);
+            }
+            if model.trim().is_empty() {
+                anyhow::bail!("Post-processing model must not be empty");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    temperature: f32,
+    max_tokens: u32,
+    stream: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+/// One event of a streamed completion
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    /// Set instead of `choices` when the server fails mid-stream
+    error: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+/// Client for an OpenAI-compatible chat completions endpoint
+#[derive(Clone)]
+pub struct ChatClient {
+    client: Client,
+    name: String,
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+    /// Refuse to run without `api_key`
+    requires_api_key: bool,
+}
+
+impl ChatClient {
+    pub fn new(name: &str, base_url: &str, model: &str) -> Self {
+        Self {
+            client: Client::new(),
+            name: name.to_string(),
+            endpoint: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key: None,
+            requires_api_key: false,
+        }
+    }
+
+    /// Report the client as unavailable until it has an API key
+    pub fn requiring_api_key(mut self) -> Self {
+        self.requires_api_key = true;
+        self
+    }
+
+    pub fn set_api_key(&mut self, api_key: Option<String>) {
+        self.api_key = api_key;
+    }
+
+    pub fn has_api_key(&self) -> bool {
+        self.api_key.is_some()
+    }
+
+    /// Ask for a completion, streamed if `on_partial` wants the text as it arrives
+    async fn complete(
+        &self,
+        request: &Request<'_>,
+        on_partial: Option<OnPartial<'_>>,
+    ) -> Result<String> {
+        if !self.is_available() {
+            anyhow::bail!("{} API key not configured", self.name);
+        }
+
+        let prompt = request.template.render(request);
+        let mut messages = Vec::new();
+        if let Some(system) = prompt.system {
+            messages.push(Message {
+                role: "system".to_string(),
+                content: system,
+            });
+        }
+        messages.push(Message {
+            role: "user".to_string(),
+            content: prompt.user,
+        });
+
+        let body = ChatRequest {
+            model: self.model.clone(),
+            messages,
+            temperature: 0.3,
+            max_tokens: 2048,
+            stream: on_partial.is_some(),
+        };
+
+        let mut http = self.client.post(&self.endpoint).json(&body);
+        if let Some(api_key) = &self.api_key {
+            http = http.bearer_auth(api_key);
+        }
+        let response = http
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to {}", self.name))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("{} API error: {} - {}", self.name, status, body);
+        }
+
+        // Servers that do not stream answer with a plain completion
+        let is_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let processed_text = match on_partial {
+            Some(on_partial) if is_stream => {
+                let streamed = self.read_stream(response, on_partial).await?;
+                Some(streamed.trim().to_string()).filter(|text| !text.is_empty())
+            }
+            _ => {
+                let chat_response: ChatResponse = response
+                    .json()
+                    .await
+                    .with_context(|| format!("Failed to parse {} response", self.name))?;
+                chat_response
+                    .choices
+                    .first()
+                    .map(|c| c.message.content.trim().to_string())
+            }
+        }
+        .unwrap_or_else(|| request.text.to_string());
+
+        log::info!(
+            "Text processed by {}: {} chars",
+            self.name,
+            processed_text.len()
+        );
+        Ok(processed_text)
+    }
+
+    /// Collect the text of a streamed completion. Dropping the future closes the
+    /// connection, which stops the server generating.
+    async fn read_stream(
+        &self,
+        mut response: Response,
+        on_partial: OnPartial<'_>,
+    ) -> Result<String> {
+        let mut parser = SseParser::default();
+        let mut text = String::new();
+        loop {
+            let chunk = response
+                .chunk()
+                .await
+                .with_context(|| format!("{} response stream broke off", self.name))?;
+            let events = match &chunk {
+                Some(chunk) => parser.feed(chunk),
+                None => parser.finish().into_iter().collect(),
+            };
+
+            for data in events {
+                // OpenAI and DeepSeek end with this; llama.cpp just closes the stream
+                if data == "[DONE]" {
+                    return Ok(text);
+                }
+                let content = self.chunk_content(&data)?;
+                if !content.is_empty() {
+                    text.push_str(&content);
+                    on_partial(text.trim_start());
+                }
+            }
+
+            if chunk.is_none() {
+                return Ok(text);
+            }
+        }
+    }
+
+    fn chunk_content(&self, data: &str) -> Result<String> {
+        let chunk: ChatChunk = serde_json::from_str(data)
+            .with_context(|| format!("Failed to parse {} stream event: {}", self.name, data))?;
+        if let Some(error) = chunk.error {
+            let message = error["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string());
+            anyhow::bail!("{} API error: {}", self.name, message);
+        }
+        Ok(chunk
+            .choices
+            .into_iter()
+            .filter_map(|choice| choice.delta.content)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl PostProcessor for ChatClient {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_available(&self) -> bool {
+        !self.requires_api_key || self.has_api_key()
+    }
+
+    async fn process(&self, request: &Request<'_>) -> Result<String> {
+        self.complete(request, None).await
+    }
+
+    async fn process_streaming(
+        &self,
+        request: &Request<'_>,
+        on_partial: OnPartial<'_>,
+    ) -> Result<String> {
+        self.complete(request, Some(on_partial)).await
+    }
+}
+
+/// Leaves transcripts untouched
+pub struct NoOp;
+
+#[async_trait]
+impl PostProcessor for NoOp {
+    fn name(&self) -> &str {
+        "none"
+    }
+
+    fn is_available(&self) -> bool {
+        false
+    }
+
+    async fn process(&self, request: &Request<'_>) -> Result<String> {
+        Ok(request.text.to_string())
+    }
+}
+
+/// The selected post-processor and the API keys of every provider
+pub struct PostProcessors {
+    secrets: Arc<dyn SecretStore>,
+    deepseek: DeepSeekClient,
+    openai: ChatClient,
+    local: ChatClient,
+    provider: Provider,
+    offline_fallback: bool,
+}
+
+impl PostProcessors {
+    pub fn new(secrets: Arc<dyn SecretStore>, settings: &PostProcessorSettings) -> Self {
+        let mut openai = ChatClient::new(
+            "OpenAI-compatible API",
+            &settings.openai_base_url,
+            &settings.openai_model,
+        );
+        openai.set_api_key(secrets.get(OPENAI_COMPATIBLE_API_KEY).unwrap_or_else(|e| {
+            log::warn!("Failed to read API key from {}: {:#}", secrets.name(), e);
+            None
+        }));
+
+        Self {
+            deepseek: DeepSeekClient::new(secrets.clone()),
+            secrets,
+            openai,
+            local: ChatClient::new(
+                "local model",
+                &settings.local_base_url,
+                &settings.local_model,
+            ),
+            provider: settings.provider,
+            offline_fallback: settings.offline_fallback,
+        }
+    }
+
+    /// Switch provider and endpoints; takes effect from the next recording
+    pub fn configure(&mut self, settings: &PostProcessorSettings) {
+        let api_key = self.openai.api_key.take();
+        self.openai = ChatClient::new(
+            "OpenAI-compatible API",
+            &settings.openai_base_url,
+            &settings.openai_model,
+        );
+        self.openai.set_api_key(api_key);
+        self.local = ChatClient::new(
+            "local model",
+            &settings.local_base_url,
+            &settings.local_model,
+        );
+        self.provider = settings.provider;
+        self.offline_fallback = settings.offline_fallback;
+    }
+
+    /// Store `key` for `provider` if non-empty, otherwise clear it
+    pub fn set_api_key(&mut self, provider: Provider, key: &str) -> Result<()> {
+        let key = key.trim();
+        match provider {
+            Provider::DeepSeek => self.deepseek.configure_api_key(key),
+            Provider::OpenAiCompatible if key.is_empty() => {
+                self.secrets.delete(OPENAI_COMPATIBLE_API_KEY)?;
+                self.openai.set_api_key(None);
+                Ok(())
+            }
+            Provider::OpenAiCompatible => {
+                self.secrets.set(OPENAI_COMPATIBLE_API_KEY, key)?;
+                self.openai.set_api_key(Some(key.to_string()));
+                Ok(())
+            }
+            Provider::None | Provider::Local => {
+                anyhow::bail!("{:?} post-processing does not use an API key", provider)
+            }
+        }
+    }
+
+    pub fn has_api_key(&self, provider: Provider) -> bool {
+        match provider {
+            Provider::DeepSeek => self.deepseek.has_api_key(),
+            Provider::OpenAiCompatible => self.openai.has_api_key(),
+            Provider::None | Provider::Local => false,
+        }
+    }
+
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    /// The post-processor for the next recording
+    pub fn current(&self) -> Arc<dyn PostProcessor> {
+        match self.provider {
+            Provider::None => Arc::new(NoOp),
+            Provider::DeepSeek => Arc::new(self.deepseek.clone()),
+            Provider::OpenAiCompatible => Arc::new(self.openai.clone()),
+            Provider::Local => Arc::new(self.local.clone()),
+        }
+    }
+
+    /// What cleans up transcripts when the current post-processor cannot
+    pub fn fallback(&self) -> Arc<dyn PostProcessor> {
+        if self.offline_fallback {
+            Arc::new(RuleCleaner)
+        } else {
+            Arc::new(NoOp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::MemoryStore;
+    use crate::templates;
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::sync::{LazyLock, Mutex};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    const POLISH: Language = Language::Known("pl");
+
+    static DEFAULT_TEMPLATE: LazyLock<Template> = LazyLock::new(templates::default_template);
+
+    /// What the mock server saw of a request
+    struct Received {
+        path: String,
+        authorization: Option<String>,
+        body: Value,
+    }
+
+    /// Minimal HTTP server answering every request through `respond`
+    struct MockServer {
+        url: String,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl MockServer {
+        /// Answer with `status` and a JSON `body`
+        fn start(status: &'static str, body: String) -> Self {
+            Self::serve(move |mut stream| {
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            })
+        }
+
+        /// Answer with recorded server-sent events, written `chunk_size` bytes at a time
+        fn stream(events: &'static [u8], chunk_size: usize) -> Self {
+            Self::serve(move |mut stream| {
+                write_event_stream(&mut stream, events.chunks(chunk_size)).unwrap();
+            })
+        }
+
+        fn serve(respond: impl Fn(TcpStream) + Send + 'static) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/v1", listener.local_addr().unwrap());
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let seen = received.clone();
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = stream.unwrap();
+                    let request = read_request(&stream);
+                    seen.lock().unwrap().push(request);
+                    respond(stream);
+                }
+            });
+
+            Self { url, received }
+        }
+
+        fn received(&self) -> std::sync::MutexGuard<'_, Vec<Received>> {
+            self.received.lock().unwrap()
+        }
+    }
+
+    fn read_request(stream: &TcpStream) -> Received {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("")
+            .to_string();
+
+        let mut length = 0;
+        let mut authorization = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                } else if name.eq_ignore_ascii_case("authorization") {
+                    authorization = Some(value.trim().to_string());
+                }
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        Received {
+            path,
+            authorization,
+            body: serde_json::from_slice(&body).unwrap(),
+        }
+    }
+
+    /// Send `chunks` as a chunked `text/event-stream` response
+    fn write_event_stream<'a>(
+        stream: &mut TcpStream,
+        chunks: impl Iterator<Item = &'a [u8]>,
+    ) -> std::io::Result<()> {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+        )?;
+        for chunk in chunks {
+            write!(stream, "{:x}\r\n", chunk.len())?;
+            stream.write_all(chunk)?;
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+        }
+        stream.write_all(b"0\r\n\r\n")
+    }
+
+    /// Events recorded from each provider and the text they add up to
+    const RECORDED_STREAMS: [(&str, &[u8], &str); 4] = [
+        (
+            "openai",
+            include_bytes!("../fixtures/sse/openai.sse"),
+            "Cześć, co słychać?",
+        ),
+        (
+            "deepseek",
+            include_bytes!("../fixtures/sse/deepseek.sse"),
+            "Spotkanie przenosimy na czwartek, godzinę 10:00.",
+        ),
+        (
+            "ollama",
+            include_bytes!("../fixtures/sse/ollama.sse"),
+            "Dziękuję za wiadomość!\n\nPozdrawiam",
+        ),
+        (
+            "llama-cpp",
+            include_bytes!("../fixtures/sse/llama-cpp.sse"),
+            "Lista zakupów:\n- mleko\n- chleb",
+        ),
+    ];
+
+    fn completion(content: &str) -> String {
+        serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": content } }]
+        })
+        .to_string()
+    }
+
+    fn request(text: &str) -> Request<'_> {
+        Request {
+            text,
+            language: POLISH,
+            translate_to: None,
+            template: &DEFAULT_TEMPLATE,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_request() {
+        let server = MockServer::start("200 OK", completion("  Cześć, co słychać?\n"));
+        let mut client = ChatClient::new("test", &format!("{}/", server.url), "gpt-test");
+        client.set_api_key(Some("sk-test".to_string()));
+
+        let processed = client
+            .process(&Request {
+                translate_to: Some(Language::Known("de")),
+                ..request("eee cześć co słychać")
+            })
+            .await
+            .unwrap();
+        assert_eq!(processed, "Cześć, co słychać?");
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/v1/chat/completions");
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer sk-test"));
+        let body = &received[0].body;
+        assert_eq!(body["model"], "gpt-test");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("ISO 639 code \"de\""));
+        assert_eq!(
+            body["messages"][1]["content"],
+            "Input: eee cześć co słychać\nOutput:"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_server_gets_no_api_key() {
+        let server = MockServer::start("200 OK", completion("Gotowe."));
+        let client = ChatClient::new("local model", &server.url, "llama3.2");
+        assert!(client.is_available());
+
+        assert_eq!(client.process(&request("gotowe")).await.unwrap(), "Gotowe.");
+        assert_eq!(server.received()[0].authorization, None);
+    }
+
+    #[tokio::test]
+    async fn test_api_error_is_reported() {
+        let server = MockServer::start(
+            "503 Service Unavailable",
+            r#"{"error":"overloaded"}"#.to_string(),
+        );
+        let client = ChatClient::new("test", &server.url, "model");
+
+        let error = client.process(&request("tekst")).await.unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("503"), "{}", message);
+        assert!(message.contains("overloaded"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_keeps_raw_text() {
+        let server = MockServer::start("200 OK", r#"{"choices":[]}"#.to_string());
+        let client = ChatClient::new("test", &server.url, "model");
+        assert_eq!(client.process(&request("tekst")).await.unwrap(), "tekst");
+    }
+
+    #[tokio::test]
+    async fn test_recorded_streams() {
+        for (name, events, expected) in RECORDED_STREAMS {
+            // Whole, in TCP-sized pieces and byte by byte
+            for chunk_size in [events.len(), 64, 1] {
+                let server = MockServer::stream(events, chunk_size);
+                let client = ChatClient::new("test", &server.url, "model");
+                let partials = Mutex::new(Vec::new());
+
+                let processed = client
+                    .process_streaming(&request("tekst"), &|text| {
+                        partials.lock().unwrap().push(text.to_string())
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    processed, expected,
+                    "{} in {} byte chunks",
+                    name, chunk_size
+                );
+                assert_eq!(server.received()[0].body["stream"], true);
+
+                // Every partial extends the previous one, the last is the whole text
+                let partials = partials.into_inner().unwrap();
+                assert!(partials.len() > 1, "{}", name);
+                assert!(partials.windows(2).all(|p| p[1].starts_with(&p[0])));
+                assert_eq!(partials.last().unwrap().trim_end(), expected);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_in_stream() {
+        let server = MockServer::stream(include_bytes!("../fixtures/sse/error.sse"), 64);
+        let client = ChatClient::new("test", &server.url, "model");
+
+        let error = client
+            .process_streaming(&request("tekst"), &|_| {})
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("server had an error"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_without_streaming() {
+        // Servers that ignore `stream` answer with a plain completion
+        let server = MockServer::start("200 OK", completion("Gotowe."));
+        let client = ChatClient::new("test", &server.url, "model");
+        let partials = Mutex::new(Vec::new());
+
+        let processed = client
+            .process_streaming(&request("gotowe"), &|text| {
+                partials.lock().unwrap().push(text.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(processed, "Gotowe.");
+        assert!(partials.into_inner().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_closes_the_stream() {
+        // The first events, then keep-alives until the client hangs up
+        let (closed, was_closed) = mpsc::channel();
+        let server = MockServer::serve(move |mut stream| {
+            let (_, events, _) = RECORDED_STREAMS[0];
+            let keep_alive = std::iter::repeat(b": keep-alive\n\n".as_slice())
+                .inspect(|_| std::thread::sleep(Duration::from_millis(20)))
+                .take(500);
+            let result =
+                write_event_stream(&mut stream, events[..600].chunks(64).chain(keep_alive));
+            closed.send(result.is_err()).unwrap();
+        });
+        let client = ChatClient::new("test", &server.url, "model");
+
+        let first_partial = Notify::new();
+        let request = request("tekst");
+        let on_partial = |_: &str| first_partial.notify_one();
+        tokio::select! {
+            _ = client.process_streaming(&request, &on_partial) => {
+                panic!("The stream should still be open");
+            }
+            _ = first_partial.notified() => {}
+        }
+
+        // Off the runtime, which has to keep running to close the connection
+        let closed = tokio::task::spawn_blocking(move || {
+            was_closed.recv_timeout(Duration::from_secs(10)).unwrap()
+        })
+        .await
+        .unwrap();
+        assert!(closed, "The server should notice the closed connection");
+    }
+
+    #[tokio::test]
+    async fn test_missing_api_key() {
+        let client = ChatClient::new("test", "http://127.0.0.1:9", "model").requiring_api_key();
+        assert!(!client.is_available());
+        assert!(client.process(&request("tekst")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_noop_keeps_text() {
+        assert!(!NoOp.is_available());
+        assert_eq!(
+            NoOp.process(&request("eee tekst")).await.unwrap(),
+            "eee tekst"
+        );
+    }
+
+    #[test]
+    fn test_provider_selection() {
+        let secrets = Arc::new(MemoryStore::default());
+        let mut settings = PostProcessorSettings::default();
+        let mut processors = PostProcessors::new(secrets.clone(), &settings);
+
+        // DeepSeek by default, unusable until it has a key
+        assert_eq!(processors.provider(), Provider::DeepSeek);
+        assert!(!processors.current().is_available());
+        processors
+            .set_api_key(Provider::DeepSeek, "sk-deepseek")
+            .unwrap();
+        assert!(processors.current().is_available());
+
+        settings.provider = Provider::Local;
+        processors.configure(&settings);
+        assert_eq!(processors.current().name(), "local model");
+        assert!(processors.current().is_available());
+        assert!(processors.set_api_key(Provider::Local, "key").is_err());
+
+        settings.provider = Provider::None;
+        processors.configure(&settings);
+        assert!(!processors.current().is_available());
+
+        // Without a model the offline cleaner takes over, unless that is off too
+        assert_eq!(processors.fallback().name(), "offline cleaner");
+        settings.offline_fallback = false;
+        processors.configure(&settings);
+        assert_eq!(processors.fallback().name(), "none");
+    }
+
+    #[test]
+    fn test_openai_compatible_key_is_kept() {
+        let secrets = Arc::new(MemoryStore::default());
+        let mut settings = PostProcessorSettings {
+            provider: Provider::OpenAiCompatible,
+            ..Default::default()
+        };
+        let mut processors = PostProcessors::new(secrets.clone(), &settings);
+        processors
+            .set_api_key(Provider::OpenAiCompatible, " sk-openai ")
+            .unwrap();
+        assert_eq!(
+            secrets.get(OPENAI_COMPATIBLE_API_KEY).unwrap().as_deref(),
+            Some("sk-openai")
+        );
+        assert!(!processors.has_api_key(Provider::DeepSeek));
+
+        // Changing the endpoint keeps the key, and so does a restart
+        settings.openai_base_url = "http://localhost:1234/v1".to_string();
+        processors.configure(&settings);
+        assert!(processors.has_api_key(Provider::OpenAiCompatible));
+        let restarted = PostProcessors::new(secrets.clone(), &settings);
+        assert!(restarted.has_api_key(Provider::OpenAiCompatible));
+
+        processors
+            .set_api_key(Provider::OpenAiCompatible, "")
+            .unwrap();
+        assert!(!processors.has_api_key(Provider::OpenAiCompatible));
+        assert_eq!(secrets.get(OPENAI_COMPATIBLE_API_KEY).unwrap(), None);
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        assert!(PostProcessorSettings::default().validate().is_ok());
+
+        let invalid = [
+            PostProcessorSettings {
+                openai_base_url: "api.openai.com".to_string(),
+                ..Default::default()
+            },
+            PostProcessorSettings {
+                local_base_url: "ftp://localhost/v1".to_string(),
+                ..Default::default()
+            },
+            PostProcessorSettings {
+                local_model: " ".to_string(),
+                ..Default::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+
+        let settings: PostProcessorSettings =
+            serde_json::from_value(serde_json::json!({ "provider": "openAiCompatible" })).unwrap();
+        assert_eq!(settings.provider, Provider::OpenAiCompatible);
+        assert_eq!(settings.local_model, DEFAULT_LOCAL_MODEL);
+    }
+}
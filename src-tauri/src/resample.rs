@@ -0,0 +1,368 @@
+//! Band-limited sample rate conversion.
+//!
+//! Windowed-sinc resampler (Kaiser window) that works on arbitrary source and
+//! target rates. The filter kernel is precomputed into a finely sampled table
+//! and linearly interpolated between table points, so conversion cost is a
+//! fixed number of multiply-adds per output sample. Input can be fed in chunks
+//! of any size; output is identical to converting the whole signal at once.
+
+/// Zero crossings of the sinc on each side of the kernel centre
+const ZERO_CROSSINGS: usize = 16;
+
+/// Table resolution: kernel points per input sample
+const TABLE_RESOLUTION: usize = 512;
+
+/// Passband edge as a fraction of the lower Nyquist frequency
+const ROLLOFF: f64 = 0.88;
+
+/// Kaiser window shape parameter (~90 dB stopband attenuation)
+const KAISER_BETA: f64 = 9.0;
+
+/// Streaming mono resampler
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    /// Kernel half width in input samples
+    half_width: f64,
+    /// Kernel values for |x| in `[0, half_width]`, `TABLE_RESOLUTION` points per input sample
+    table: Vec<f32>,
+    /// Buffered input; `history[0]` is absolute input sample `history_start`
+    history: Vec<f32>,
+    history_start: u64,
+    /// Total input samples received so far
+    input_len: u64,
+    /// Index of the next output sample to produce
+    output_index: u64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        assert!(
+            input_rate > 0 && output_rate > 0,
+            "sample rates must be non-zero"
+        );
+
+        // Cutoff in cycles per input sample, below the lower of the two Nyquist rates
+        let ratio = output_rate as f64 / input_rate as f64;
+        let cutoff = 0.5 * ratio.min(1.0) * ROLLOFF;
+        let half_width = ZERO_CROSSINGS as f64 / (2.0 * cutoff);
+
+        let table_len = (half_width * TABLE_RESOLUTION as f64).ceil() as usize + 2;
+        let table = (0..table_len)
+            .map(|i| {
+                let x = i as f64 / TABLE_RESOLUTION as f64;
+                kernel(x, cutoff, half_width) as f32
+            })
+            .collect();
+
+        Self {
+            input_rate,
+            output_rate,
+            half_width,
+            table,
+            history: Vec::new(),
+            history_start: 0,
+            input_len: 0,
+            output_index: 0,
+        }
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Feed a chunk of input, returning every output sample that can be computed so far
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.history.extend_from_slice(input);
+        self.input_len += input.len() as u64;
+
+        let mut output = Vec::new();
+        if self.input_rate == self.output_rate {
+            output.extend_from_slice(&self.history);
+            self.history_start += self.history.len() as u64;
+            self.output_index += self.history.len() as u64;
+            self.history.clear();
+            return output;
+        }
+
+        // An output sample needs input up to `t + half_width`
+        let available = self.input_len as f64;
+        while self.output_time() + self.half_width < available {
+            output.push(self.compute_next());
+        }
+
+        self.discard_consumed_history();
+        output
+    }
+
+    /// Flush the filter tail, treating the input as zero past its end
+    pub fn flush(&mut self) -> Vec<f32> {
+        let expected = (self.input_len * self.output_rate as u64).div_ceil(self.input_rate as u64);
+
+        let mut output = Vec::new();
+        while self.output_index < expected {
+            output.push(self.compute_next());
+        }
+
+        self.discard_consumed_history();
+        output
+    }
+
+    /// Position of the next output sample, in input samples
+    fn output_time(&self) -> f64 {
+        let numerator = self.output_index * self.input_rate as u64;
+        let whole = numerator / self.output_rate as u64;
+        let frac = (numerator % self.output_rate as u64) as f64 / self.output_rate as f64;
+        whole as f64 + frac
+    }
+
+    fn compute_next(&mut self) -> f32 {
+        let t = self.output_time();
+        let first = (t - self.half_width).ceil().max(0.0) as u64;
+        let last = ((t + self.half_width).floor() as u64).min(self.input_len.saturating_sub(1));
+
+        let mut acc = 0.0f32;
+        if self.input_len > 0 {
+            for k in first.max(self.history_start)..=last {
+                let sample = self.history[(k - self.history_start) as usize];
+                acc += sample * self.lookup((t - k as f64).abs());
+            }
+        }
+
+        self.output_index += 1;
+        acc
+    }
+
+    /// Linearly interpolated kernel value at distance `x` (input samples) from the centre
+    fn lookup(&self, x: f64) -> f32 {
+        let pos = x * TABLE_RESOLUTION as f64;
+        let index = pos as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let frac = (pos - index as f64) as f32;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * frac
+    }
+
+    /// Drop input that no future output sample can reach
+    fn discard_consumed_history(&mut self) {
+        let keep_from = (self.output_time() - self.half_width).floor().max(0.0) as u64;
+        if keep_from > self.history_start {
+            let drop = ((keep_from - self.history_start) as usize).min(self.history.len());
+            self.history.drain(..drop);
+            self.history_start += drop as u64;
+        }
+    }
+}
+
+/// Resample a complete mono signal
+pub fn resample(samples: &[f32], input_rate: u32, output_rate: u32) -> Vec<f32> {
+    if input_rate == output_rate {
+        return samples.to_vec();
+    }
+
+    let mut resampler = Resampler::new(input_rate, output_rate);
+    let mut output = resampler.process(samples);
+    output.extend(resampler.flush());
+    output
+}
+
+/// Average interleaved channels into a single mono channel
+pub fn downmix_to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks(channels as usize)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+/// Windowed-sinc low-pass kernel at distance `x` input samples from the centre
+fn kernel(x: f64, cutoff: f64, half_width: f64) -> f64 {
+    if x >= half_width {
+        return 0.0;
+    }
+
+    let sinc = if x == 0.0 {
+        1.0
+    } else {
+        let arg = std::f64::consts::PI * 2.0 * cutoff * x;
+        arg.sin() / arg
+    };
+
+    let ratio = x / half_width;
+    let window = bessel_i0(KAISER_BETA * (1.0 - ratio * ratio).sqrt()) / bessel_i0(KAISER_BETA);
+
+    2.0 * cutoff * sinc * window
+}
+
+/// Zeroth-order modified Bessel function of the first kind
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..64 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: [u32; 5] = [8000, 22050, 44100, 48000, 96000];
+
+    fn sine(freq: f64, rate: u32, seconds: f64) -> Vec<f32> {
+        let len = (rate as f64 * seconds) as usize;
+        (0..len)
+            .map(|i| {
+                (2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64).sin() as f32 * 0.5
+            })
+            .collect()
+    }
+
+    /// Power of a single frequency (Hann-windowed DFT bin)
+    fn tone_power(samples: &[f32], rate: u32, freq: f64) -> f64 {
+        let n = samples.len();
+        let (mut re, mut im) = (0.0f64, 0.0f64);
+        for (i, &s) in samples.iter().enumerate() {
+            let w = 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / n as f64).cos();
+            let phase = 2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64;
+            re += s as f64 * w * phase.cos();
+            im -= s as f64 * w * phase.sin();
+        }
+        (re * re + im * im) / (n as f64 * n as f64)
+    }
+
+    fn db(ratio: f64) -> f64 {
+        10.0 * ratio.log10()
+    }
+
+    /// Skip the filter's settling region at both ends
+    fn trim(samples: &[f32], rate: u32) -> &[f32] {
+        let edge = (rate / 10) as usize;
+        &samples[edge..samples.len() - edge]
+    }
+
+    #[test]
+    fn test_output_length() {
+        for &rate in &RATES {
+            let input = vec![0.0; rate as usize];
+            let output = resample(&input, rate, 16000);
+            assert_eq!(output.len(), 16000, "rate {}", rate);
+        }
+    }
+
+    #[test]
+    fn test_passband_tone_preserved() {
+        for &rate in &RATES {
+            let input = sine(1000.0, rate, 1.0);
+            let output = resample(&input, rate, 16000);
+            let expected = sine(1000.0, 16000, 1.0);
+
+            let output = trim(&output, 16000);
+            let expected = trim(&expected, 16000);
+            let max_error = output
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0f32, f32::max);
+            assert!(max_error < 5e-3, "rate {}: max error {}", rate, max_error);
+        }
+    }
+
+    #[test]
+    fn test_aliasing_suppressed() {
+        // Tones above the 8 kHz target Nyquist must not fold back into the passband
+        for &rate in &[22050, 44100, 48000, 96000] {
+            let reference = resample(&sine(1000.0, rate, 1.0), rate, 16000);
+            let reference_power = tone_power(trim(&reference, 16000), 16000, 1000.0);
+
+            for &freq in &[9000.0, 10000.0, 12000.0] {
+                if freq >= rate as f64 / 2.0 {
+                    continue;
+                }
+                let output = resample(&sine(freq, rate, 1.0), rate, 16000);
+                let alias = 16000.0 - freq;
+                let alias_power = tone_power(trim(&output, 16000), 16000, alias);
+                let attenuation = db(alias_power / reference_power);
+                assert!(
+                    attenuation < -70.0,
+                    "rate {} tone {}: alias at {} Hz only {:.1} dB down",
+                    rate,
+                    freq,
+                    alias,
+                    attenuation
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_error() {
+        for &rate in &[22050, 44100, 48000] {
+            let input: Vec<f32> = sine(440.0, rate, 1.0)
+                .iter()
+                .zip(sine(2500.0, rate, 1.0))
+                .map(|(a, b)| a * 0.6 + b * 0.4)
+                .collect();
+
+            let down = resample(&input, rate, 16000);
+            let up = resample(&down, 16000, rate);
+            assert_eq!(up.len(), input.len());
+
+            let input = trim(&input, rate);
+            let up = trim(&up, rate);
+            let signal: f64 = input.iter().map(|&s| (s as f64).powi(2)).sum();
+            let noise: f64 = input
+                .iter()
+                .zip(up)
+                .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+                .sum();
+            let snr = db(signal / noise);
+            assert!(snr > 60.0, "rate {}: round-trip SNR {:.1} dB", rate, snr);
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let input = sine(700.0, 44100, 0.5);
+        let expected = resample(&input, 44100, 16000);
+
+        let mut resampler = Resampler::new(44100, 16000);
+        let mut output = Vec::new();
+        for chunk in input.chunks(441) {
+            output.extend(resampler.process(chunk));
+        }
+        output.extend(resampler.flush());
+
+        assert_eq!(output.len(), expected.len());
+        for (a, b) in output.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_same_rate_is_identity() {
+        let input = sine(440.0, 16000, 0.1);
+        assert_eq!(resample(&input, 16000, 16000), input);
+    }
+
+    #[test]
+    fn test_downmix_to_mono() {
+        let stereo = vec![1.0, 0.0, 0.5, 0.5, -1.0, 1.0];
+        assert_eq!(downmix_to_mono(&stereo, 2), vec![0.5, 0.5, 0.0]);
+        assert_eq!(downmix_to_mono(&stereo, 1), stereo);
+    }
+}
@@ -0,0 +1,383 @@
+//! Credential storage.
+//!
+//! Secrets live in the platform keyring (Secret Service on Linux, Credential
+//! Manager on Windows, Keychain on macOS). When no keyring is reachable they
+//! go to a ChaCha20-Poly1305 encrypted file in the app data dir instead; its
+//! key sits next to it, readable only by the current user, so the file keeps
+//! the secret out of plain sight but is not a substitute for a real keyring.
+
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Keyring service name
+const SERVICE: &str = "ValleyFlow";
+
+/// Name under which the DeepSeek API key is stored
+pub const DEEPSEEK_API_KEY: &str = "deepseek-api-key";
+
+/// Name under which the key for the OpenAI-compatible provider is stored
+pub const OPENAI_COMPATIBLE_API_KEY: &str = "openai-compatible-api-key";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+pub trait SecretStore: Send + Sync {
+    /// Human-readable backend name for logs
+    fn name(&self) -> &'static str;
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    /// Remove `key`; removing a missing key is not an error
+    fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Platform keyring
+pub struct KeyringStore {
+    service: String,
+}
+
+impl KeyringStore {
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+        }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, key).context("Failed to open keyring entry")
+    }
+
+    /// Whether the keyring can actually be reached (e.g. a Secret Service daemon is running)
+    pub fn is_available(&self) -> bool {
+        match self.entry("availability-probe").map(|e| e.get_password()) {
+            Ok(Ok(_)) | Ok(Err(keyring::Error::NoEntry)) => true,
+            Ok(Err(e)) => {
+                log::info!("Keyring unavailable: {}", e);
+                false
+            }
+            Err(e) => {
+                log::info!("Keyring unavailable: {:#}", e);
+                false
+            }
+        }
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "system keyring"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        match self.entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context("Failed to read from keyring"),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.entry(key)?
+            .set_password(value)
+            .context("Failed to write to keyring")
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        match self.entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e).context("Failed to delete from keyring"),
+        }
+    }
+}
+
+/// Encrypted JSON map on disk, used when no keyring is available
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    /// Serializes read-modify-write cycles
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join("secrets.enc"),
+            key_path: dir.join("secrets.key"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn cipher(&self, create: bool) -> Result<Option<ChaCha20Poly1305>> {
+        let key = match fs::read(&self.key_path) {
+            Ok(key) => key,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+                let mut key = vec![0u8; KEY_LEN];
+                getrandom::getrandom(&mut key).context("Failed to generate encryption key")?;
+                write_private(&self.key_path, &key)?;
+                key
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read encryption key"),
+        };
+        let key: [u8; KEY_LEN] = key
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Encryption key file is corrupt"))?;
+        Ok(Some(ChaCha20Poly1305::new(&Key::from(key))))
+    }
+
+    fn read(&self) -> Result<HashMap<String, String>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e).context("Failed to read secrets file"),
+        };
+        let cipher = self
+            .cipher(false)?
+            .context("Secrets file exists but its key is missing")?;
+
+        if data.len() < NONCE_LEN {
+            anyhow::bail!("Secrets file is corrupt");
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().expect("split at nonce length");
+        let plaintext = cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt secrets file"))?;
+        serde_json::from_slice(&plaintext).context("Secrets file is corrupt")
+    }
+
+    fn write(&self, secrets: &HashMap<String, String>) -> Result<()> {
+        let cipher = self.cipher(true)?.expect("key is created on demand");
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).context("Failed to generate nonce")?;
+
+        let plaintext = serde_json::to_vec(secrets)?;
+        let ciphertext = cipher
+            .encrypt(&Nonce::from(nonce), plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secrets"))?;
+
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        write_private(&self.path, &data)
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.read()?.remove(key))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.read()?;
+        secrets.insert(key.to_string(), value.to_string());
+        self.write(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.read()?;
+        if secrets.remove(key).is_some() {
+            self.write(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+/// Atomically write a file only the current user can read
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create secrets directory")?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&tmp_path)
+            .context("Failed to create secrets file")?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path).context("Failed to replace secrets file")
+}
+
+/// Keeps secrets in memory only
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    values: Mutex<HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl SecretStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// The keyring if it is reachable, otherwise the encrypted file
+pub fn open_default() -> Arc<dyn SecretStore> {
+    let keyring = KeyringStore::new(SERVICE);
+    if keyring.is_available() {
+        return Arc::new(keyring);
+    }
+
+    let dir = dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ValleyFlow");
+    log::warn!("No system keyring, storing secrets encrypted in {:?}", dir);
+    Arc::new(EncryptedFileStore::new(&dir))
+}
+
+/// Move a plaintext API key from an older version into `store`.
+///
+/// A key already in the store wins. Returns whether `legacy` was stored.
+pub fn migrate_api_key(store: &dyn SecretStore, legacy: Option<&str>) -> Result<bool> {
+    let legacy = match legacy.map(str::trim) {
+        Some(key) if !key.is_empty() => key,
+        _ => return Ok(false),
+    };
+    if store.get(DEEPSEEK_API_KEY)?.is_some() {
+        return Ok(false);
+    }
+
+    store.set(DEEPSEEK_API_KEY, legacy)?;
+    log::info!("Moved API key into the {}", store.name());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::temp_dir;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::default();
+        assert_eq!(store.get("a").unwrap(), None);
+        store.set("a", "1").unwrap();
+        assert_eq!(store.get("a").unwrap(), Some("1".to_string()));
+        store.delete("a").unwrap();
+        store.delete("a").unwrap();
+        assert_eq!(store.get("a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let dir = temp_dir("secrets-round-trip");
+        let store = EncryptedFileStore::new(&dir);
+        assert_eq!(store.get(DEEPSEEK_API_KEY).unwrap(), None);
+
+        store.set(DEEPSEEK_API_KEY, "sk-secret-value").unwrap();
+        store.set("other", "x").unwrap();
+
+        // A fresh instance reads the same file back
+        let reopened = EncryptedFileStore::new(&dir);
+        assert_eq!(
+            reopened.get(DEEPSEEK_API_KEY).unwrap(),
+            Some("sk-secret-value".to_string())
+        );
+
+        // The secret never hits the disk in plaintext
+        let raw = fs::read(dir.join("secrets.enc")).unwrap();
+        assert!(!raw.windows(15).any(|w| w == b"sk-secret-value"));
+
+        reopened.delete(DEEPSEEK_API_KEY).unwrap();
+        assert_eq!(store.get(DEEPSEEK_API_KEY).unwrap(), None);
+        assert_eq!(store.get("other").unwrap(), Some("x".to_string()));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_encrypted_file_detects_tampering() {
+        let dir = temp_dir("secrets-tamper");
+        let store = EncryptedFileStore::new(&dir);
+        store.set(DEEPSEEK_API_KEY, "sk-secret").unwrap();
+
+        let path = dir.join("secrets.enc");
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&path, data).unwrap();
+        assert!(store.get(DEEPSEEK_API_KEY).is_err());
+
+        // Without its key the file cannot be read at all
+        fs::remove_file(dir.join("secrets.key")).unwrap();
+        assert!(store.get(DEEPSEEK_API_KEY).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_encrypted_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("secrets-permissions");
+        EncryptedFileStore::new(&dir).set("a", "1").unwrap();
+        for file in ["secrets.enc", "secrets.key"] {
+            let mode = fs::metadata(dir.join(file)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate_api_key() {
+        let store = MemoryStore::default();
+        assert!(!migrate_api_key(&store, None).unwrap());
+        assert!(!migrate_api_key(&store, Some("  ")).unwrap());
+        assert_eq!(store.get(DEEPSEEK_API_KEY).unwrap(), None);
+
+        assert!(migrate_api_key(&store, Some(" sk-old ")).unwrap());
+        assert_eq!(
+            store.get(DEEPSEEK_API_KEY).unwrap(),
+            Some("sk-old".to_string())
+        );
+
+        // Later migrations never overwrite a stored key
+        assert!(!migrate_api_key(&store, Some("sk-other")).unwrap());
+        assert_eq!(
+            store.get(DEEPSEEK_API_KEY).unwrap(),
+            Some("sk-old".to_string())
+        );
+    }
+}
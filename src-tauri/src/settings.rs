@@ -0,0 +1,589 @@
+//! Persistent user settings.
+//!
+//! Stored as versioned JSON in the app config dir. Older files are upgraded by
+//! running the migrations in order; every write goes through a temp file and a
+//! rename so a crash never leaves a half-written settings file behind.
+
+use crate::foreground::AppRule;
+use crate::fs_util;
+use crate::models;
+use crate::postprocess::PostProcessorSettings;
+use crate::shortcut::Hotkey;
+use crate::templates::{self, DEFAULT_TEMPLATE};
+use crate::transcription::{Language, TranscriptionOptions};
+use crate::trigger::RecordingMode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Current schema version
+pub const SETTINGS_VERSION: u32 = 2;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub version: u32,
+    /// Interface language ("pl" or "en")
+    pub language: String,
+    /// Input device name, or "default"
+    pub microphone: String,
+    pub hotkey: String,
+    /// Toggle or push-to-talk
+    pub recording_mode: RecordingMode,
+    pub has_completed_wizard: bool,
+    /// Start with the system; `None` until the first launch decides
+    pub autostart: Option<bool>,
+    /// A plaintext API key from an older version (settings file or `.env`) has been
+    /// moved into the secret store; it is not imported again
+    pub api_key_migrated: bool,
+    /// Transcribe while recording
+    pub streaming: bool,
+    /// Stop after this many seconds of silence; `None` disables auto-stop
+    pub auto_stop_silence_secs: Option<f32>,
+    pub max_recording_secs: u32,
+    pub cancel_on_escape: bool,
+    /// Whisper model id from the catalog
+    pub model: String,
+    /// Model file chosen by the user; takes precedence over `model`
+    pub model_path: Option<String>,
+    /// How Whisper decodes recordings
+    pub transcription: TranscriptionOptions,
+    /// Language code to translate recordings into; `None` keeps the spoken language
+    pub translate_to: Option<String>,
+    /// Which language model cleans up transcripts
+    pub post_processor: PostProcessorSettings,
+    /// Id of the output template used for post-processing
+    pub template: String,
+    /// Shortcuts that switch to a template, by template id
+    pub template_hotkeys: BTreeMap<String, String>,
+    /// Templates picked by the app in the foreground, first match wins
+    pub app_rules: Vec<AppRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            language: "pl".to_string(),
+            microphone: "default".to_string(),
+            hotkey: "Ctrl+Shift+Space".to_string(),
+            recording_mode: RecordingMode::Toggle,
+            has_completed_wizard: false,
+            autostart: None,
+            api_key_migrated: false,
+            streaming: true,
+            auto_stop_silence_secs: None,
+            max_recording_secs: crate::audio::DEFAULT_MAX_DURATION_SECS,
+            cancel_on_escape: true,
+            model: models::DEFAULT_MODEL.to_string(),
+            model_path: None,
+            transcription: TranscriptionOptions::default(),
+            translate_to: None,
+            post_processor: PostProcessorSettings::default(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            template_hotkeys: BTreeMap::new(),
+            app_rules: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Check that every field holds a usable value
+    pub fn validate(&self) -> Result<()> {
+        if !matches!(self.language.as_str(), "pl" | "en") {
+            anyhow::bail!("Unsupported interface language: {}", self.language);
+        }
+        if self.microphone.trim().is_empty() {
+            anyhow::bail!("Microphone must not be empty");
+        }
+        let hotkey = self.hotkey.parse::<Hotkey>()?;
+        if let Some(secs) = self.auto_stop_silence_secs {
+            if !(0.5..=60.0).contains(&secs) {
+                anyhow::bail!("Auto-stop silence must be between 0.5 and 60 seconds");
+            }
+        }
+        if !(10..=crate::audio::DEFAULT_MAX_DURATION_SECS).contains(&self.max_recording_secs) {
+            anyhow::bail!(
+                "Maximum recording length must be between 10 and {} seconds",
+                crate::audio::DEFAULT_MAX_DURATION_SECS
+            );
+        }
+        if models::find(&self.model).is_none() {
+            anyhow::bail!("Unknown Whisper model: {}", self.model);
+        }
+        if self
+            .model_path
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            anyhow::bail!("Model path must not be empty");
+        }
+        self.transcription.validate()?;
+        if let Some(target) = &self.translate_to {
+            if Language::from_code(target) == Language::Unknown {
+                anyhow::bail!("Cannot translate into unknown language: {}", target);
+            }
+        }
+        self.post_processor.validate()?;
+        if !templates::is_valid_id(&self.template) {
+            anyhow::bail!("Invalid template id: {}", self.template);
+        }
+        let mut hotkeys = vec![hotkey];
+        for (id, hotkey) in &self.template_hotkeys {
+            if !templates::is_valid_id(id) {
+                anyhow::bail!("Invalid template id: {}", id);
+            }
+            let hotkey = hotkey.parse::<Hotkey>()?;
+            if hotkeys.contains(&hotkey) {
+                anyhow::bail!("Shortcut {} is used twice", hotkey);
+            }
+            hotkeys.push(hotkey);
+        }
+        for rule in &self.app_rules {
+            rule.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Apply a partial update (a JSON object with camelCase keys)
+    pub fn merged(&self, patch: &Value) -> Result<Self> {
+        let patch = patch
+            .as_object()
+            .context("Settings update must be a JSON object")?;
+
+        let mut value = serde_json::to_value(self)?;
+        let object = value
+            .as_object_mut()
+            .expect("settings serialize to an object");
+        for (key, field) in patch {
+            if key == "version" {
+                continue;
+            }
+            if !object.contains_key(key) {
+                anyhow::bail!("Unknown setting: {}", key);
+            }
+            object.insert(key.clone(), field.clone());
+        }
+
+        let settings: Settings = serde_json::from_value(value).context("Invalid settings value")?;
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+/// Upgrade a settings document one version at a time.
+///
+/// `MIGRATIONS[n]` turns a version `n` document into version `n + 1`.
+const MIGRATIONS: &[fn(&mut Value)] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Version 0 is the unversioned shape the frontend kept in localStorage
+fn migrate_v0_to_v1(value: &mut Value) {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), Value::from(1));
+    }
+}
+
+/// Version 2 keeps the API key in the secret store instead of the settings file
+fn migrate_v1_to_v2(value: &mut Value) {
+    if let Some(object) = value.as_object_mut() {
+        object.remove("apiKey");
+        object.insert("version".to_string(), Value::from(2));
+    }
+}
+
+/// Plaintext API key left in a settings document by version 1 or earlier
+fn legacy_api_key(json: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    let key = value.get("apiKey")?.as_str()?.trim();
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+fn migrate(mut value: Value) -> Result<Value> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > SETTINGS_VERSION {
+        anyhow::bail!(
+            "Settings were written by a newer version (schema {}, supported {})",
+            version,
+            SETTINGS_VERSION
+        );
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        log::info!("Migrating settings from version {} to {}", from, from + 1);
+        migration(&mut value);
+    }
+    Ok(value)
+}
+
+/// Parse, migrate and validate a settings document
+pub fn parse(json: &str) -> Result<Settings> {
+    let value: Value = serde_json::from_str(json).context("Settings file is not valid JSON")?;
+    let settings: Settings =
+        serde_json::from_value(migrate(value)?).context("Settings file has invalid values")?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// Default location of the settings file
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ValleyFlow")
+        .join(SETTINGS_FILE)
+}
+
+/// Load settings from `path`, falling back to defaults if it is missing or unusable
+pub fn load(path: &Path) -> Settings {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::info!("No settings file at {:?}, using defaults", path);
+            return Settings::default();
+        }
+        Err(e) => {
+            log::warn!("Failed to read settings from {:?}: {}", path, e);
+            return Settings::default();
+        }
+    };
+
+    match parse(&json) {
+        Ok(settings) => {
+            log::info!("Settings loaded from {:?}", path);
+            settings
+        }
+        Err(e) => {
+            log::warn!("Ignoring settings file {:?}: {:#}", path, e);
+            // Keep the unreadable file around instead of overwriting it on the next save
+            let _ = fs::rename(path, path.with_extension("json.bak"));
+            Settings::default()
+        }
+    }
+}
+
+/// Write settings atomically (temp file + rename)
+pub fn save(path: &Path, settings: &Settings) -> Result<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs_util::write_atomic(path, json.as_bytes()).context("Failed to save settings")?;
+
+    log::info!("Settings saved to {:?}", path);
+    Ok(())
+}
+
+/// Settings file plus its current contents
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Settings,
+    legacy_api_key: Option<String>,
+    is_new: bool,
+}
+
+impl SettingsStore {
+    pub fn load(path: PathBuf) -> Self {
+        let legacy_api_key = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| legacy_api_key(&json));
+        let is_new = !path.exists();
+        let current = load(&path);
+        Self {
+            path,
+            current,
+            legacy_api_key,
+            is_new,
+        }
+    }
+
+    pub fn get(&self) -> &Settings {
+        &self.current
+    }
+
+    /// Whether there was no settings file yet, i.e. this is the first run
+    pub fn is_new(&self) -> bool {
+        self.is_new
+    }
+
+    /// API key found in an older settings file; it stays on disk until the next save
+    pub fn take_legacy_api_key(&mut self) -> Option<String> {
+        self.legacy_api_key.take()
+    }
+
+    /// Merge `patch` into the current settings, persist and return the result
+    pub fn update(&mut self, patch: &Value) -> Result<Settings> {
+        let updated = self.current.merged(patch)?;
+        save(&self.path, &updated)?;
+        self.current = updated.clone();
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::temp_dir;
+    use crate::postprocess::Provider;
+
+    fn temp_path(name: &str) -> PathBuf {
+        temp_dir(&format!("settings-{}", name)).join(SETTINGS_FILE)
+    }
+
+    #[test]
+    fn test_defaults_are_valid() {
+        Settings::default().validate().unwrap();
+    }
+
+    #[test]
+    fn test_migrates_unversioned_settings() {
+        let legacy = r#"{
+            "language": "en",
+            "microphone": "USB Mic",
+            "apiKey": "sk-legacy",
+            "hotkey": "Ctrl+Alt+D",
+            "hasCompletedWizard": true
+        }"#;
+
+        let settings = parse(legacy).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.language, "en");
+        assert_eq!(settings.microphone, "USB Mic");
+        assert_eq!(settings.hotkey, "Ctrl+Alt+D");
+        assert!(settings.has_completed_wizard);
+        assert!(settings.streaming);
+        assert_eq!(legacy_api_key(legacy), Some("sk-legacy".to_string()));
+    }
+
+    #[test]
+    fn test_v1_api_key_is_moved_out() {
+        let path = temp_path("legacy-key");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"{ "version": 1, "apiKey": "sk-v1", "language": "en" }"#,
+        )
+        .unwrap();
+
+        let mut store = SettingsStore::load(path.clone());
+        assert_eq!(store.get().language, "en");
+        assert_eq!(store.take_legacy_api_key(), Some("sk-v1".to_string()));
+        assert_eq!(store.take_legacy_api_key(), None);
+
+        store
+            .update(&serde_json::json!({ "apiKeyMigrated": true }))
+            .unwrap();
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("sk-v1"));
+        assert!(SettingsStore::load(path.clone())
+            .take_legacy_api_key()
+            .is_none());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let json = format!(r#"{{ "version": {} }}"#, SETTINGS_VERSION + 1);
+        assert!(parse(&json).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = [
+            r#"{ "version": 1, "language": "de" }"#,
+            r#"{ "version": 1, "hotkey": " " }"#,
+            r#"{ "version": 1, "hotkey": "Ctrl+Banana" }"#,
+            r#"{ "version": 1, "autoStopSilenceSecs": 0.1 }"#,
+            r#"{ "version": 1, "maxRecordingSecs": 3600 }"#,
+            r#"{ "version": 1, "model": "huge" }"#,
+            r#"{ "version": 1, "modelPath": "" }"#,
+            r#"{ "version": 1, "transcription": { "beamSize": 0 } }"#,
+            r#"{ "version": 1, "transcription": { "language": "xx" } }"#,
+            r#"{ "version": 1, "translateTo": "auto" }"#,
+            r#"{ "version": 1, "postProcessor": { "provider": "chatGpt" } }"#,
+            r#"{ "version": 1, "postProcessor": { "localBaseUrl": "localhost:11434" } }"#,
+            r#"{ "version": 1, "template": "" }"#,
+            r#"{ "version": 1, "template": "../email" }"#,
+            r#"{ "version": 1, "templateHotkeys": { "email": "Ctrl+Banana" } }"#,
+            r#"{ "version": 1, "templateHotkeys": { "Email": "Ctrl+Alt+E" } }"#,
+            r#"{ "version": 1, "appRules": [{ "apps": [], "template": "email" }] }"#,
+            r#"{ "version": 1, "appRules": [{ "apps": ["code"], "template": "Code" }] }"#,
+        ];
+        for json in invalid {
+            assert!(parse(json).is_err(), "{} should be rejected", json);
+        }
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let settings = Settings::default();
+        let updated = settings
+            .merged(&serde_json::json!({
+                "language": "en",
+                "autoStopSilenceSecs": 2.5,
+                "recordingMode": "pushToTalk"
+            }))
+            .unwrap();
+        assert_eq!(updated.language, "en");
+        assert_eq!(updated.recording_mode, RecordingMode::PushToTalk);
+        assert_eq!(updated.auto_stop_silence_secs, Some(2.5));
+        assert_eq!(updated.hotkey, settings.hotkey);
+
+        assert!(settings
+            .merged(&serde_json::json!({ "unknown": 1 }))
+            .is_err());
+        assert!(settings
+            .merged(&serde_json::json!({ "language": 5 }))
+            .is_err());
+        assert!(settings
+            .merged(&serde_json::json!({ "recordingMode": "hold" }))
+            .is_err());
+        assert!(settings.merged(&serde_json::json!(["language"])).is_err());
+    }
+
+    #[test]
+    fn test_post_processor_selection() {
+        let updated = Settings::default()
+            .merged(&serde_json::json!({
+                "postProcessor": { "provider": "local", "localModel": "qwen2.5:7b" }
+            }))
+            .unwrap();
+        assert_eq!(updated.post_processor.provider, Provider::Local);
+        assert_eq!(updated.post_processor.local_model, "qwen2.5:7b");
+        assert_eq!(
+            updated.post_processor.local_base_url,
+            PostProcessorSettings::default().local_base_url
+        );
+    }
+
+    #[test]
+    fn test_template_hotkeys() {
+        let updated = Settings::default()
+            .merged(&serde_json::json!({
+                "template": "email",
+                "templateHotkeys": { "email": "alt+ctrl+e", "bullet-notes": "Ctrl+Alt+B" }
+            }))
+            .unwrap();
+        assert_eq!(updated.template, "email");
+        assert_eq!(updated.template_hotkeys["email"], "alt+ctrl+e");
+
+        // A shortcut can only do one thing
+        for hotkeys in [
+            serde_json::json!({ "email": "Ctrl+Shift+Space" }),
+            serde_json::json!({ "email": "Ctrl+Alt+E", "slack": "Alt+Ctrl+E" }),
+        ] {
+            assert!(
+                Settings::default()
+                    .merged(&serde_json::json!({ "templateHotkeys": hotkeys }))
+                    .is_err(),
+                "{}",
+                hotkeys
+            );
+        }
+    }
+
+    #[test]
+    fn test_app_rules() {
+        let updated = Settings::default()
+            .merged(&serde_json::json!({
+                "appRules": [
+                    { "apps": ["WindowsTerminal", "kitty"], "template": null },
+                    { "apps": ["thunderbird"], "template": "email" }
+                ]
+            }))
+            .unwrap();
+        assert_eq!(updated.app_rules.len(), 2);
+        assert_eq!(updated.app_rules[0].template, None);
+        assert_eq!(updated.app_rules[1].template.as_deref(), Some("email"));
+        assert!(Settings::default().app_rules.is_empty());
+    }
+
+    #[test]
+    fn test_transcription_options_round_trip() {
+        let updated = Settings::default()
+            .merged(&serde_json::json!({
+                "transcription": { "language": "pl", "beamSize": 5, "initialPrompt": "ValleyFlow" }
+            }))
+            .unwrap();
+        assert_eq!(updated.transcription.language.as_deref(), Some("pl"));
+        assert_eq!(updated.transcription.beam_size, 5);
+        // Fields left out of the object keep their defaults
+        assert!(updated.transcription.suppress_non_speech);
+
+        let json = serde_json::to_string(&updated).unwrap();
+        assert_eq!(parse(&json).unwrap(), updated);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let settings = Settings {
+            microphone: "Headset".to_string(),
+            auto_stop_silence_secs: Some(3.0),
+            ..Settings::default()
+        };
+
+        save(&path, &settings).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        assert_eq!(load(&path), settings);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_corrupt_file_falls_back_to_defaults() {
+        let path = temp_path("corrupt");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        assert_eq!(load(&path), Settings::default());
+        assert!(path.with_extension("json.bak").exists());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_store_update_persists() {
+        let path = temp_path("store");
+        let mut store = SettingsStore::load(path.clone());
+        let updated = store
+            .update(&serde_json::json!({ "streaming": false }))
+            .unwrap();
+
+        assert!(!updated.streaming);
+        assert_eq!(store.get(), &updated);
+        assert_eq!(load(&path), updated);
+
+        // A rejected update leaves both memory and disk untouched
+        assert!(store
+            .update(&serde_json::json!({ "language": "xx" }))
+            .is_err());
+        assert_eq!(load(&path), updated);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_first_run_is_remembered() {
+        let path = temp_path("first-run");
+        let mut store = SettingsStore::load(path.clone());
+        assert!(store.is_new());
+        assert_eq!(store.get().autostart, None);
+        store
+            .update(&serde_json::json!({ "autostart": false }))
+            .unwrap();
+
+        let store = SettingsStore::load(path.clone());
+        assert!(!store.is_new());
+        assert_eq!(store.get().autostart, Some(false));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let path = temp_path("missing");
+        assert_eq!(load(&path), Settings::default());
+    }
+}
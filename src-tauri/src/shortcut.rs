@@ -0,0 +1,470 @@
+//! Global shortcuts: the recording shortcut and the template shortcuts.
+//!
+//! Parses user-facing shortcut strings such as "Ctrl+Alt+D" and swaps the
+//! registered shortcuts at runtime. Failures come back as [`ShortcutError`] so
+//! the settings UI can tell a typo from a shortcut another app already owns.
+
+use crate::commands;
+use crate::hotkey::{self, RecordingState};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum ShortcutError {
+    /// The string is not a valid shortcut
+    Invalid(String),
+    /// The app uses this shortcut for something else
+    Reserved(String),
+    /// Already registered, by this app or another application
+    Conflict(String),
+    /// The OS refused the shortcut for another reason
+    Registration(String),
+}
+
+impl fmt::Display for ShortcutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortcutError::Invalid(msg) => write!(f, "Invalid shortcut: {}", msg),
+            ShortcutError::Reserved(name) => write!(f, "Shortcut is reserved: {}", name),
+            ShortcutError::Conflict(name) => write!(f, "Shortcut is already in use: {}", name),
+            ShortcutError::Registration(msg) => write!(f, "Failed to register shortcut: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShortcutError {}
+
+/// Modifier names, in display order
+const MODIFIERS: &[(&[&str], Modifiers, &str)] = &[
+    (&["ctrl", "control", "ctl"], Modifiers::CONTROL, "Ctrl"),
+    (&["alt", "option"], Modifiers::ALT, "Alt"),
+    (&["shift"], Modifiers::SHIFT, "Shift"),
+    (
+        &["super", "win", "windows", "cmd", "command", "meta"],
+        Modifiers::SUPER,
+        "Super",
+    ),
+];
+
+/// Friendly key names that differ from the W3C `Code` names
+const KEY_ALIASES: &[(&str, Code)] = &[
+    ("space", Code::Space),
+    ("enter", Code::Enter),
+    ("return", Code::Enter),
+    ("tab", Code::Tab),
+    ("esc", Code::Escape),
+    ("escape", Code::Escape),
+    ("backspace", Code::Backspace),
+    ("del", Code::Delete),
+    ("delete", Code::Delete),
+    ("ins", Code::Insert),
+    ("insert", Code::Insert),
+    ("home", Code::Home),
+    ("end", Code::End),
+    ("pgup", Code::PageUp),
+    ("pageup", Code::PageUp),
+    ("pgdn", Code::PageDown),
+    ("pagedown", Code::PageDown),
+    ("up", Code::ArrowUp),
+    ("down", Code::ArrowDown),
+    ("left", Code::ArrowLeft),
+    ("right", Code::ArrowRight),
+    ("-", Code::Minus),
+    ("=", Code::Equal),
+    (",", Code::Comma),
+    (".", Code::Period),
+    ("/", Code::Slash),
+    (";", Code::Semicolon),
+    ("'", Code::Quote),
+    ("`", Code::Backquote),
+    ("[", Code::BracketLeft),
+    ("]", Code::BracketRight),
+    ("\\", Code::Backslash),
+];
+
+/// A parsed shortcut; `Display` gives the canonical form stored in settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub modifiers: Modifiers,
+    pub key: Code,
+}
+
+impl Hotkey {
+    pub fn shortcut(&self) -> Shortcut {
+        Shortcut::new(Some(self.modifiers), self.key)
+    }
+}
+
+fn parse_modifier(name: &str) -> Option<Modifiers> {
+    let name = name.to_lowercase();
+    MODIFIERS
+        .iter()
+        .find(|(names, _, _)| names.contains(&name.as_str()))
+        .map(|(_, modifier, _)| *modifier)
+}
+
+fn parse_key(name: &str) -> Option<Code> {
+    let lower = name.to_lowercase();
+    if let Some((_, code)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        return Some(*code);
+    }
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Code::from_str(&format!("Key{}", c.to_ascii_uppercase())).ok();
+        }
+        if c.is_ascii_digit() {
+            return Code::from_str(&format!("Digit{}", c)).ok();
+        }
+    }
+
+    if let Some(n) = lower.strip_prefix('f') {
+        if let Ok(n) = n.parse::<u8>() {
+            return Code::from_str(&format!("F{}", n)).ok();
+        }
+    }
+
+    // Fall back to W3C names such as "Numpad5" or "MediaPlayPause"
+    Code::from_str(name)
+        .ok()
+        .filter(|code| *code != Code::Unidentified && !is_modifier_key(*code))
+}
+
+fn is_modifier_key(code: Code) -> bool {
+    matches!(
+        code,
+        Code::ControlLeft
+            | Code::ControlRight
+            | Code::AltLeft
+            | Code::AltRight
+            | Code::ShiftLeft
+            | Code::ShiftRight
+            | Code::MetaLeft
+            | Code::MetaRight
+    )
+}
+
+fn is_function_key(code: Code) -> bool {
+    let name = code.to_string();
+    name.len() > 1 && name.starts_with('F') && name[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+impl FromStr for Hotkey {
+    type Err = ShortcutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ShortcutError::Invalid("shortcut is empty".to_string()));
+        }
+
+        let mut modifiers = Modifiers::empty();
+        let mut key = None;
+        for part in s.split('+').map(str::trim) {
+            if part.is_empty() {
+                return Err(ShortcutError::Invalid(format!("empty key in \"{}\"", s)));
+            }
+
+            if let Some(modifier) = parse_modifier(part) {
+                if modifiers.contains(modifier) {
+                    return Err(ShortcutError::Invalid(format!("{} appears twice", part)));
+                }
+                modifiers |= modifier;
+                continue;
+            }
+
+            if key.is_some() {
+                return Err(ShortcutError::Invalid(
+                    "only one key besides modifiers is allowed".to_string(),
+                ));
+            }
+            key = Some(
+                parse_key(part)
+                    .ok_or_else(|| ShortcutError::Invalid(format!("unknown key \"{}\"", part)))?,
+            );
+        }
+
+        let key = key.ok_or_else(|| {
+            ShortcutError::Invalid("a key is needed besides modifiers".to_string())
+        })?;
+
+        // A bare letter as a global shortcut would swallow it in every other app
+        if modifiers.is_empty() && !is_function_key(key) {
+            return Err(ShortcutError::Invalid(
+                "add Ctrl, Alt, Shift or Super, or use a function key".to_string(),
+            ));
+        }
+
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (_, modifier, name) in MODIFIERS {
+            if self.modifiers.contains(*modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+
+        let code = self.key.to_string();
+        let symbol = KEY_ALIASES
+            .iter()
+            .find(|(name, c)| *c == self.key && !name.chars().all(char::is_alphabetic));
+        if let Some((symbol, _)) = symbol {
+            write!(f, "{}", symbol)
+        } else if let Some(letter) = code.strip_prefix("Key") {
+            write!(f, "{}", letter)
+        } else if let Some(digit) = code.strip_prefix("Digit") {
+            write!(f, "{}", digit)
+        } else if let Some(arrow) = code.strip_prefix("Arrow") {
+            write!(f, "{}", arrow)
+        } else {
+            write!(f, "{}", code)
+        }
+    }
+}
+
+/// Shortcuts the app registers for its own purposes while recording
+fn reserved(hotkey: &Hotkey) -> bool {
+    hotkey.key == Code::Escape
+}
+
+/// Register `hotkey` to call `action` when it is pressed or released
+fn bind<F>(app: &AppHandle, hotkey: &Hotkey, action: F) -> Result<(), ShortcutError>
+where
+    F: Fn(&AppHandle, ShortcutState) + Send + Sync + 'static,
+{
+    if reserved(hotkey) {
+        return Err(ShortcutError::Reserved(hotkey.to_string()));
+    }
+
+    let shortcuts = app.global_shortcut();
+    let shortcut = hotkey.shortcut();
+    if shortcuts.is_registered(shortcut) {
+        return Err(ShortcutError::Conflict(hotkey.to_string()));
+    }
+
+    let name = hotkey.to_string();
+    shortcuts
+        .on_shortcut(shortcut, move |app, _shortcut, event| {
+            log::debug!("Hotkey {}: {:?}", name, event.state());
+            action(app, event.state());
+        })
+        .map_err(|e| {
+            let message = e.to_string();
+            if message.to_lowercase().contains("already registered") {
+                ShortcutError::Conflict(hotkey.to_string())
+            } else {
+                ShortcutError::Registration(message)
+            }
+        })
+}
+
+/// Register `hotkey` to toggle recording
+pub fn register(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    hotkey: &Hotkey,
+) -> Result<(), ShortcutError> {
+    let handler_state = state.clone();
+    bind(app, hotkey, move |app, event| {
+        hotkey::handle_hotkey(app, &handler_state, event)
+    })?;
+    state.lock().unwrap().hotkey = Some(*hotkey);
+    log::info!("Recording shortcut registered: {}", hotkey);
+    Ok(())
+}
+
+/// Replace the registered recording shortcut with `new`. On failure the
+/// registered one stays.
+pub fn rebind(
+    app: &AppHandle,
+    state: &Arc<Mutex<RecordingState>>,
+    new: &Hotkey,
+) -> Result<(), ShortcutError> {
+    let current = state.lock().unwrap().hotkey;
+    if current == Some(*new) {
+        return Ok(());
+    }
+
+    register(app, state, new)?;
+    if let Some(current) = current {
+        if let Err(e) = app.global_shortcut().unregister(current.shortcut()) {
+            log::warn!("Failed to unregister old shortcut {}: {}", current, e);
+        }
+    }
+    Ok(())
+}
+
+/// Unregister the recording shortcut, if one is registered
+pub fn unregister(app: &AppHandle, state: &Arc<Mutex<RecordingState>>) {
+    let Some(hotkey) = state.lock().unwrap().hotkey.take() else {
+        return;
+    };
+    if let Err(e) = app.global_shortcut().unregister(hotkey.shortcut()) {
+        log::warn!("Failed to unregister shortcut {}: {}", hotkey, e);
+    }
+}
+
+/// Register `hotkey` to switch to the template `id`
+pub fn register_template(app: &AppHandle, id: &str, hotkey: &Hotkey) -> Result<(), ShortcutError> {
+    let id = id.to_string();
+    bind(app, hotkey, move |app, event| {
+        if event == ShortcutState::Pressed {
+            if let Err(e) = commands::activate_template(app, &id) {
+                log::warn!("Failed to switch to template {}: {}", id, e);
+            }
+        }
+    })?;
+    log::info!("Template shortcut registered: {}", hotkey);
+    Ok(())
+}
+
+/// Parse template shortcuts as stored in the settings (template id -> shortcut)
+pub fn parse_template_hotkeys(
+    hotkeys: &BTreeMap<String, String>,
+) -> Result<Vec<(String, Hotkey)>, ShortcutError> {
+    hotkeys
+        .iter()
+        .map(|(id, hotkey)| Ok((id.clone(), hotkey.parse()?)))
+        .collect()
+}
+
+fn unregister_templates(app: &AppHandle, hotkeys: &[(String, Hotkey)]) {
+    for (_, hotkey) in hotkeys {
+        if let Err(e) = app.global_shortcut().unregister(hotkey.shortcut()) {
+            log::debug!("Failed to unregister template shortcut {}: {}", hotkey, e);
+        }
+    }
+}
+
+/// Replace the template shortcuts in `current` with those in `new`. On failure
+/// the shortcuts in `current` stay registered.
+pub fn rebind_templates(
+    app: &AppHandle,
+    current: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Result<(), ShortcutError> {
+    if current == new {
+        return Ok(());
+    }
+
+    let new = parse_template_hotkeys(new)?;
+    // The current shortcuts were validated when they were saved
+    let current = parse_template_hotkeys(current).unwrap_or_default();
+    unregister_templates(app, &current);
+
+    for (registered, (id, hotkey)) in new.iter().enumerate() {
+        if let Err(e) = register_template(app, id, hotkey) {
+            unregister_templates(app, &new[..registered]);
+            for (id, hotkey) in &current {
+                if let Err(e) = register_template(app, id, hotkey) {
+                    log::warn!("Failed to restore template shortcut {}: {}", hotkey, e);
+                }
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Hotkey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_default_shortcut() {
+        let hotkey = parse("Ctrl+Shift+Space");
+        assert_eq!(hotkey.modifiers, Modifiers::CONTROL | Modifiers::SHIFT);
+        assert_eq!(hotkey.key, Code::Space);
+    }
+
+    #[test]
+    fn test_parse_is_lenient_about_case_and_aliases() {
+        assert_eq!(parse("ctrl + alt + d"), parse("Control+Option+D"));
+        assert_eq!(parse("Win+Up").key, Code::ArrowUp);
+        assert_eq!(parse("Cmd+Shift+5").key, Code::Digit5);
+        assert_eq!(parse("Alt+Numpad5").key, Code::Numpad5);
+        assert_eq!(parse("Ctrl+-").key, Code::Minus);
+    }
+
+    #[test]
+    fn test_function_keys_need_no_modifier() {
+        assert_eq!(parse("F9").modifiers, Modifiers::empty());
+        assert_eq!(parse("f12").key, Code::F12);
+    }
+
+    #[test]
+    fn test_invalid_shortcuts() {
+        for s in [
+            "",
+            "Ctrl+",
+            "Ctrl+Shift",
+            "D",
+            "Ctrl+Ctrl+D",
+            "Ctrl+D+E",
+            "Ctrl+Banana",
+            "Ctrl+ShiftLeft",
+        ] {
+            assert!(
+                matches!(s.parse::<Hotkey>(), Err(ShortcutError::Invalid(_))),
+                "{:?} should be rejected",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_form_round_trips() {
+        for (input, canonical) in [
+            ("shift+ctrl+space", "Ctrl+Shift+Space"),
+            ("alt+ctrl+d", "Ctrl+Alt+D"),
+            ("super+pgup", "Super+PageUp"),
+            ("ctrl+alt+left", "Ctrl+Alt+Left"),
+            ("ctrl+/", "Ctrl+/"),
+            ("F7", "F7"),
+            ("Ctrl+7", "Ctrl+7"),
+        ] {
+            let hotkey = parse(input);
+            assert_eq!(hotkey.to_string(), canonical);
+            assert_eq!(parse(canonical), hotkey);
+        }
+    }
+
+    #[test]
+    fn test_parse_template_hotkeys() {
+        let hotkeys = BTreeMap::from([
+            ("email".to_string(), "alt+ctrl+e".to_string()),
+            ("slack".to_string(), "F8".to_string()),
+        ]);
+        let parsed = parse_template_hotkeys(&hotkeys).unwrap();
+        assert_eq!(parsed[0], ("email".to_string(), parse("Ctrl+Alt+E")));
+        assert_eq!(parsed[1].1.key, Code::F8);
+
+        let invalid = BTreeMap::from([("email".to_string(), "E".to_string())]);
+        assert!(matches!(
+            parse_template_hotkeys(&invalid),
+            Err(ShortcutError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_error_serialization() {
+        let error = ShortcutError::Conflict("Ctrl+Alt+D".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "kind": "conflict", "message": "Ctrl+Alt+D" })
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Server-sent events, the format chat completion APIs stream responses in.
+//!
+//! Only `data` fields matter for chat completions; `event`, `id` and `retry`
+//! are ignored, as are comments such as DeepSeek's `: keep-alive`.
+
+/// Splits a byte stream into the data of its events. Chunks may end anywhere,
+/// even inside a line or a UTF-8 character.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    /// Bytes after the last complete line
+    buffer: Vec<u8>,
+    /// Data lines of the event being read
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed the next chunk; returns the data of every event it completes
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(event) = self.line(line.trim_end_matches(['\n', '\r'])) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// The event left over when the stream ends without a blank line after it
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = String::from_utf8_lossy(&rest);
+        if !rest.is_empty() {
+            self.line(rest.trim_end_matches('\r'));
+        }
+        self.dispatch()
+    }
+
+    fn line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        if field == "data" {
+            self.data.push(value.to_string());
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_in_chunks(stream: &[u8], size: usize) -> Vec<String> {
+        let mut parser = SseParser::default();
+        let mut events: Vec<String> = stream
+            .chunks(size)
+            .flat_map(|chunk| parser.feed(chunk))
+            .collect();
+        events.extend(parser.finish());
+        events
+    }
+
+    #[test]
+    fn test_events() {
+        let stream = b": keep-alive\n\ndata: {\"a\":1}\n\nevent: message\nid: 7\ndata:{\"b\":2}\n\ndata: [DONE]\n\n";
+        assert_eq!(
+            parse_in_chunks(stream, stream.len()),
+            vec!["{\"a\":1}", "{\"b\":2}", "[DONE]"]
+        );
+    }
+
+    #[test]
+    fn test_multi_line_data_and_crlf() {
+        let stream = b"data: first\r\ndata: second\r\n\r\ndata\r\n\r\n";
+        assert_eq!(parse_in_chunks(stream, 64), vec!["first\nsecond", ""]);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let stream = "data: Zażółć\r\n\r\ndata: gęślą jaźń\n\n".as_bytes();
+        // Every split point, including inside "\r\n" and multi-byte characters
+        for size in 1..stream.len() {
+            assert_eq!(
+                parse_in_chunks(stream, size),
+                vec!["Zażółć", "gęślą jaźń"],
+                "chunks of {} bytes",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_unterminated_last_event() {
+        assert_eq!(parse_in_chunks(b"data: a\n\ndata: b", 3), vec!["a", "b"]);
+        assert_eq!(parse_in_chunks(b"data: a\n\n", 3), vec!["a"]);
+    }
+}
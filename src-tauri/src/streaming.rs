@@ -0,0 +1,431 @@
+//! Incremental transcription while the user is still speaking.
+//!
+//! A worker thread periodically re-transcribes the uncommitted tail of the
+//! recording. Segments that come out the same in two consecutive passes are
+//! committed and their audio is dropped from the window, so by the time the
+//! hotkey is released only the last few seconds still need a Whisper pass.
+
+use crate::audio::{CaptureTap, Recording};
+use crate::inference::{CancelToken, InferenceWorker, JobOptions};
+use crate::resample::{downmix_to_mono, Resampler};
+use crate::transcription::{
+    join_segments, Detection, Language, Segment, TranscriptionOptions, WHISPER_SAMPLE_RATE,
+};
+use crate::vad;
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How often the worker checks for new audio
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// New audio required before another pass is worth running
+const STEP_SECS: f32 = 2.0;
+
+/// Shortest window Whisper is asked to transcribe
+const MIN_WINDOW_SECS: f32 = 1.0;
+
+/// Window length at which segments are committed without waiting for agreement
+const MAX_WINDOW_SECS: f32 = 20.0;
+
+/// Payload of the `transcription-partial` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PartialTranscript {
+    /// Text that will not change any more
+    pub stable: String,
+    /// Current guess for the audio after the stable part
+    pub tentative: String,
+}
+
+/// Commit policy over consecutive passes of the same window.
+///
+/// A segment is committed once two passes agree on it and it is not the last
+/// segment of the window (which may be cut mid-word).
+#[derive(Debug, Default)]
+pub struct SegmentAgreement {
+    committed: Vec<String>,
+    previous: Vec<Segment>,
+}
+
+impl SegmentAgreement {
+    /// Feed the segments of a pass over the current window.
+    ///
+    /// Returns the window position (seconds) up to which audio is now committed.
+    pub fn update(&mut self, segments: Vec<Segment>, window_secs: f32) -> f32 {
+        let candidates = segments.len().saturating_sub(1);
+        let mut agreed = segments
+            .iter()
+            .zip(&self.previous)
+            .take(candidates)
+            .take_while(|(current, previous)| same_text(current, previous))
+            .count();
+
+        if window_secs >= MAX_WINDOW_SECS {
+            agreed = candidates;
+        }
+
+        let commit_until = if agreed > 0 {
+            segments[agreed - 1].end.clamp(0.0, window_secs)
+        } else {
+            0.0
+        };
+
+        self.committed.extend(
+            segments[..agreed]
+                .iter()
+                .map(|s| s.text.trim().to_string())
+                .filter(|t| !t.is_empty()),
+        );
+
+        // Remaining segments, re-based to the new window start
+        self.previous = segments[agreed..]
+            .iter()
+            .map(|s| Segment {
+                text: s.text.clone(),
+                start: (s.start - commit_until).max(0.0),
+                end: (s.end - commit_until).max(0.0),
+            })
+            .collect();
+
+        commit_until
+    }
+
+    pub fn committed_text(&self) -> String {
+        self.committed.join(" ")
+    }
+
+    pub fn partial(&self) -> PartialTranscript {
+        PartialTranscript {
+            stable: self.committed_text(),
+            tentative: join_segments(&self.previous),
+        }
+    }
+}
+
+fn same_text(a: &Segment, b: &Segment) -> bool {
+    normalize(&a.text) == normalize(&b.text)
+}
+
+fn normalize(text: &str) -> String {
+    text.trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase()
+}
+
+/// Audio and agreement state owned by the streaming worker
+struct StreamState {
+    resampler: Resampler,
+    channels: u16,
+    /// Interleaved samples consumed from the capture buffer
+    read_offset: usize,
+    /// Uncommitted 16 kHz mono audio
+    window: Vec<f32>,
+    /// Window samples added since the last pass
+    pending: usize,
+    agreement: SegmentAgreement,
+    /// Last pass that recognised the language
+    detection: Detection,
+}
+
+impl StreamState {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            resampler: Resampler::new(sample_rate, WHISPER_SAMPLE_RATE),
+            channels,
+            read_offset: 0,
+            window: Vec::new(),
+            pending: 0,
+            agreement: SegmentAgreement::default(),
+            detection: Detection::UNKNOWN,
+        }
+    }
+
+    fn feed(&mut self, interleaved: &[f32]) {
+        self.read_offset += interleaved.len();
+        let mono = downmix_to_mono(interleaved, self.channels);
+        let resampled = self.resampler.process(&mono);
+        self.pending += resampled.len();
+        self.window.extend(resampled);
+    }
+
+    fn window_secs(&self) -> f32 {
+        self.window.len() as f32 / WHISPER_SAMPLE_RATE as f32
+    }
+
+    fn ready_for_pass(&self) -> bool {
+        self.window_secs() >= MIN_WINDOW_SECS
+            && self.pending as f32 / WHISPER_SAMPLE_RATE as f32 >= STEP_SECS
+    }
+
+    /// Apply the result of a pass over the current window
+    fn apply_pass(&mut self, segments: Vec<Segment>, detection: Detection) {
+        self.pending = 0;
+        if detection.language != Language::Unknown {
+            self.detection = detection;
+        }
+
+        let commit_until = self.agreement.update(segments, self.window_secs());
+        let drop = ((commit_until * WHISPER_SAMPLE_RATE as f32) as usize).min(self.window.len());
+        self.window.drain(..drop);
+    }
+}
+
+/// A running streaming transcription tied to one recording
+pub struct StreamingSession {
+    stop: Arc<AtomicBool>,
+    /// Cancels a pass that is queued or running on the inference worker
+    cancel: CancelToken,
+    handle: JoinHandle<StreamState>,
+    inference: InferenceWorker,
+}
+
+impl StreamingSession {
+    /// Start transcribing audio from `tap` in the background
+    pub fn start(
+        app: AppHandle,
+        tap: CaptureTap,
+        inference: InferenceWorker,
+        transcription: TranscriptionOptions,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let cancel = CancelToken::default();
+        let worker_stop = stop.clone();
+        let worker_cancel = cancel.clone();
+        let worker_inference = inference.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut state = StreamState::new(tap.sample_rate, tap.channels);
+
+            while !worker_stop.load(Ordering::SeqCst) {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let chunk = tap.read_from(state.read_offset);
+                state.feed(&chunk);
+                if !state.ready_for_pass() {
+                    continue;
+                }
+
+                // Whisper invents text for silence, so wait for actual speech
+                if vad::speech_bounds(&state.window, WHISPER_SAMPLE_RATE).is_none() {
+                    continue;
+                }
+
+                let options = JobOptions {
+                    transcription: transcription.clone(),
+                    cancel: worker_cancel.clone(),
+                    ..JobOptions::default()
+                };
+                let result = worker_inference
+                    .submit(state.window.clone(), options)
+                    .wait();
+                match result {
+                    Ok(transcript) => {
+                        state.apply_pass(transcript.segments, transcript.detection);
+                        let _ = app.emit("transcription-partial", state.agreement.partial());
+                    }
+                    Err(e) => log::warn!("Streaming pass failed: {}", e),
+                }
+            }
+
+            state
+        });
+
+        log::info!("Streaming transcription started");
+        Self {
+            stop,
+            cancel,
+            handle,
+            inference,
+        }
+    }
+
+    /// Stop the worker without waiting for it and discard its results
+    pub fn cancel(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.cancel.cancel();
+        log::info!("Streaming transcription cancelled");
+    }
+
+    /// Stop the worker and transcribe whatever is left of `recording`
+    pub async fn finish(
+        self,
+        recording: &Recording,
+        options: JobOptions,
+    ) -> Result<(String, Detection)> {
+        self.stop.store(true, Ordering::SeqCst);
+        // The worker may be waiting for a pass to finish
+        let handle = self.handle;
+        let mut state = tauri::async_runtime::spawn_blocking(move || handle.join())
+            .await?
+            .map_err(|_| anyhow::anyhow!("Streaming transcription worker panicked"))?;
+
+        if state.read_offset < recording.samples.len() {
+            state.feed(&recording.samples[state.read_offset..]);
+        }
+        let tail = state.resampler.flush();
+        state.window.extend(tail);
+
+        let mut text = state.agreement.committed_text();
+        let mut detection = state.detection;
+
+        let tail_secs = state.window_secs();
+        if let Some(speech) = vad::trim_silence(&state.window, WHISPER_SAMPLE_RATE) {
+            let tail = self
+                .inference
+                .submit(speech.to_vec(), options)
+                .result()
+                .await?;
+            let tail_text = tail.text();
+            if !tail_text.is_empty() {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(&tail_text);
+            }
+            if tail.detection.language != Language::Unknown {
+                detection = tail.detection;
+            }
+        }
+
+        log::info!(
+            "Streaming transcription finished: {} chars, final pass over {:.1}s",
+            text.len(),
+            tail_secs
+        );
+        Ok((text, detection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(text: &str, start: f32, end: f32) -> Segment {
+        Segment {
+            text: text.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_first_pass_commits_nothing() {
+        let mut agreement = SegmentAgreement::default();
+        let commit = agreement.update(
+            vec![seg("Hello there.", 0.0, 1.5), seg("How are", 1.5, 3.0)],
+            3.0,
+        );
+
+        assert_eq!(commit, 0.0);
+        assert_eq!(
+            agreement.partial(),
+            PartialTranscript {
+                stable: String::new(),
+                tentative: "Hello there. How are".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_agreed_segments_are_committed() {
+        let mut agreement = SegmentAgreement::default();
+        agreement.update(
+            vec![seg("Hello there.", 0.0, 1.5), seg("How are", 1.5, 3.0)],
+            3.0,
+        );
+        let commit = agreement.update(
+            vec![
+                seg(" hello there", 0.0, 1.6),
+                seg("How are you?", 1.6, 4.0),
+                seg("I", 4.0, 5.0),
+            ],
+            5.0,
+        );
+
+        assert_eq!(commit, 1.6);
+        let partial = agreement.partial();
+        assert_eq!(partial.stable, "hello there");
+        assert_eq!(partial.tentative, "How are you? I");
+    }
+
+    #[test]
+    fn test_last_segment_never_committed_by_agreement() {
+        let mut agreement = SegmentAgreement::default();
+        agreement.update(vec![seg("One two", 0.0, 2.0)], 2.0);
+        let commit = agreement.update(vec![seg("One two", 0.0, 2.0)], 4.0);
+
+        assert_eq!(commit, 0.0);
+        assert_eq!(agreement.committed_text(), "");
+    }
+
+    #[test]
+    fn test_long_window_forces_commit() {
+        let mut agreement = SegmentAgreement::default();
+        let commit = agreement.update(
+            vec![
+                seg("First.", 0.0, 8.0),
+                seg("Second.", 8.0, 16.0),
+                seg("Third", 16.0, 21.0),
+            ],
+            21.0,
+        );
+
+        assert_eq!(commit, 16.0);
+        assert_eq!(agreement.committed_text(), "First. Second.");
+        assert_eq!(agreement.partial().tentative, "Third");
+    }
+
+    #[test]
+    fn test_remaining_segments_are_rebased() {
+        let mut agreement = SegmentAgreement::default();
+        agreement.update(vec![seg("A.", 0.0, 1.0), seg("B", 1.0, 2.0)], 2.0);
+        let commit = agreement.update(
+            vec![
+                seg("A.", 0.0, 1.0),
+                seg("Bee", 1.0, 2.0),
+                seg("C", 2.0, 3.0),
+            ],
+            3.0,
+        );
+        assert_eq!(commit, 1.0);
+
+        // Next pass runs over the shortened window, so "Bee" now starts at zero
+        let commit = agreement.update(
+            vec![
+                seg("Bee.", 0.0, 1.0),
+                seg("C", 1.0, 2.5),
+                seg("D", 2.5, 3.0),
+            ],
+            3.0,
+        );
+        assert_eq!(commit, 2.5);
+        assert_eq!(agreement.committed_text(), "A. Bee. C");
+    }
+
+    #[test]
+    fn test_stream_state_drops_committed_audio() {
+        let mut state = StreamState::new(WHISPER_SAMPLE_RATE, 1);
+        state.feed(&vec![0.0; WHISPER_SAMPLE_RATE as usize * 3]);
+        assert!(state.ready_for_pass());
+
+        state.apply_pass(
+            vec![seg("One.", 0.0, 1.0), seg("Two", 1.0, 3.0)],
+            Detection {
+                language: Language::Known("en"),
+                confidence: Some(0.8),
+            },
+        );
+        state.apply_pass(
+            vec![seg("One.", 0.0, 1.0), seg("Two", 1.0, 3.0)],
+            Detection::UNKNOWN,
+        );
+
+        assert_eq!(state.window.len(), WHISPER_SAMPLE_RATE as usize * 2);
+        assert_eq!(state.detection.language, Language::Known("en"));
+        assert!(!state.ready_for_pass());
+    }
+}
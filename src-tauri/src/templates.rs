@@ -0,0 +1,669 @@
+//! Named output styles for post-processing.
+//!
+//! Each template is a Markdown file in the templates directory: a `# Name`
+//! heading, an optional `Language: <code>` line that pins the output language,
+//! then the prompt sent to the language model. `{{input}}`, `{{language}}` and
+//! `{{source_language}}` in the prompt are filled in per recording. Built-in templates are copied there the first time a version
+//! ships them and can be edited or deleted like the user's own; only the
+//! default one always exists.
+
+use crate::fs_util;
+use crate::postprocess::Request;
+use crate::transcription::Language;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Id of the template used when none is selected or the selected one is gone
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+const TEMPLATE_EXTENSION: &str = "md";
+
+/// Starts the line under the heading that pins a template's output language
+const LANGUAGE_PREFIX: &str = "Language:";
+
+/// Lists the built-in templates already copied into the directory
+const INSTALLED_FILE: &str = ".builtin";
+
+const BUILTIN: &[(&str, &str)] = &[
+    (DEFAULT_TEMPLATE, include_str!("../templates/default.md")),
+    ("verbatim", include_str!("../templates/verbatim.md")),
+    ("email", include_str!("../templates/email.md")),
+    ("bullet-notes", include_str!("../templates/bullet-notes.md")),
+    ("slack", include_str!("../templates/slack.md")),
+    (
+        "commit-message",
+        include_str!("../templates/commit-message.md"),
+    ),
+    (
+        "formal-polish",
+        include_str!("../templates/formal-polish.md"),
+    ),
+    ("code-comment", include_str!("../templates/code-comment.md")),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    /// File name without the extension, e.g. "bullet-notes"
+    pub id: String,
+    /// Name shown in the tray and settings, e.g. "Bullet notes"
+    pub name: String,
+    /// Language code the template always writes in, e.g. "pl" for "Formal
+    /// Polish"; the translation target does not apply to it
+    #[serde(default)]
+    pub language: Option<String>,
+    pub prompt: String,
+}
+
+/// A rendered template, ready to be sent as chat messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub system: Option<String>,
+    pub user: String,
+}
+
+impl Template {
+    /// Read a template file; the name comes from a leading `# ` heading, else from `id`
+    pub fn parse(id: &str, contents: &str) -> Self {
+        let contents = contents.trim_start_matches('\u{feff}').trim();
+        let (name, prompt) = match contents.strip_prefix("# ") {
+            Some(rest) => {
+                let (name, prompt) = rest.split_once('\n').unwrap_or((rest, ""));
+                (name.trim().to_string(), prompt.trim())
+            }
+            None => (id.to_string(), contents),
+        };
+        let (language, prompt) = match prompt.split_once('\n') {
+            Some((first, rest)) if first.starts_with(LANGUAGE_PREFIX) => (Some(first), rest),
+            _ if prompt.starts_with(LANGUAGE_PREFIX) => (Some(prompt), ""),
+            _ => (None, prompt),
+        };
+
+        Self {
+            id: id.to_string(),
+            name,
+            language: language.map(|line| line[LANGUAGE_PREFIX.len()..].trim().to_string()),
+            prompt: prompt.trim().replace("\r\n", "\n"),
+        }
+    }
+
+    /// The file contents `parse` reads back
+    pub fn to_file(&self) -> String {
+        let language = match &self.language {
+            Some(code) => format!("{} {}\n", LANGUAGE_PREFIX, code),
+            None => String::new(),
+        };
+        format!(
+            "# {}\n{}\n{}\n",
+            self.name.trim(),
+            language,
+            self.prompt.trim()
+        )
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !is_valid_id(&self.id) {
+            anyhow::bail!(
+                "Template id must be lowercase letters, digits and dashes: {}",
+                self.id
+            );
+        }
+        if self.name.trim().is_empty() || self.name.contains('\n') {
+            anyhow::bail!("Template name must be a single non-empty line");
+        }
+        if self.prompt.trim().is_empty() {
+            anyhow::bail!("Template prompt must not be empty");
+        }
+        if let Some(code) = &self.language {
+            if Language::from_code(code) == Language::Unknown {
+                anyhow::bail!("Unknown template language: {}", code);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in the variables for `request`.
+    ///
+    /// A prompt with `{{input}}` is sent as a single user message. Otherwise it
+    /// becomes the system message and the transcript follows as the user message.
+    /// Prompts that never mention `{{language}}` still get told to translate,
+    /// unless the template pins its own language.
+    pub fn render(&self, request: &Request) -> Prompt {
+        let pinned = self.language.as_deref().map(Language::from_code);
+        let target = match (pinned, request.translate_to) {
+            (Some(language), _) => language,
+            (None, Some(target @ Language::Known(_))) => target,
+            _ => request.language,
+        };
+        let instructions = self
+            .prompt
+            .replace("{{language}}", &language_phrase(target))
+            .replace("{{source_language}}", &language_phrase(request.language));
+        let follows_target = pinned.is_none() && !self.prompt.contains("{{language}}");
+        let translation = match request.translate_to {
+            Some(Language::Known(_)) if follows_target => Some(format!(
+                "Translate the output into {} and respond only in that language.",
+                language_phrase(target)
+            )),
+            _ => None,
+        };
+
+        if instructions.contains("{{input}}") {
+            Prompt {
+                system: translation,
+                user: instructions.replace("{{input}}", request.text),
+            }
+        } else {
+            let system = match translation {
+                Some(translation) => format!("{}\n\n{}", instructions, translation),
+                None => instructions,
+            };
+            Prompt {
+                system: Some(system),
+                user: format!("Input: {}\nOutput:", request.text),
+            }
+        }
+    }
+}
+
+/// How the prompt refers to `language`
+fn language_phrase(language: Language) -> String {
+    match language {
+        Language::Known(code) => format!("the language with ISO 639 code \"{}\"", code),
+        Language::Unknown => "the same language as the input".to_string(),
+    }
+}
+
+/// Whether `id` can name a template file
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && !id.starts_with('-')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Id-friendly form of `name`, e.g. "Meeting notes" -> "meeting-notes"
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    // Leave room for the suffix `unused_id` may add
+    slug.truncate(56);
+    slug.trim_end_matches('-').to_string()
+}
+
+/// The templates that ship with the app
+pub fn builtin() -> Vec<Template> {
+    BUILTIN
+        .iter()
+        .map(|(id, contents)| Template::parse(id, contents))
+        .collect()
+}
+
+/// The built-in default template, whatever the user did to its file
+pub fn default_template() -> Template {
+    builtin()
+        .into_iter()
+        .find(|t| t.id == DEFAULT_TEMPLATE)
+        .expect("default template is built in")
+}
+
+/// Default location of the templates directory
+pub fn default_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ValleyFlow")
+        .join("templates")
+}
+
+/// The templates in a directory, default first and the rest by name
+#[derive(Debug, Clone)]
+pub struct Templates {
+    dir: PathBuf,
+    templates: Vec<Template>,
+}
+
+impl Templates {
+    /// Load the templates in `dir`, first adding built-in ones it never had
+    pub fn load(dir: &Path) -> Self {
+        if let Err(e) = install_builtin(dir) {
+            log::warn!("Failed to create templates in {:?}: {:#}", dir, e);
+        }
+
+        let mut templates = match read_dir(dir) {
+            Ok(templates) => templates,
+            Err(e) => {
+                log::warn!("Failed to read templates from {:?}: {:#}", dir, e);
+                Vec::new()
+            }
+        };
+        if !templates.iter().any(|t| t.id == DEFAULT_TEMPLATE) {
+            templates.push(default_template());
+        }
+
+        let mut loaded = Self {
+            dir: dir.to_path_buf(),
+            templates,
+        };
+        loaded.sort();
+        loaded
+    }
+
+    pub fn all(&self) -> &[Template] {
+        &self.templates
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Template> {
+        self.templates.iter().find(|t| t.id == id)
+    }
+
+    /// An id for a new template called `name` that no other template has
+    pub fn unused_id(&self, name: &str) -> String {
+        let base = match slug(name) {
+            slug if slug.is_empty() => "template".to_string(),
+            slug => slug,
+        };
+        let mut id = base.clone();
+        let mut n = 2;
+        while self.get(&id).is_some() {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    /// The template `id`, or the default one if it does not exist
+    pub fn resolve(&self, id: &str) -> Template {
+        match self.get(id) {
+            Some(template) => template.clone(),
+            None => {
+                log::warn!("Template {} not found, using {}", id, DEFAULT_TEMPLATE);
+                self.get(DEFAULT_TEMPLATE)
+                    .cloned()
+                    .unwrap_or_else(default_template)
+            }
+        }
+    }
+
+    /// Create or replace a template file
+    pub fn save(&mut self, template: Template) -> Result<()> {
+        template.validate()?;
+        fs_util::write_atomic(&self.path(&template.id), template.to_file().as_bytes())
+            .context("Failed to save template")?;
+
+        self.templates.retain(|t| t.id != template.id);
+        self.templates.push(template);
+        self.sort();
+        Ok(())
+    }
+
+    /// Delete a template file; the default template cannot be deleted
+    pub fn delete(&mut self, id: &str) -> Result<()> {
+        if id == DEFAULT_TEMPLATE {
+            anyhow::bail!("The default template cannot be deleted");
+        }
+        if self.get(id).is_none() {
+            anyhow::bail!("Unknown template: {}", id);
+        }
+
+        match fs::remove_file(self.path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).context("Failed to delete template file");
+            }
+            _ => {}
+        }
+        self.templates.retain(|t| t.id != id);
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id).with_extension(TEMPLATE_EXTENSION)
+    }
+
+    fn sort(&mut self) {
+        self.templates.sort_by(|a, b| {
+            (a.id != DEFAULT_TEMPLATE, a.name.to_lowercase())
+                .cmp(&(b.id != DEFAULT_TEMPLATE, b.name.to_lowercase()))
+        });
+    }
+}
+
+/// Copy the built-in templates that were never installed; deleted ones stay deleted
+fn install_builtin(dir: &Path) -> Result<()> {
+    let marker = dir.join(INSTALLED_FILE);
+    let installed = fs::read_to_string(&marker).unwrap_or_default();
+    let installed: Vec<&str> = installed.lines().map(str::trim).collect();
+    let missing: Vec<_> = BUILTIN
+        .iter()
+        .filter(|(id, _)| !installed.contains(id))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    for (id, contents) in &missing {
+        let path = dir.join(id).with_extension(TEMPLATE_EXTENSION);
+        if !path.exists() {
+            fs_util::write_atomic(&path, contents.as_bytes())?;
+        }
+    }
+    let ids: Vec<&str> = BUILTIN.iter().map(|(id, _)| *id).collect();
+    fs_util::write_atomic(&marker, format!("{}\n", ids.join("\n")).as_bytes())?;
+
+    log::info!("{} built-in templates written to {:?}", missing.len(), dir);
+    Ok(())
+}
+
+/// Every `*.md` file in `dir` with a valid id; other files are left alone
+fn read_dir(dir: &Path) -> Result<Vec<Template>> {
+    let mut templates = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(TEMPLATE_EXTENSION) {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if !is_valid_id(id) {
+            log::warn!("Ignoring template {:?}: invalid file name", path);
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let template = Template::parse(id, &contents);
+                match template.validate() {
+                    Ok(()) => templates.push(template),
+                    Err(e) => log::warn!("Ignoring template {:?}: {:#}", path, e),
+                }
+            }
+            Err(e) => log::warn!("Failed to read template {:?}: {}", path, e),
+        }
+    }
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::temp_dir;
+
+    const POLISH: Language = Language::Known("pl");
+
+    fn template(prompt: &str) -> Template {
+        Template {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            language: None,
+            prompt: prompt.to_string(),
+        }
+    }
+
+    fn render(prompt: &str, text: &str, translate_to: Option<Language>) -> Prompt {
+        let template = template(prompt);
+        template.render(&Request {
+            text,
+            language: POLISH,
+            translate_to,
+            template: &template,
+        })
+    }
+
+    #[test]
+    fn test_builtin_templates() {
+        let templates = builtin();
+        assert_eq!(templates.len(), BUILTIN.len());
+        for template in &templates {
+            template.validate().unwrap();
+            assert!(!template.prompt.starts_with('#'), "{}", template.id);
+            // Otherwise translating would append a second, conflicting language instruction
+            assert!(
+                template.language.is_some() || template.prompt.contains("{{language}}"),
+                "{}",
+                template.id
+            );
+        }
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert!(names.contains(&"Email"));
+        assert!(names.contains(&"Formal Polish"));
+    }
+
+    #[test]
+    fn test_parse_and_write_round_trip() {
+        let template = Template::parse(
+            "notes",
+            "# Meeting notes\r\n\r\nSummarize:\r\n{{input}}\r\n",
+        );
+        assert_eq!(template.name, "Meeting notes");
+        assert_eq!(template.prompt, "Summarize:\n{{input}}");
+        assert_eq!(Template::parse("notes", &template.to_file()), template);
+
+        let untitled = Template::parse("notes", "Just a prompt");
+        assert_eq!(untitled.name, "notes");
+        assert_eq!(untitled.prompt, "Just a prompt");
+    }
+
+    #[test]
+    fn test_render_as_system_prompt() {
+        let prompt = render("Clean this up. Write in {{language}}.", "eee cześć", None);
+        assert_eq!(
+            prompt.system.as_deref(),
+            Some("Clean this up. Write in the language with ISO 639 code \"pl\".")
+        );
+        assert_eq!(prompt.user, "Input: eee cześć\nOutput:");
+
+        let template = template("Write in {{language}}.");
+        let prompt = template.render(&Request {
+            text: "hi",
+            language: Language::Unknown,
+            translate_to: None,
+            template: &template,
+        });
+        assert_eq!(
+            prompt.system.as_deref(),
+            Some("Write in the same language as the input.")
+        );
+    }
+
+    #[test]
+    fn test_render_with_input_variable() {
+        let prompt = render(
+            "Summarize ({{source_language}}):\n{{input}}",
+            "{{language}} stays as spoken",
+            None,
+        );
+        assert_eq!(prompt.system, None);
+        assert_eq!(
+            prompt.user,
+            "Summarize (the language with ISO 639 code \"pl\"):\n{{language}} stays as spoken"
+        );
+    }
+
+    #[test]
+    fn test_render_translation() {
+        let german = Some(Language::Known("de"));
+
+        let prompt = render(
+            "Write in {{language}}, not {{source_language}}.",
+            "cześć",
+            german,
+        );
+        let system = prompt.system.unwrap();
+        assert!(system.contains("Write in the language with ISO 639 code \"de\""));
+        assert!(system.contains("not the language with ISO 639 code \"pl\""));
+        assert!(!system.contains("Translate"));
+
+        // Templates that do not mention the language are still told to translate
+        let prompt = render("Clean this up.", "cześć", german);
+        assert!(prompt
+            .system
+            .unwrap()
+            .ends_with("Translate the output into the language with ISO 639 code \"de\" and respond only in that language."));
+        let prompt = render("Clean up: {{input}}", "cześć", german);
+        assert!(prompt.system.unwrap().starts_with("Translate"));
+        assert_eq!(prompt.user, "Clean up: cześć");
+
+        // An unknown target keeps the language of the input
+        let prompt = render("Clean this up.", "cześć", Some(Language::Unknown));
+        assert_eq!(prompt.system.as_deref(), Some("Clean this up."));
+    }
+
+    #[test]
+    fn test_pinned_language() {
+        let formal = Template::parse(
+            "formal",
+            "# Formal\r\nLanguage: pl\r\n\r\nAlways write in Polish.\r\n",
+        );
+        assert_eq!(formal.language.as_deref(), Some("pl"));
+        assert_eq!(formal.prompt, "Always write in Polish.");
+        assert_eq!(Template::parse("formal", &formal.to_file()), formal);
+
+        let prompt = formal.render(&Request {
+            text: "hello",
+            language: Language::Known("en"),
+            translate_to: Some(Language::Known("de")),
+            template: &formal,
+        });
+        assert_eq!(prompt.system.as_deref(), Some("Always write in Polish."));
+
+        let pinned = Template {
+            language: Some("pl".to_string()),
+            ..template("Write in {{language}}.")
+        };
+        let prompt = pinned.render(&Request {
+            text: "hello",
+            language: Language::Known("en"),
+            translate_to: Some(Language::Known("de")),
+            template: &pinned,
+        });
+        assert_eq!(
+            prompt.system.as_deref(),
+            Some("Write in the language with ISO 639 code \"pl\".")
+        );
+
+        let unknown = Template {
+            language: Some("xx".to_string()),
+            ..template("Clean")
+        };
+        assert!(unknown.validate().is_err());
+    }
+
+    #[test]
+    fn test_default_template_language() {
+        let default = default_template();
+        let render = |translate_to| {
+            let request = Request {
+                text: "eee",
+                language: POLISH,
+                translate_to,
+                template: &default,
+            };
+            default.render(&request).system.unwrap()
+        };
+        let system = render(None);
+        assert!(system.contains("Write the output in the language with ISO 639 code \"pl\""));
+
+        let system = render(Some(Language::Known("de")));
+        assert!(system.contains("Write the output in the language with ISO 639 code \"de\""));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(template("Clean").validate().is_ok());
+        for id in ["", "Email", "a b", "-x", "../x", "notes.md"] {
+            let invalid = Template {
+                id: id.to_string(),
+                ..template("Clean")
+            };
+            assert!(invalid.validate().is_err(), "{:?}", id);
+        }
+        assert!(template("  ").validate().is_err());
+        let unnamed = Template {
+            name: " ".to_string(),
+            ..template("Clean")
+        };
+        assert!(unnamed.validate().is_err());
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Meeting notes"), "meeting-notes");
+        assert_eq!(slug("  Slack: #general!  "), "slack-general");
+        assert_eq!(slug("Ważne notatki"), "wa-ne-notatki");
+        assert_eq!(slug("???"), "");
+    }
+
+    #[test]
+    fn test_first_run_installs_builtin_templates() {
+        let dir = temp_dir("templates-install");
+        let templates = Templates::load(&dir);
+        assert_eq!(templates.all().len(), BUILTIN.len());
+        assert_eq!(templates.all()[0].id, DEFAULT_TEMPLATE);
+        assert!(dir.join("email.md").exists());
+
+        // Deleted built-ins stay deleted, except the default one
+        fs::remove_file(dir.join("email.md")).unwrap();
+        fs::remove_file(dir.join("default.md")).unwrap();
+        fs::write(dir.join("readme.txt"), "not a template").unwrap();
+        let templates = Templates::load(&dir);
+        assert!(templates.get("email").is_none());
+        assert!(templates.get(DEFAULT_TEMPLATE).is_some());
+        assert_eq!(templates.all().len(), BUILTIN.len() - 1);
+
+        // Built-ins added by an update are installed, edits are kept
+        let marker = dir.join(INSTALLED_FILE);
+        let older = fs::read_to_string(&marker)
+            .unwrap()
+            .replace("code-comment\n", "");
+        fs::write(&marker, older).unwrap();
+        fs::remove_file(dir.join("code-comment.md")).unwrap();
+        fs::write(dir.join("slack.md"), "# Slack\n\nMy own prompt").unwrap();
+        let templates = Templates::load(&dir);
+        assert!(templates.get("code-comment").is_some());
+        assert!(templates.get("email").is_none());
+        assert_eq!(templates.get("slack").unwrap().prompt, "My own prompt");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_delete() {
+        let dir = temp_dir("templates-save");
+        let mut templates = Templates::load(&dir);
+
+        templates.save(template("Summarize {{input}}")).unwrap();
+        let reloaded = Templates::load(&dir);
+        assert_eq!(reloaded.get("test"), Some(&template("Summarize {{input}}")));
+
+        let invalid = Template {
+            id: "Bad Id".to_string(),
+            ..template("x")
+        };
+        assert!(templates.save(invalid).is_err());
+
+        templates.delete("test").unwrap();
+        assert!(templates.get("test").is_none());
+        assert!(!dir.join("test.md").exists());
+        assert!(templates.delete("test").is_err());
+        assert!(templates.delete(DEFAULT_TEMPLATE).is_err());
+
+        assert_eq!(templates.unused_id("Meeting notes"), "meeting-notes");
+        assert_eq!(templates.unused_id("Email"), "email-2");
+        assert_eq!(templates.unused_id("!!!"), "template");
+
+        assert_eq!(templates.resolve("missing").id, DEFAULT_TEMPLATE);
+        assert_eq!(templates.resolve("email").name, "Email");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,21 +1,190 @@
+use crate::inference::ProgressFn;
+use crate::resample;
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, Serializer};
 use std::path::PathBuf;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
-/// Language detection result
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Sample rate Whisper expects its input in
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// A language from Whisper's table, by its code ("pl", "en", "yue", ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Language {
-    Polish,
-    English,
+    Known(&'static str),
     Unknown,
 }
 
+impl Language {
+    /// The language Whisper numbers `id`
+    pub fn from_id(id: i32) -> Self {
+        whisper_rs::get_lang_str(id).map_or(Language::Unknown, Language::Known)
+    }
+
+    /// The language with code `code`, if Whisper knows it
+    pub fn from_code(code: &str) -> Self {
+        whisper_rs::get_lang_id(code).map_or(Language::Unknown, Self::from_id)
+    }
+
+    /// Every language Whisper can transcribe, in Whisper's order
+    pub fn all() -> Vec<Self> {
+        (0..=whisper_rs::get_lang_max_id())
+            .map(Self::from_id)
+            .filter(|language| *language != Language::Unknown)
+            .collect()
+    }
+
+    /// The language code, or "auto" when unknown
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Known(code) => code,
+            Language::Unknown => "auto",
+        }
+    }
+}
+
 impl From<Language> for &str {
     fn from(lang: Language) -> Self {
-        match lang {
-            Language::Polish => "pl",
-            Language::English => "en",
-            Language::Unknown => "auto",
+        lang.code()
+    }
+}
+
+impl Serialize for Language {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+/// Language of a transcript and how sure Whisper was about it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Detection {
+    pub language: Language,
+    /// Probability of `language` (0 to 1); `None` when it was forced
+    pub confidence: Option<f32>,
+}
+
+impl Detection {
+    pub const UNKNOWN: Detection = Detection {
+        language: Language::Unknown,
+        confidence: None,
+    };
+
+    /// Pick the most likely language from Whisper's per-language probabilities
+    pub fn from_probabilities(probabilities: &[f32]) -> Self {
+        probabilities
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(Detection::UNKNOWN, |(id, &p)| Detection {
+                language: Language::from_id(id as i32),
+                confidence: Some(p),
+            })
+    }
+}
+
+/// A piece of transcribed text with its position in the input (seconds)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Join segment texts into a single transcript
+pub fn join_segments(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Largest beam whisper.cpp decodes with
+const MAX_BEAM_SIZE: u32 = 8;
+
+/// Whisper gains little from more threads than this
+const MAX_DEFAULT_THREADS: u32 = 8;
+
+/// How Whisper decodes; persisted as part of the settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscriptionOptions {
+    /// Language code to force (e.g. "pl"); `None` detects it per recording
+    pub language: Option<String>,
+    /// Beam search width; 1 decodes greedily
+    pub beam_size: u32,
+    /// Decoder threads; `None` derives it from the CPU cores
+    pub threads: Option<u32>,
+    /// Temperature added on each retry when decoding fails; 0 disables the fallback
+    pub temperature_increment: f32,
+    /// Text the decoder is primed with, e.g. names and jargon it should spell right
+    pub initial_prompt: Option<String>,
+    /// Keep Whisper from emitting non-speech tokens such as "[Music]"
+    pub suppress_non_speech: bool,
+    /// Transcribe straight into English; follows the translation setting
+    #[serde(skip)]
+    pub translate: bool,
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        Self {
+            language: None,
+            beam_size: 1,
+            threads: None,
+            temperature_increment: 0.2,
+            initial_prompt: None,
+            suppress_non_speech: true,
+            translate: false,
+        }
+    }
+}
+
+impl TranscriptionOptions {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(language) = &self.language {
+            if Language::from_code(language) == Language::Unknown {
+                anyhow::bail!("Whisper does not support language: {}", language);
+            }
+        }
+        if !(1..=MAX_BEAM_SIZE).contains(&self.beam_size) {
+            anyhow::bail!("Beam size must be between 1 and {}", MAX_BEAM_SIZE);
+        }
+        if self.threads == Some(0) {
+            anyhow::bail!("Thread count must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&self.temperature_increment) {
+            anyhow::bail!("Temperature increment must be between 0 and 1");
+        }
+        Ok(())
+    }
+
+    /// Threads to decode with
+    pub fn thread_count(&self) -> u32 {
+        self.threads.unwrap_or_else(|| {
+            let cores = std::thread::available_parallelism().map_or(4, |n| n.get() as u32);
+            cores.min(MAX_DEFAULT_THREADS)
+        })
+    }
+
+    /// The initial prompt, unless it is blank
+    pub fn prompt(&self) -> Option<&str> {
+        self.initial_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+    }
+
+    fn sampling_strategy(&self) -> SamplingStrategy {
+        if self.beam_size > 1 {
+            SamplingStrategy::BeamSearch {
+                beam_size: self.beam_size as i32,
+                patience: -1.0,
+            }
+        } else {
+            SamplingStrategy::Greedy { best_of: 1 }
         }
     }
 }
@@ -27,20 +196,12 @@ pub struct Transcriber {
 
 impl Transcriber {
     /// Initialize transcriber with Whisper model
-    pub fn new(model_path: Option<PathBuf>) -> Result<Self> {
-        let model_path = model_path.unwrap_or_else(|| {
-            dirs::data_local_dir()
-                .unwrap_or_else(|| PathBuf::from("."))
-                .join("ValleyFlow")
-                .join("models")
-                .join("ggml-small.bin")
-        });
-
+    pub fn new(model_path: PathBuf) -> Result<Self> {
         log::info!("Loading Whisper model from: {:?}", model_path);
 
         if !model_path.exists() {
             anyhow::bail!(
-                "Whisper model not found at {:?}. Please download ggml-small.bin from https://huggingface.co/ggerganov/whisper.cpp",
+                "Whisper model not found at {:?}. Download a model in Settings",
                 model_path
             );
         }
@@ -54,54 +215,94 @@ impl Transcriber {
         Ok(Self { ctx, model_path })
     }
 
-    /// Transcribe audio samples to text
-    pub fn transcribe(&mut self, samples: &[f32]) -> Result<(String, Language)> {
-        // Create params
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    /// Run Whisper over `samples`, reporting progress in percent
+    pub fn run_with_progress(
+        &mut self,
+        samples: &[f32],
+        options: &TranscriptionOptions,
+        single_segment: bool,
+        progress: Option<ProgressFn>,
+    ) -> Result<(Vec<Segment>, Detection)> {
+        let threads = options.thread_count();
+        let mut state = self
+            .ctx
+            .create_state()
+            .context("Failed to create Whisper state")?;
+
+        // Whisper's own auto-detection does the same pass but keeps the
+        // probabilities to itself, so detect first and then force the result
+        let detection = match &options.language {
+            Some(code) => Detection {
+                language: Language::from_code(code),
+                confidence: None,
+            },
+            None => detect_language(&mut state, samples, threads as usize).unwrap_or_else(|e| {
+                log::warn!("Language detection failed, leaving it to Whisper: {:#}", e);
+                Detection::UNKNOWN
+            }),
+        };
 
-        // Configure for speed and accuracy balance
-        params.set_n_threads(4);
-        params.set_translate(false);
+        let mut params = FullParams::new(options.sampling_strategy());
+
+        params.set_n_threads(threads as i32);
+        params.set_translate(options.translate);
         params.set_no_context(true);
-        params.set_single_segment(true);
+        params.set_single_segment(single_segment);
         params.set_print_special(false);
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
 
-        // Auto-detect language
-        params.set_language(None);
+        // "auto" leaves detection to Whisper
+        params.set_language(Some(detection.language.code()));
+        if let Some(prompt) = options.prompt() {
+            params.set_initial_prompt(prompt);
+        }
+        params.set_temperature(0.0);
+        params.set_temperature_inc(options.temperature_increment);
+        params.set_suppress_non_speech_tokens(options.suppress_non_speech);
+
+        if let Some(progress) = progress {
+            params.set_progress_callback_safe(progress);
+        }
 
         // Run transcription
-        let state = self
-            .ctx
+        state
             .full(params, samples)
             .context("Failed to run transcription")?;
 
-        // Extract text
-        let mut text = String::new();
-        let mut detected_lang = Language::Unknown;
-
-        for i in 0..state.full_n_segments() {
-            let segment = state.full_get_segment_text(i);
-            text.push_str(&segment);
-            text.push(' ');
-
-            // Get detected language from first segment
-            if i == 0 {
-                let lang_id = state.full_lang_id();
-                detected_lang = match lang_id {
-                    18 => Language::Polish,  // Polish language ID in Whisper
-                    0 => Language::English, // English language ID
-                    _ => Language::Unknown,
-                };
-            }
+        // Extract segments (timestamps are in 10 ms units)
+        let n_segments = state.full_n_segments().context("Failed to read segments")?;
+        let mut segments = Vec::with_capacity(n_segments as usize);
+        for i in 0..n_segments {
+            segments.push(Segment {
+                text: state
+                    .full_get_segment_text(i)
+                    .context("Failed to read segment text")?
+                    .trim()
+                    .to_string(),
+                start: state.full_get_segment_t0(i).unwrap_or(0) as f32 / 100.0,
+                end: state.full_get_segment_t1(i).unwrap_or(0) as f32 / 100.0,
+            });
         }
 
-        let text = text.trim().to_string();
-        log::info!("Transcription complete: {} chars, detected language: {:?}", text.len(), detected_lang);
+        let detection = match detection.language {
+            Language::Unknown => Detection {
+                language: state
+                    .full_lang_id_from_state()
+                    .map_or(Language::Unknown, Language::from_id),
+                confidence: None,
+            },
+            _ => detection,
+        };
 
-        Ok((text, detected_lang))
+        log::info!(
+            "Transcribed {} segments, language: {} (confidence {:?})",
+            segments.len(),
+            detection.language.code(),
+            detection.confidence
+        );
+        Ok((segments, detection))
     }
 
     /// Get model path
@@ -110,34 +311,25 @@ impl Transcriber {
     }
 }
 
+/// Probabilities of every language for the first 30 s of `samples`
+fn detect_language(
+    state: &mut whisper_rs::WhisperState,
+    samples: &[f32],
+    threads: usize,
+) -> Result<Detection> {
+    state
+        .pcm_to_mel(samples, threads)
+        .context("Failed to compute spectrogram")?;
+    let probabilities = state
+        .lang_detect(0, threads)
+        .context("Failed to detect language")?;
+    Ok(Detection::from_probabilities(&probabilities))
+}
+
 /// Convert audio samples to 16kHz mono (Whisper requirement)
 pub fn resample_to_16k_mono(samples: &[f32], original_sample_rate: u32, channels: u16) -> Vec<f32> {
-    let mono: Vec<f32> = if channels > 1 {
-        // Convert to mono by averaging channels
-        samples
-            .chunks(channels as usize)
-            .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
-            .collect()
-    } else {
-        samples.to_vec()
-    };
-
-    if original_sample_rate == 16000 {
-        return mono;
-    }
-
-    // Simple linear resampling
-    let ratio = 16000.0 / original_sample_rate as f64;
-    let new_len = (mono.len() as f64 * ratio) as usize;
-    let mut resampled = Vec::with_capacity(new_len);
-
-    for i in 0..new_len {
-        let src_idx = (i as f64 / ratio) as usize;
-        let sample = mono.get(src_idx).copied().unwrap_or(0.0);
-        resampled.push(sample);
-    }
-
-    resampled
+    let mono = resample::downmix_to_mono(samples, channels);
+    resample::resample(&mono, original_sample_rate, WHISPER_SAMPLE_RATE)
 }
 
 #[cfg(test)]
@@ -146,8 +338,113 @@ mod tests {
 
     #[test]
     fn test_language_conversion() {
-        assert_eq!(<&str>::from(Language::Polish), "pl");
-        assert_eq!(<&str>::from(Language::English), "en");
+        assert_eq!(<&str>::from(Language::from_code("pl")), "pl");
+        assert_eq!(<&str>::from(Language::from_code("en")), "en");
         assert_eq!(<&str>::from(Language::Unknown), "auto");
     }
+
+    #[test]
+    fn test_languages_follow_whisper_table() {
+        // Ids from whisper.cpp's language table
+        assert_eq!(Language::from_id(0), Language::Known("en"));
+        assert_eq!(Language::from_id(10), Language::Known("pl"));
+        assert_eq!(Language::from_id(-1), Language::Unknown);
+        assert_eq!(Language::from_code("uk"), Language::Known("uk"));
+        assert_eq!(Language::from_code("xx"), Language::Unknown);
+        assert_eq!(Language::from_code("auto"), Language::Unknown);
+
+        let all = Language::all();
+        assert_eq!(all.len(), whisper_rs::get_lang_max_id() as usize + 1);
+        assert!(all.len() >= 99);
+        assert!(all.contains(&Language::Known("yue")));
+    }
+
+    #[test]
+    fn test_detection_picks_most_likely_language() {
+        let mut probabilities = vec![0.01; whisper_rs::get_lang_max_id() as usize + 1];
+        probabilities[10] = 0.9;
+        let detection = Detection::from_probabilities(&probabilities);
+        assert_eq!(detection.language, Language::Known("pl"));
+        assert_eq!(detection.confidence, Some(0.9));
+
+        assert_eq!(Detection::from_probabilities(&[]), Detection::UNKNOWN);
+        assert_eq!(
+            serde_json::to_value(detection).unwrap(),
+            serde_json::json!({ "language": "pl", "confidence": 0.9f32 })
+        );
+    }
+
+    #[test]
+    fn test_transcription_options_validation() {
+        assert!(TranscriptionOptions::default().validate().is_ok());
+
+        let forced = TranscriptionOptions {
+            language: Some("pl".to_string()),
+            beam_size: 5,
+            threads: Some(2),
+            ..TranscriptionOptions::default()
+        };
+        assert!(forced.validate().is_ok());
+        assert_eq!(forced.thread_count(), 2);
+
+        for invalid in [
+            TranscriptionOptions {
+                language: Some("klingon".to_string()),
+                ..TranscriptionOptions::default()
+            },
+            TranscriptionOptions {
+                beam_size: 0,
+                ..TranscriptionOptions::default()
+            },
+            TranscriptionOptions {
+                beam_size: 9,
+                ..TranscriptionOptions::default()
+            },
+            TranscriptionOptions {
+                threads: Some(0),
+                ..TranscriptionOptions::default()
+            },
+            TranscriptionOptions {
+                temperature_increment: -0.1,
+                ..TranscriptionOptions::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_default_threads_follow_cpu_cores() {
+        let threads = TranscriptionOptions::default().thread_count();
+        assert!((1..=MAX_DEFAULT_THREADS).contains(&threads));
+    }
+
+    #[test]
+    fn test_blank_prompt_is_ignored() {
+        let options = TranscriptionOptions {
+            initial_prompt: Some("  ".to_string()),
+            ..TranscriptionOptions::default()
+        };
+        assert_eq!(options.prompt(), None);
+
+        let options = TranscriptionOptions {
+            initial_prompt: Some(" ValleyFlow, Tauri ".to_string()),
+            ..TranscriptionOptions::default()
+        };
+        assert_eq!(options.prompt(), Some("ValleyFlow, Tauri"));
+    }
+
+    #[test]
+    fn test_resample_stereo_44k() {
+        let frames = 44100;
+        let stereo: Vec<f32> = (0..frames)
+            .flat_map(|i| {
+                let s = (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin();
+                [s, s]
+            })
+            .collect();
+
+        let resampled = resample_to_16k_mono(&stereo, 44100, 2);
+        assert_eq!(resampled.len(), 16000);
+    }
 }
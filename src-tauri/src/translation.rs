@@ -0,0 +1,85 @@
+//! Translation mode: dictate in one language, paste in another.
+//!
+//! Whisper can only translate into English, and does that in the same pass as
+//! the transcription. Every other target is left to the post-processing step.
+
+use crate::transcription::Language;
+
+/// Where the translation into the target language happens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Translation {
+    /// Whisper transcribes straight into English
+    Whisper,
+    /// The post-processor translates into this language
+    PostProcessing(Language),
+}
+
+impl Translation {
+    /// How to translate into `target` (a language code); `None` for an unknown code
+    pub fn to(target: &str) -> Option<Self> {
+        match Language::from_code(target) {
+            Language::Known("en") => Some(Translation::Whisper),
+            Language::Unknown => None,
+            language => Some(Translation::PostProcessing(language)),
+        }
+    }
+
+    /// Whether Whisper should translate while transcribing
+    pub fn whisper_translates(&self) -> bool {
+        *self == Translation::Whisper
+    }
+
+    /// Language of the transcript Whisper produces for speech in `spoken`
+    pub fn transcript_language(&self, spoken: Language) -> Language {
+        match self {
+            Translation::Whisper => Language::Known("en"),
+            Translation::PostProcessing(_) => spoken,
+        }
+    }
+
+    /// The language the post-processor still has to translate a transcript in
+    /// `spoken` into, if any
+    pub fn post_processing_target(&self, spoken: Language) -> Option<Language> {
+        match self {
+            Translation::PostProcessing(target) if *target != spoken => Some(*target),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLISH: Language = Language::Known("pl");
+    const ENGLISH: Language = Language::Known("en");
+    const GERMAN: Language = Language::Known("de");
+
+    #[test]
+    fn test_english_is_translated_by_whisper() {
+        let translation = Translation::to("en").unwrap();
+        assert!(translation.whisper_translates());
+        assert_eq!(translation.transcript_language(POLISH), ENGLISH);
+        assert_eq!(translation.post_processing_target(ENGLISH), None);
+    }
+
+    #[test]
+    fn test_other_targets_are_left_to_post_processing() {
+        let translation = Translation::to("de").unwrap();
+        assert!(!translation.whisper_translates());
+        assert_eq!(translation.transcript_language(POLISH), POLISH);
+        assert_eq!(translation.post_processing_target(POLISH), Some(GERMAN));
+        assert_eq!(
+            translation.post_processing_target(Language::Unknown),
+            Some(GERMAN)
+        );
+        // Already in the target language
+        assert_eq!(translation.post_processing_target(GERMAN), None);
+    }
+
+    #[test]
+    fn test_unknown_target() {
+        assert_eq!(Translation::to("xx"), None);
+        assert_eq!(Translation::to("auto"), None);
+    }
+}
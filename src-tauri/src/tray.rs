@@ -1,16 +1,22 @@
+use crate::commands;
+use crate::settings::SettingsStore;
+use crate::templates::Templates;
+use std::sync::{Arc, Mutex};
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, Submenu},
     tray::TrayIconBuilder,
-    App, Manager,
+    App, AppHandle, Manager, Wry,
 };
 
-pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
-    let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+const TRAY_ID: &str = "main";
 
-    let menu = Menu::with_items(app, &[&settings_item, &quit_item])?;
+/// Menu ids of templates are this prefix followed by the template id
+const TEMPLATE_PREFIX: &str = "template:";
+
+pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
+    let menu = build_menu(app.handle())?;
 
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(app.default_window_icon().unwrap().clone())
         .menu(&menu)
         .on_menu_event(|app, event| match event.id.as_ref() {
@@ -23,10 +29,67 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
             "quit" => {
                 app.exit(0);
             }
-            _ => {}
+            id => {
+                if let Some(template) = id.strip_prefix(TEMPLATE_PREFIX) {
+                    if let Err(e) = commands::activate_template(app, template) {
+                        log::warn!("Failed to switch to template {}: {}", template, e);
+                    }
+                }
+            }
         })
         .build(app)?;
 
     log::info!("System tray initialized");
     Ok(())
 }
+
+/// Rebuild the menu after a template was added, removed or selected
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let result = build_menu(app).and_then(|menu| tray.set_menu(Some(menu)));
+    if let Err(e) = result {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+}
+
+fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let active = app
+        .state::<Arc<Mutex<SettingsStore>>>()
+        .lock()
+        .unwrap()
+        .get()
+        .template
+        .clone();
+    let templates = app
+        .state::<Arc<Mutex<Templates>>>()
+        .lock()
+        .unwrap()
+        .all()
+        .to_vec();
+
+    let template_items = templates
+        .iter()
+        .map(|template| {
+            CheckMenuItem::with_id(
+                app,
+                format!("{}{}", TEMPLATE_PREFIX, template.id),
+                &template.name,
+                true,
+                template.id == active,
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let template_items: Vec<&dyn IsMenuItem<Wry>> = template_items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<Wry>)
+        .collect();
+    let template_menu = Submenu::with_items(app, "Output style", true, &template_items)?;
+
+    let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(app, &[&template_menu, &settings_item, &quit_item])
+}
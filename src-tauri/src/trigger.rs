@@ -0,0 +1,223 @@
+//! Turns raw shortcut events into recording start/stop actions.
+//!
+//! The global shortcut fires on both press and release, and keyboards may
+//! bounce or auto-repeat, so every event goes through [`HotkeyTrigger`]
+//! instead of toggling the recording directly.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri_plugin_global_shortcut::ShortcutState;
+
+/// Presses this soon after the previous accepted event are treated as bounce
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingMode {
+    /// Press once to start, again to stop
+    #[default]
+    Toggle,
+    /// Record while the shortcut is held down
+    PushToTalk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Start,
+    Stop,
+    Ignore,
+}
+
+#[derive(Debug, Default)]
+pub struct HotkeyTrigger {
+    mode: RecordingMode,
+    /// A press was accepted and its release has not arrived yet
+    held: bool,
+    last_event: Option<Instant>,
+}
+
+impl HotkeyTrigger {
+    pub fn new(mode: RecordingMode) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+
+    pub fn mode(&self) -> RecordingMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RecordingMode) {
+        if mode != self.mode {
+            *self = Self::new(mode);
+        }
+    }
+
+    /// Decide what a shortcut event means given whether a recording is running
+    pub fn on_event(
+        &mut self,
+        state: ShortcutState,
+        now: Instant,
+        recording: bool,
+    ) -> HotkeyAction {
+        match state {
+            ShortcutState::Pressed => {
+                // Auto-repeat, or a second press event for the same keystroke
+                if self.held {
+                    return HotkeyAction::Ignore;
+                }
+                if let Some(last) = self.last_event {
+                    if now.duration_since(last) < DEBOUNCE {
+                        return HotkeyAction::Ignore;
+                    }
+                }
+
+                self.held = true;
+                self.last_event = Some(now);
+                match (self.mode, recording) {
+                    (RecordingMode::Toggle, true) => HotkeyAction::Stop,
+                    (_, false) => HotkeyAction::Start,
+                    // Already recording (e.g. started from the UI); the release will stop it
+                    (RecordingMode::PushToTalk, true) => HotkeyAction::Ignore,
+                }
+            }
+            ShortcutState::Released => {
+                // Release of a press that was ignored
+                if !self.held {
+                    return HotkeyAction::Ignore;
+                }
+
+                self.held = false;
+                self.last_event = Some(now);
+                match (self.mode, recording) {
+                    (RecordingMode::PushToTalk, true) => HotkeyAction::Stop,
+                    _ => HotkeyAction::Ignore,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ShortcutState::{Pressed, Released};
+
+    /// Feeds events at the given millisecond offsets, tracking the recording state
+    struct Harness {
+        trigger: HotkeyTrigger,
+        start: Instant,
+        recording: bool,
+    }
+
+    impl Harness {
+        fn new(mode: RecordingMode) -> Self {
+            Self {
+                trigger: HotkeyTrigger::new(mode),
+                start: Instant::now(),
+                recording: false,
+            }
+        }
+
+        fn event(&mut self, state: ShortcutState, at_ms: u64) -> HotkeyAction {
+            let now = self.start + Duration::from_millis(at_ms);
+            let action = self.trigger.on_event(state, now, self.recording);
+            match action {
+                HotkeyAction::Start => self.recording = true,
+                HotkeyAction::Stop => self.recording = false,
+                HotkeyAction::Ignore => {}
+            }
+            action
+        }
+    }
+
+    #[test]
+    fn test_toggle_acts_on_press_only() {
+        let mut h = Harness::new(RecordingMode::Toggle);
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+        assert_eq!(h.event(Released, 100), HotkeyAction::Ignore);
+        assert!(h.recording);
+
+        assert_eq!(h.event(Pressed, 2000), HotkeyAction::Stop);
+        assert_eq!(h.event(Released, 2100), HotkeyAction::Ignore);
+        assert!(!h.recording);
+    }
+
+    #[test]
+    fn test_push_to_talk_records_while_held() {
+        let mut h = Harness::new(RecordingMode::PushToTalk);
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+        assert_eq!(h.event(Released, 3000), HotkeyAction::Stop);
+        assert_eq!(h.event(Pressed, 5000), HotkeyAction::Start);
+        assert_eq!(h.event(Released, 6000), HotkeyAction::Stop);
+    }
+
+    #[test]
+    fn test_auto_repeat_is_ignored() {
+        for mode in [RecordingMode::Toggle, RecordingMode::PushToTalk] {
+            let mut h = Harness::new(mode);
+            assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+            for t in (500..1500).step_by(30) {
+                assert_eq!(h.event(Pressed, t), HotkeyAction::Ignore);
+            }
+            assert!(h.recording);
+        }
+    }
+
+    #[test]
+    fn test_bounce_is_debounced() {
+        let mut h = Harness::new(RecordingMode::Toggle);
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+        assert_eq!(h.event(Released, 40), HotkeyAction::Ignore);
+        // Contact bounce right after the release
+        assert_eq!(h.event(Pressed, 60), HotkeyAction::Ignore);
+        assert_eq!(h.event(Released, 70), HotkeyAction::Ignore);
+        assert!(h.recording);
+
+        assert_eq!(h.event(Pressed, 400), HotkeyAction::Stop);
+    }
+
+    #[test]
+    fn test_quick_push_to_talk_tap_still_stops() {
+        let mut h = Harness::new(RecordingMode::PushToTalk);
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+        assert_eq!(h.event(Released, 20), HotkeyAction::Stop);
+        assert!(!h.recording);
+    }
+
+    #[test]
+    fn test_push_to_talk_ignores_recording_started_elsewhere() {
+        let mut h = Harness::new(RecordingMode::PushToTalk);
+        h.recording = true;
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Ignore);
+        assert_eq!(h.event(Released, 500), HotkeyAction::Stop);
+    }
+
+    #[test]
+    fn test_release_after_external_stop() {
+        // Recording auto-stopped while the key was still held
+        let mut h = Harness::new(RecordingMode::PushToTalk);
+        assert_eq!(h.event(Pressed, 0), HotkeyAction::Start);
+        h.recording = false;
+        assert_eq!(h.event(Released, 1000), HotkeyAction::Ignore);
+        assert_eq!(h.event(Pressed, 2000), HotkeyAction::Start);
+    }
+
+    #[test]
+    fn test_mode_change_resets_state() {
+        let mut trigger = HotkeyTrigger::new(RecordingMode::Toggle);
+        let now = Instant::now();
+        trigger.on_event(Pressed, now, false);
+        trigger.set_mode(RecordingMode::PushToTalk);
+        assert_eq!(trigger.on_event(Pressed, now, false), HotkeyAction::Start);
+    }
+
+    #[test]
+    fn test_mode_serialization() {
+        assert_eq!(
+            serde_json::to_value(RecordingMode::PushToTalk).unwrap(),
+            serde_json::json!("pushToTalk")
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Energy-based voice activity detection.
+//!
+//! Audio is split into short frames and each frame's RMS level is compared
+//! against an estimate of the background noise floor. Used to trim leading and
+//! trailing silence before Whisper sees the audio (silence is where it tends to
+//! hallucinate) and to stop a recording once the speaker has gone quiet.
+
+use std::ops::Range;
+
+/// Analysis frame length
+const FRAME_MS: u32 = 30;
+
+/// A frame counts as speech when it is this far above the noise floor
+const SPEECH_MARGIN_DB: f32 = 12.0;
+
+/// Frames quieter than this are never speech, whatever the noise floor
+const MIN_SPEECH_DB: f32 = -50.0;
+
+/// Noise floor estimates above this are assumed to be speech, not background
+const MAX_NOISE_FLOOR_DB: f32 = -40.0;
+
+/// Audio kept around detected speech so word onsets and tails are not clipped
+const PADDING_MS: u32 = 250;
+
+/// How fast the tracked noise floor may rise, per frame
+const FLOOR_RISE_DB: f32 = 0.05;
+
+fn frame_len(sample_rate: u32) -> usize {
+    (sample_rate * FRAME_MS / 1000).max(1) as usize
+}
+
+/// RMS level of a frame in dBFS
+fn level_db(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * mean_square.max(1e-12).log10()
+}
+
+fn speech_threshold(noise_floor_db: f32) -> f32 {
+    (noise_floor_db.min(MAX_NOISE_FLOOR_DB) + SPEECH_MARGIN_DB).max(MIN_SPEECH_DB)
+}
+
+/// Sample range containing speech (padded), or `None` if the audio is silent
+pub fn speech_bounds(samples: &[f32], sample_rate: u32) -> Option<Range<usize>> {
+    let frame_len = frame_len(sample_rate);
+    let levels: Vec<f32> = samples.chunks(frame_len).map(level_db).collect();
+    if levels.is_empty() {
+        return None;
+    }
+
+    // The quietest tenth of the recording approximates the background noise
+    let mut sorted = levels.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold = speech_threshold(noise_floor);
+
+    let first = levels.iter().position(|&l| l > threshold)?;
+    let last = levels.iter().rposition(|&l| l > threshold)?;
+
+    let padding = (sample_rate * PADDING_MS / 1000) as usize;
+    let start = (first * frame_len).saturating_sub(padding);
+    let end = ((last + 1) * frame_len + padding).min(samples.len());
+    Some(start..end)
+}
+
+/// Drop leading and trailing silence; `None` when there is no speech at all
+pub fn trim_silence(samples: &[f32], sample_rate: u32) -> Option<&[f32]> {
+    speech_bounds(samples, sample_rate).map(|range| &samples[range])
+}
+
+/// Streaming detector that tracks how long the speaker has been quiet
+pub struct SilenceDetector {
+    sample_rate: u32,
+    frame: Vec<f32>,
+    noise_floor_db: Option<f32>,
+    heard_speech: bool,
+    silent_frames: usize,
+}
+
+impl SilenceDetector {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            frame: Vec::with_capacity(frame_len(sample_rate)),
+            noise_floor_db: None,
+            heard_speech: false,
+            silent_frames: 0,
+        }
+    }
+
+    /// Feed mono samples
+    pub fn feed(&mut self, samples: &[f32]) {
+        let frame_len = frame_len(self.sample_rate);
+        for &sample in samples {
+            self.frame.push(sample);
+            if self.frame.len() == frame_len {
+                let level = level_db(&self.frame);
+                self.frame.clear();
+                self.push_level(level);
+            }
+        }
+    }
+
+    fn push_level(&mut self, level: f32) {
+        let floor = match self.noise_floor_db {
+            Some(floor) if level >= floor => floor + FLOOR_RISE_DB,
+            _ => level,
+        };
+        self.noise_floor_db = Some(floor);
+
+        if level > speech_threshold(floor) {
+            self.heard_speech = true;
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames += 1;
+        }
+    }
+
+    /// Whether any speech has been detected so far
+    pub fn heard_speech(&self) -> bool {
+        self.heard_speech
+    }
+
+    /// Length of the silence since the last speech frame, in seconds
+    pub fn trailing_silence_secs(&self) -> f32 {
+        (self.silent_frames as u32 * FRAME_MS) as f32 / 1000.0
+    }
+
+    /// True once speech was heard and has been followed by `secs` of silence
+    pub fn should_stop(&self, secs: f32) -> bool {
+        self.heard_speech && self.trailing_silence_secs() >= secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn noise(seconds: f32, amplitude: f32) -> Vec<f32> {
+        // Deterministic pseudo-random noise
+        let mut state = 0x1234_5678u32;
+        (0..(RATE as f32 * seconds) as usize)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    fn tone(seconds: f32) -> Vec<f32> {
+        (0..(RATE as f32 * seconds) as usize)
+            .map(|i| (2.0 * std::f32::consts::PI * 220.0 * i as f32 / RATE as f32).sin() * 0.3)
+            .collect()
+    }
+
+    #[test]
+    fn test_silence_has_no_speech() {
+        assert_eq!(speech_bounds(&noise(2.0, 0.001), RATE), None);
+        assert_eq!(speech_bounds(&[], RATE), None);
+    }
+
+    #[test]
+    fn test_trims_leading_and_trailing_silence() {
+        let mut samples = noise(1.0, 0.002);
+        samples.extend(tone(1.0));
+        samples.extend(noise(2.0, 0.002));
+
+        let range = speech_bounds(&samples, RATE).unwrap();
+        let padding = (RATE * PADDING_MS / 1000) as usize;
+        let frame = frame_len(RATE);
+
+        assert!(range.start <= RATE as usize - padding);
+        assert!(range.start + padding + frame >= RATE as usize);
+        assert!(range.end >= 2 * RATE as usize + padding - frame);
+        assert!(range.end <= 2 * RATE as usize + padding + frame);
+    }
+
+    #[test]
+    fn test_speech_only_is_kept_whole() {
+        let samples = tone(1.0);
+        let trimmed = trim_silence(&samples, RATE).unwrap();
+        assert_eq!(trimmed.len(), samples.len());
+    }
+
+    #[test]
+    fn test_detector_waits_for_speech() {
+        let mut detector = SilenceDetector::new(RATE);
+        detector.feed(&noise(3.0, 0.002));
+        assert!(!detector.heard_speech());
+        assert!(!detector.should_stop(1.0));
+    }
+
+    #[test]
+    fn test_detector_tracks_trailing_silence() {
+        let mut detector = SilenceDetector::new(RATE);
+        detector.feed(&noise(0.5, 0.002));
+        detector.feed(&tone(1.0));
+        assert!(detector.heard_speech());
+        assert!(detector.trailing_silence_secs() < 0.1);
+
+        detector.feed(&noise(1.5, 0.002));
+        assert!(detector.should_stop(1.4));
+        assert!(!detector.should_stop(2.0));
+
+        // Speaking again resets the countdown
+        detector.feed(&tone(0.3));
+        assert!(!detector.should_stop(1.0));
+    }
+}
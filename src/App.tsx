@@ -1,12 +1,35 @@
-import { useState, useEffect } from 'react'
+import { useState, useEffect, useRef } from 'react'
 import { AppProvider, useApp } from './store'
 import {
   RecordingOverlay,
   SettingsWindow,
   HistoryWindow,
   WelcomeWizard,
+  type AutoStopReason,
 } from './components'
 
+type PipelineState =
+  | { state: 'idle' | 'recording' | 'transcribing' | 'postProcessing' | 'delivering' }
+  | { state: 'error'; message: string }
+
+type Detection = { language: string; confidence: number | null }
+
+type LimitWarning = { remainingSecs: number }
+
+// "pl" -> "Polish" in the interface language
+function languageName(code: string, uiLanguage: 'pl' | 'en'): string {
+  try {
+    return new Intl.DisplayNames([uiLanguage], { type: 'language' }).of(code) ?? code
+  } catch {
+    return code
+  }
+}
+
+type ModelStatus = { path: string } & (
+  | { state: 'notLoaded' | 'missing' | 'loading' | 'loaded' }
+  | { state: 'failed'; message: string }
+)
+
 function AppContent() {
   const {
     settings,
@@ -22,6 +45,14 @@ function AppContent() {
   const [showSettings, setShowSettings] = useState(false)
   const [showHistory, setShowHistory] = useState(false)
   const [showWizard, setShowWizard] = useState(!settings.hasCompletedWizard)
+  const [partialText, setPartialText] = useState('')
+  const [processedText, setProcessedText] = useState('')
+  const [progress, setProgress] = useState<number | null>(null)
+  const [modelStatus, setModelStatus] = useState<ModelStatus | null>(null)
+  const [detection, setDetection] = useState<Detection | null>(null)
+  const [secondsLeft, setSecondsLeft] = useState<number | null>(null)
+  const [autoStopReason, setAutoStopReason] = useState<AutoStopReason | null>(null)
+  const detectionRef = useRef<Detection | null>(null)
 
   // Listen for Tauri events
   useEffect(() => {
@@ -29,40 +60,115 @@ function AppContent() {
 
     const unlisteners: (() => void)[] = []
 
-    window.__TAURI__.event.listen<boolean>('recording-state', (event) => {
-      setRecording(event.payload)
-      if (event.payload) {
+    window.__TAURI__.event.listen<PipelineState>('pipeline-state', (event) => {
+      const { state } = event.payload
+      setRecording(state === 'recording')
+      setProcessing(state === 'transcribing' || state === 'postProcessing' || state === 'delivering')
+      if (state === 'recording') {
         setRecordingTime(0)
+        setPartialText('')
+        setSecondsLeft(null)
+        setAutoStopReason(null)
+      }
+      if (state !== 'transcribing') {
+        setProgress(null)
+      }
+      if (state === 'recording' || state === 'idle' || state === 'error') {
+        setProcessedText('')
+      }
+      if (event.payload.state === 'error') {
+        console.error('Pipeline error:', event.payload.message)
       }
     }).then((unlisten) => unlisteners.push(unlisten))
 
-    window.__TAURI__.event.listen<boolean>('recording-processing', (event) => {
-      setProcessing(event.payload)
+    window.__TAURI__.event.listen<{ stable: string; tentative: string }>('transcription-partial', (event) => {
+      const { stable, tentative } = event.payload
+      setPartialText([stable, tentative].filter(Boolean).join(' '))
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    // The language model's output so far, while it streams in
+    window.__TAURI__.event.listen<string>('postprocess-partial', (event) => {
+      setProcessedText(event.payload)
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    window.__TAURI__.event.listen<number>('transcription-progress', (event) => {
+      setProgress(event.payload)
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    window.__TAURI__.event.listen('recording-cancelled', () => {
+      setRecordingTime(0)
+      setPartialText('')
+      setSecondsLeft(null)
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    // The length limit is close; count down from what the backend reports
+    window.__TAURI__.event.listen<LimitWarning>('recording-limit-warning', (event) => {
+      setSecondsLeft(event.payload.remainingSecs)
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    window.__TAURI__.event.listen<AutoStopReason>('recording-auto-stopped', (event) => {
+      setSecondsLeft(null)
+      setAutoStopReason(event.payload)
+    }).then((unlisten) => unlisteners.push(unlisten))
+
+    window.__TAURI__.event.listen<Detection>('transcription-language', (event) => {
+      detectionRef.current = event.payload
+      setDetection(event.payload)
     }).then((unlisten) => unlisteners.push(unlisten))
 
     window.__TAURI__.event.listen<string>('transcription-complete', (event) => {
       addToHistory({
         text: event.payload,
         rawText: event.payload, // TODO: get raw text separately
-        language: settings.language,
+        language: detectionRef.current?.language ?? 'auto',
       })
     }).then((unlisten) => unlisteners.push(unlisten))
 
     return () => {
       unlisteners.forEach((unlisten) => unlisten())
     }
-  }, [setRecording, setProcessing, setRecordingTime, addToHistory, settings.language])
+  }, [setRecording, setProcessing, setRecordingTime, addToHistory])
+
+  // Whisper model status; the model loads on first use and may be missing
+  useEffect(() => {
+    if (!window.__TAURI__) return
+
+    let unlisten: (() => void) | undefined
+    window.__TAURI__.core
+      .invoke<ModelStatus>('get_model_status')
+      .then(setModelStatus)
+      .catch(console.error)
+    window.__TAURI__.event
+      .listen<ModelStatus>('model-status', (event) => setModelStatus(event.payload))
+      .then((u) => (unlisten = u))
+    return () => unlisten?.()
+  }, [])
+
+  // Keep the auto-stop notice up for a moment after the overlay would close
+  useEffect(() => {
+    if (!autoStopReason) return
+    const timer = setTimeout(() => setAutoStopReason(null), 4000)
+    return () => clearTimeout(timer)
+  }, [autoStopReason])
+
+  const handleRetryModel = () => {
+    window.__TAURI__?.core
+      .invoke<ModelStatus>('load_model')
+      .then(setModelStatus)
+      .catch((e: unknown) => console.error('Failed to load model:', e))
+  }
 
   // Recording timer
   useEffect(() => {
     let interval: ReturnType<typeof setInterval>
     if (isRecording) {
       interval = setInterval(() => {
-        setRecordingTime((t) => (t >= 299 ? t : t + 1))
+        setRecordingTime((t) => Math.min(t + 1, settings.maxRecordingSecs))
+        setSecondsLeft((s) => (s === null ? null : Math.max(s - 1, 0)))
       }, 1000)
     }
     return () => clearInterval(interval)
-  }, [isRecording, setRecordingTime])
+  }, [isRecording, setRecordingTime, settings.maxRecordingSecs])
 
   // Listen for tray menu events
   useEffect(() => {
@@ -86,7 +192,11 @@ function AppContent() {
   const handleCancelRecording = () => {
     setRecording(false)
     setRecordingTime(0)
-    // In production, this would notify the Rust backend
+    if (window.__TAURI__) {
+      window.__TAURI__.core.invoke('cancel_recording').catch((e: unknown) => {
+        console.error('Failed to cancel recording:', e)
+      })
+    }
   }
 
   const handleWizardComplete = () => {
@@ -110,6 +220,28 @@ function AppContent() {
       <div className="main-content">
         <h1>ValleyFlow</h1>
 
+        {modelStatus && (modelStatus.state === 'missing' || modelStatus.state === 'failed') && (
+          <div className="model-banner">
+            <p>
+              {modelStatus.state === 'missing'
+                ? settings.language === 'pl'
+                  ? 'Brak modelu Whisper. Pobierz go w ustawieniach lub wskaż plik modelu.'
+                  : 'No Whisper model found. Download one in Settings or choose a model file.'
+                : `${settings.language === 'pl' ? 'Nie udało się wczytać modelu' : 'Failed to load the model'}: ${modelStatus.message}`}
+            </p>
+            <div className="model-banner-actions">
+              <button onClick={() => setShowSettings(true)}>
+                {settings.language === 'pl' ? 'Otwórz ustawienia' : 'Open settings'}
+              </button>
+              {modelStatus.state === 'failed' && (
+                <button onClick={handleRetryModel}>
+                  {settings.language === 'pl' ? 'Spróbuj ponownie' : 'Retry'}
+                </button>
+              )}
+            </div>
+          </div>
+        )}
+
         <div className={`status-indicator ${isRecording ? 'recording' : isProcessing ? 'processing' : 'idle'}`}>
           {isRecording && (
             <>
@@ -121,20 +253,37 @@ function AppContent() {
           {isProcessing && (
             <>
               <div className="spinner-small"></div>
-              <span>{settings.language === 'pl' ? 'Przetwarzanie' : 'Processing'}...</span>
+              <span>
+                {settings.language === 'pl' ? 'Przetwarzanie' : 'Processing'}...
+                {progress !== null && ` ${progress}%`}
+              </span>
             </>
           )}
-          {!isRecording && !isProcessing && (
+          {!isRecording && !isProcessing && modelStatus?.state === 'loading' && (
+            <>
+              <div className="spinner-small"></div>
+              <span>{settings.language === 'pl' ? 'Wczytywanie modelu' : 'Loading model'}...</span>
+            </>
+          )}
+          {!isRecording && !isProcessing && modelStatus?.state !== 'loading' && (
             <span>{settings.language === 'pl' ? 'Gotowy' : 'Ready'}</span>
           )}
         </div>
 
         <p className="hint">
           {settings.language === 'pl'
-            ? 'Naciśnij Ctrl+Shift+Space aby rozpocząć nagrywanie'
-            : 'Press Ctrl+Shift+Space to start recording'}
+            ? `Naciśnij ${settings.hotkey} aby rozpocząć nagrywanie`
+            : `Press ${settings.hotkey} to start recording`}
         </p>
 
+        {detection && detection.language !== 'auto' && !isRecording && (
+          <p className="hint">
+            {settings.language === 'pl' ? 'Wykryty język' : 'Detected language'}:{' '}
+            {languageName(detection.language, settings.language)}
+            {detection.confidence !== null && ` (${Math.round(detection.confidence * 100)}%)`}
+          </p>
+        )}
+
         <div className="quick-actions">
           <button onClick={() => setShowSettings(true)}>
             {settings.language === 'pl' ? 'Ustawienia' : 'Settings'}
@@ -146,7 +295,13 @@ function AppContent() {
       </div>
 
       {/* Recording Overlay */}
-      <RecordingOverlay onCancel={handleCancelRecording} />
+      <RecordingOverlay
+        onCancel={handleCancelRecording}
+        partialText={partialText}
+        processedText={processedText}
+        secondsLeft={secondsLeft}
+        autoStopReason={autoStopReason}
+      />
 
       {/* Settings Modal */}
       {showSettings && (
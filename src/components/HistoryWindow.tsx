@@ -118,8 +118,8 @@ export function HistoryWindow({ onClose }: HistoryWindowProps) {
             </p>
             <p className="hint">
               {settings.language === 'pl'
-                ? 'Naciśnij Ctrl+Shift+Space aby rozpocząć'
-                : 'Press Ctrl+Shift+Space to start'}
+                ? `Naciśnij ${settings.hotkey} aby rozpocząć`
+                : `Press ${settings.hotkey} to start`}
             </p>
           </div>
         ) : (
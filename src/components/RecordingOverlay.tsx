@@ -1,22 +1,40 @@
 import { useApp } from '../store'
 import { useEffect, useState } from 'react'
 
+/** Payload of `recording-auto-stopped` */
+export type AutoStopReason = 'max-duration' | 'silence'
+
 interface RecordingOverlayProps {
   onCancel: () => void
+  partialText?: string
+  /** Post-processed text streaming in after the recording stopped */
+  processedText?: string
+  /** Seconds until the length limit stops the recording, once it is close */
+  secondsLeft?: number | null
+  /** Why the recording stopped on its own, if it did */
+  autoStopReason?: AutoStopReason | null
 }
 
-export function RecordingOverlay({ onCancel }: RecordingOverlayProps) {
+export function RecordingOverlay({
+  onCancel,
+  partialText,
+  processedText,
+  secondsLeft,
+  autoStopReason,
+}: RecordingOverlayProps) {
   const { isRecording, recordingTime, settings } = useApp()
   const [isVisible, setIsVisible] = useState(false)
+  const isStreaming = !isRecording && !!processedText
+  const isActive = isRecording || isStreaming || !!autoStopReason
 
   useEffect(() => {
-    if (isRecording) {
+    if (isActive) {
       setIsVisible(true)
     } else {
       const timer = setTimeout(() => setIsVisible(false), 300)
       return () => clearTimeout(timer)
     }
-  }, [isRecording])
+  }, [isActive])
 
   if (!isVisible) return null
 
@@ -30,19 +48,53 @@ export function RecordingOverlay({ onCancel }: RecordingOverlayProps) {
     onCancel()
   }
 
+  const autoStopText =
+    autoStopReason === 'silence'
+      ? settings.language === 'pl'
+        ? 'Nagrywanie zatrzymane po ciszy'
+        : 'Recording stopped after silence'
+      : settings.language === 'pl'
+        ? 'Osiągnięto limit długości nagrania'
+        : 'Recording length limit reached'
+
   return (
-    <div className={`overlay ${isRecording ? 'visible' : 'hiding'}`}>
+    <div className={`overlay ${isActive ? 'visible' : 'hiding'}`}>
       <div className="overlay-content">
-        <div className="recording-indicator">
-          <span className="recording-dot"></span>
-          <span className="recording-text">
-            {settings.language === 'pl' ? 'Nagrywanie' : 'Recording'}...
-          </span>
-        </div>
-        <span className="timer">{formatTime(recordingTime)}</span>
-        <button className="cancel-btn" onClick={handleCancel}>
-          {settings.language === 'pl' ? 'Anuluj' : 'Cancel'}
-        </button>
+        {isStreaming ? (
+          <>
+            <div className="recording-indicator">
+              <div className="spinner-small"></div>
+              <span className="recording-text">
+                {settings.language === 'pl' ? 'Porządkowanie' : 'Cleaning up'}...
+              </span>
+            </div>
+            {autoStopReason && <p className="limit-notice">{autoStopText}</p>}
+            <p className="partial-text processed">{processedText}</p>
+          </>
+        ) : !isRecording && autoStopReason ? (
+          <p className="limit-notice">{autoStopText}</p>
+        ) : (
+          <>
+            <div className="recording-indicator">
+              <span className="recording-dot"></span>
+              <span className="recording-text">
+                {settings.language === 'pl' ? 'Nagrywanie' : 'Recording'}...
+              </span>
+            </div>
+            <span className="timer">{formatTime(recordingTime)}</span>
+            {secondsLeft != null && (
+              <p className="limit-notice">
+                {settings.language === 'pl'
+                  ? `Nagrywanie zakończy się za ${secondsLeft} s`
+                  : `Recording stops in ${secondsLeft}s`}
+              </p>
+            )}
+            {partialText && <p className="partial-text">{partialText}</p>}
+            <button className="cancel-btn" onClick={handleCancel}>
+              {settings.language === 'pl' ? 'Anuluj' : 'Cancel'}
+            </button>
+          </>
+        )}
       </div>
     </div>
   )
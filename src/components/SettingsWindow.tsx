@@ -1,5 +1,5 @@
-import { useState, useEffect } from 'react'
-import { useApp } from '../store'
+import { useState, useEffect, KeyboardEvent } from 'react'
+import { useApp, TranscriptionOptions, PostProcessorSettings, OutputTemplate, AppRule } from '../store'
 
 interface SettingsWindowProps {
   onClose: () => void
@@ -8,46 +8,496 @@ interface SettingsWindowProps {
 interface AudioDevice {
   id: string
   name: string
+  isDefault?: boolean
+}
+
+interface AudioDeviceList {
+  devices: AudioDevice[]
+  selected: string
+}
+
+interface ModelEntry {
+  id: string
+  fileName: string
+  sizeMb: number
+  quantized: boolean
+  downloaded: boolean
+}
+
+interface DownloadProgress {
+  id: string
+  downloaded: number
+  total: number | null
+}
+
+interface ShortcutError {
+  kind: 'invalid' | 'reserved' | 'conflict' | 'registration'
+  message: string
+}
+
+interface CommandError {
+  kind: string
+  message: string | ShortcutError
+}
+
+interface Replacement {
+  pattern: string
+  replacement: string
+  regex: boolean
+}
+
+interface Glossary {
+  terms: string[]
+  fuzzy: boolean
+  replacements: Replacement[]
+}
+
+// One rule per line: "misheard => correct", or "/regex/ => replacement"
+function formatReplacements(replacements: Replacement[]): string {
+  return replacements
+    .map((r) => `${r.regex ? `/${r.pattern}/` : r.pattern} => ${r.replacement}`)
+    .join('\n')
+}
+
+function parseReplacements(text: string): Replacement[] {
+  return text
+    .split('\n')
+    .filter((line) => line.includes('=>'))
+    .map((line) => {
+      const [pattern, ...rest] = line.split('=>')
+      const trimmed = pattern.trim()
+      const regex = trimmed.length > 1 && trimmed.startsWith('/') && trimmed.endsWith('/')
+      return {
+        pattern: regex ? trimmed.slice(1, -1) : trimmed,
+        replacement: rest.join('=>').trim(),
+        regex,
+      }
+    })
+}
+
+interface ForegroundApp {
+  process: string | null
+  class: string | null
+}
+
+// One rule per line: "thunderbird, outlook => email"; "-" pastes the raw transcript
+function formatAppRules(rules: AppRule[]): string {
+  return rules.map((r) => `${r.apps.join(', ')} => ${r.template ?? '-'}`).join('\n')
+}
+
+function parseAppRules(text: string): AppRule[] {
+  return text
+    .split('\n')
+    .filter((line) => line.includes('=>'))
+    .map((line) => {
+      const [apps, template] = line.split('=>')
+      const id = template.trim()
+      return {
+        apps: apps.split(',').map((app) => app.trim()).filter(Boolean),
+        template: id === '-' ? null : id,
+      }
+    })
+}
+
+const newTemplate: OutputTemplate = { id: '', name: '', prompt: '' }
+
+const defaultMicrophone: AudioDevice = { id: 'default', name: 'Default Microphone' }
+
+const modifierCodes = ['Control', 'Alt', 'Shift', 'Meta', 'OS']
+
+// Build "Ctrl+Alt+D" from a key press; the backend does the real validation
+function shortcutFromEvent(e: KeyboardEvent): string | null {
+  if (modifierCodes.some((m) => e.code.startsWith(m))) return null
+  const parts: string[] = []
+  if (e.ctrlKey) parts.push('Ctrl')
+  if (e.altKey) parts.push('Alt')
+  if (e.shiftKey) parts.push('Shift')
+  if (e.metaKey) parts.push('Super')
+  const key = e.code.replace(/^Key/, '').replace(/^Digit/, '').replace(/^Arrow/, '')
+  parts.push(key)
+  return parts.join('+')
+}
+
+function describeError(error: CommandError, language: 'pl' | 'en'): string {
+  return typeof error.message === 'string'
+    ? error.message
+    : describeShortcutError(error.message, language)
+}
+
+function describeShortcutError(error: ShortcutError, language: 'pl' | 'en'): string {
+  const pl = language === 'pl'
+  switch (error.kind) {
+    case 'conflict':
+      return pl ? `${error.message} jest już używany przez inny program` : `${error.message} is already used by another app`
+    case 'reserved':
+      return pl ? `${error.message} jest zarezerwowany przez ValleyFlow` : `${error.message} is reserved by ValleyFlow`
+    default:
+      return error.message
+  }
 }
 
 export function SettingsWindow({ onClose }: SettingsWindowProps) {
   const { settings, updateSettings } = useApp()
   const [microphones, setMicrophones] = useState<AudioDevice[]>([])
-  const [localApiKey, setLocalApiKey] = useState(settings.apiKey)
+  const [localApiKey, setLocalApiKey] = useState('')
+  const [apiKeyConfigured, setApiKeyConfigured] = useState(false)
+  const [hotkeyDraft, setHotkeyDraft] = useState(settings.hotkey)
+  const [hotkeyError, setHotkeyError] = useState<string | null>(null)
   const [showApiKey, setShowApiKey] = useState(false)
+  const [models, setModels] = useState<ModelEntry[]>([])
+  const [modelDraft, setModelDraft] = useState(settings.model)
+  const [download, setDownload] = useState<DownloadProgress | null>(null)
+  const [modelError, setModelError] = useState<string | null>(null)
+  const [modelPathDraft, setModelPathDraft] = useState(settings.modelPath ?? '')
+  const [languages, setLanguages] = useState<string[]>(['pl', 'en'])
+  const [termsDraft, setTermsDraft] = useState('')
+  const [replacementsDraft, setReplacementsDraft] = useState('')
+  const [fuzzyDraft, setFuzzyDraft] = useState(true)
+  const [glossaryError, setGlossaryError] = useState<string | null>(null)
+  const [glossarySaved, setGlossarySaved] = useState(false)
+  const [promptDraft, setPromptDraft] = useState(settings.transcription.initialPrompt ?? '')
+  const [llmDraft, setLlmDraft] = useState(settings.postProcessor)
+  const [postProcessorError, setPostProcessorError] = useState<string | null>(null)
+  const [templates, setTemplates] = useState<OutputTemplate[]>([])
+  const [templateDraft, setTemplateDraft] = useState<OutputTemplate>(newTemplate)
+  const [templateHotkeyDraft, setTemplateHotkeyDraft] = useState('')
+  const [templateError, setTemplateError] = useState<string | null>(null)
+  const [templateSaved, setTemplateSaved] = useState(false)
+  const [appRulesDraft, setAppRulesDraft] = useState(formatAppRules(settings.appRules))
+  const [appRulesError, setAppRulesError] = useState<string | null>(null)
+  const [appRulesSaved, setAppRulesSaved] = useState(false)
+  const [detectedApp, setDetectedApp] = useState<string | null>(null)
+  const [detecting, setDetecting] = useState(false)
 
   useEffect(() => {
     // Get available microphones from Tauri backend
     const fetchMicrophones = async () => {
       if (window.__TAURI__) {
         try {
-          // This would be a Tauri command to list audio devices
-          // For now, we'll use a placeholder
-          setMicrophones([
-            { id: 'default', name: 'Default Microphone' },
-          ])
+          const list = await window.__TAURI__.core.invoke<AudioDeviceList>('list_audio_devices')
+          setMicrophones([defaultMicrophone, ...list.devices])
         } catch (e) {
           console.error('Failed to fetch microphones:', e)
+          setMicrophones([defaultMicrophone])
         }
       } else {
-        setMicrophones([
-          { id: 'default', name: 'Default Microphone' },
-        ])
+        setMicrophones([defaultMicrophone])
       }
     }
     fetchMicrophones()
   }, [])
 
-  const handleSaveApiKey = () => {
-    updateSettings({ apiKey: localApiKey })
-    // In production, this would save to .env or secure storage
+  const provider = settings.postProcessor.provider
+  const usesApiKey = provider === 'deepSeek' || provider === 'openAiCompatible'
+
+  useEffect(() => {
+    if (!usesApiKey) return
+    // The key itself never leaves the backend, only whether one is stored
+    window.__TAURI__?.core
+      .invoke<{ configured: boolean }>('get_api_key_status', { request: { provider } })
+      .then((status) => setApiKeyConfigured(status.configured))
+      .catch(console.error)
+  }, [provider, usesApiKey])
+
+  useEffect(() => {
+    setLlmDraft(settings.postProcessor)
+  }, [settings.postProcessor])
+
+  const updatePostProcessor = async (changes: Partial<PostProcessorSettings>) => {
+    const postProcessor = { ...settings.postProcessor, ...changes }
+    setPostProcessorError(null)
+    try {
+      // Invalid URLs are rejected; the saved settings come back through settings-changed
+      await window.__TAURI__?.core.invoke('update_settings', { patch: { postProcessor } })
+    } catch (e) {
+      setPostProcessorError((e as { message?: string }).message ?? String(e))
+      setLlmDraft(settings.postProcessor)
+    }
+  }
+
+  const refreshTemplates = () => {
+    window.__TAURI__?.core
+      .invoke<OutputTemplate[]>('list_templates')
+      .then(setTemplates)
+      .catch(console.error)
+  }
+
+  useEffect(refreshTemplates, [])
+
+  // The editor follows the active template, e.g. when it is switched from the tray
+  useEffect(() => {
+    const active = templates.find((t) => t.id === settings.template)
+    if (active) setTemplateDraft(active)
+  }, [templates, settings.template])
+
+  useEffect(() => {
+    setTemplateHotkeyDraft(settings.templateHotkeys[templateDraft.id] ?? '')
+  }, [templateDraft.id, settings.templateHotkeys])
+
+  const handleSelectTemplate = async (id: string) => {
+    setTemplateError(null)
+    setTemplateSaved(false)
+    try {
+      await window.__TAURI__?.core.invoke('select_template', { request: { id } })
+    } catch (e) {
+      setTemplateError(describeError(e as CommandError, settings.language))
+    }
+  }
+
+  useEffect(() => {
+    setAppRulesDraft(formatAppRules(settings.appRules))
+  }, [settings.appRules])
+
+  const handleSaveAppRules = async () => {
+    if (!window.__TAURI__) return
+    setAppRulesError(null)
+    setAppRulesSaved(false)
+    try {
+      await window.__TAURI__.core.invoke('update_settings', {
+        patch: { appRules: parseAppRules(appRulesDraft) },
+      })
+      setAppRulesSaved(true)
+    } catch (e) {
+      setAppRulesError(describeError(e as CommandError, settings.language))
+    }
+  }
+
+  // Gives the user time to switch to the app they want a rule for
+  const handleDetectApp = async () => {
+    if (!window.__TAURI__) return
+    setDetecting(true)
+    setDetectedApp(null)
+    setAppRulesError(null)
+    try {
+      await new Promise((resolve) => setTimeout(resolve, 3000))
+      const app = await window.__TAURI__.core.invoke<ForegroundApp | null>('get_foreground_app')
+      const names = app ? [app.process, app.class].filter(Boolean) : []
+      setDetectedApp(
+        names.length > 0 ? names.join(', ') : settings.language === 'pl' ? 'brak okna' : 'no window'
+      )
+    } catch (e) {
+      setAppRulesError(describeError(e as CommandError, settings.language))
+    } finally {
+      setDetecting(false)
+    }
+  }
+
+  const handleTemplateHotkeyKeyDown = (e: KeyboardEvent<HTMLInputElement>) => {
+    if (e.key === 'Tab') return
+    e.preventDefault()
+    if (e.key === 'Backspace' || e.key === 'Delete') {
+      setTemplateHotkeyDraft('')
+      return
+    }
+    const shortcut = shortcutFromEvent(e)
+    if (shortcut) setTemplateHotkeyDraft(shortcut)
+  }
+
+  const handleSaveTemplate = async () => {
+    if (!window.__TAURI__) return
+    const { invoke } = window.__TAURI__.core
+    setTemplateError(null)
+    setTemplateSaved(false)
+    try {
+      const saved = await invoke<OutputTemplate>('save_template', { request: templateDraft })
+
+      const templateHotkeys = { ...settings.templateHotkeys }
+      if (templateHotkeyDraft) {
+        templateHotkeys[saved.id] = templateHotkeyDraft
+      } else {
+        delete templateHotkeys[saved.id]
+      }
+      if (templateHotkeys[saved.id] !== settings.templateHotkeys[saved.id]) {
+        await invoke('update_settings', { patch: { templateHotkeys } })
+      }
+
+      if (saved.id !== settings.template) {
+        await invoke('select_template', { request: { id: saved.id } })
+      }
+      setTemplateDraft(saved)
+      setTemplateSaved(true)
+    } catch (e) {
+      setTemplateError(describeError(e as CommandError, settings.language))
+    } finally {
+      refreshTemplates()
+    }
+  }
+
+  const handleDeleteTemplate = async () => {
+    if (!window.__TAURI__ || !templateDraft.id) return
+    setTemplateError(null)
+    setTemplateSaved(false)
+    try {
+      await window.__TAURI__.core.invoke('delete_template', { request: { id: templateDraft.id } })
+    } catch (e) {
+      setTemplateError(describeError(e as CommandError, settings.language))
+    } finally {
+      refreshTemplates()
+    }
+  }
+
+  const handleSaveApiKey = async () => {
+    if (!window.__TAURI__) return
+    try {
+      const status = await window.__TAURI__.core.invoke<{ configured: boolean }>('set_api_key', {
+        request: { apiKey: localApiKey, provider },
+      })
+      setApiKeyConfigured(status.configured)
+      setLocalApiKey('')
+    } catch (e) {
+      console.error('Failed to save API key:', e)
+    }
+  }
+
+  useEffect(() => {
+    setHotkeyDraft(settings.hotkey)
+  }, [settings.hotkey])
+
+  const refreshModels = () => {
+    window.__TAURI__?.core
+      .invoke<ModelEntry[]>('list_models')
+      .then(setModels)
+      .catch(console.error)
+  }
+
+  useEffect(() => {
+    if (!window.__TAURI__) return
+    refreshModels()
+
+    let unlisten: (() => void) | undefined
+    window.__TAURI__.event
+      .listen<DownloadProgress>('model-download-progress', (event) => setDownload(event.payload))
+      .then((u) => (unlisten = u))
+    return () => unlisten?.()
+  }, [])
+
+  useEffect(() => {
+    setModelDraft(settings.model)
+  }, [settings.model])
+
+  useEffect(() => {
+    setModelPathDraft(settings.modelPath ?? '')
+  }, [settings.modelPath])
+
+  useEffect(() => {
+    setPromptDraft(settings.transcription.initialPrompt ?? '')
+  }, [settings.transcription.initialPrompt])
+
+  const showGlossary = (glossary: Glossary) => {
+    setTermsDraft(glossary.terms.join('\n'))
+    setReplacementsDraft(formatReplacements(glossary.replacements))
+    setFuzzyDraft(glossary.fuzzy)
+  }
+
+  useEffect(() => {
+    window.__TAURI__?.core.invoke<string[]>('list_languages').then(setLanguages).catch(console.error)
+  }, [])
+
+  const languageNames = new Intl.DisplayNames([settings.language], { type: 'language' })
+  const languageName = (code: string) => {
+    try {
+      return languageNames.of(code) ?? code
+    } catch {
+      return code
+    }
+  }
+
+  useEffect(() => {
+    window.__TAURI__?.core.invoke<Glossary>('get_glossary').then(showGlossary).catch(console.error)
+  }, [])
+
+  const handleSaveGlossary = async () => {
+    if (!window.__TAURI__) return
+    setGlossaryError(null)
+    setGlossarySaved(false)
+    try {
+      const glossary = await window.__TAURI__.core.invoke<Glossary>('set_glossary', {
+        request: {
+          terms: termsDraft.split('\n').map((t) => t.trim()).filter(Boolean),
+          fuzzy: fuzzyDraft,
+          replacements: parseReplacements(replacementsDraft),
+        },
+      })
+      showGlossary(glossary)
+      setGlossarySaved(true)
+    } catch (e) {
+      setGlossaryError(String((e as CommandError).message ?? e))
+    }
+  }
+
+  const updateTranscription = (changes: Partial<TranscriptionOptions>) => {
+    updateSettings({ transcription: { ...settings.transcription, ...changes } })
+  }
+
+  const selectedModel = models.find((m) => m.id === modelDraft)
+
+  const handleApplyModel = async () => {
+    if (!window.__TAURI__ || !selectedModel) return
+    setModelError(null)
+    try {
+      await window.__TAURI__.core.invoke(selectedModel.downloaded ? 'select_model' : 'download_model', {
+        request: { id: selectedModel.id },
+      })
+    } catch (e) {
+      setModelError(String((e as CommandError).message ?? e))
+    } finally {
+      setDownload(null)
+      refreshModels()
+    }
+  }
+
+  const handleUseModelFile = async () => {
+    if (!window.__TAURI__) return
+    setModelError(null)
+    try {
+      await window.__TAURI__.core.invoke('set_model_path', {
+        request: { path: modelPathDraft.trim() },
+      })
+    } catch (e) {
+      setModelError(String((e as CommandError).message ?? e))
+    }
+  }
+
+  const handleCancelDownload = () => {
+    window.__TAURI__?.core.invoke('cancel_model_download').catch(console.error)
+  }
+
+  const handleHotkeyKeyDown = (e: KeyboardEvent<HTMLInputElement>) => {
+    if (e.key === 'Tab') return
+    e.preventDefault()
+    const shortcut = shortcutFromEvent(e)
+    if (shortcut) {
+      setHotkeyDraft(shortcut)
+      setHotkeyError(null)
+    }
+  }
+
+  const handleSaveHotkey = async () => {
+    if (!window.__TAURI__) return
+    try {
+      const status = await window.__TAURI__.core.invoke<{ hotkey: string }>('set_hotkey', {
+        request: { hotkey: hotkeyDraft },
+      })
+      setHotkeyDraft(status.hotkey)
+      setHotkeyError(null)
+    } catch (e) {
+      setHotkeyError(describeError(e as CommandError, settings.language))
+    }
   }
 
   const handleLanguageChange = (lang: 'pl' | 'en') => {
     updateSettings({ language: lang })
   }
 
-  const handleMicrophoneChange = (deviceId: string) => {
+  const handleMicrophoneChange = async (deviceId: string) => {
+    if (window.__TAURI__) {
+      try {
+        await window.__TAURI__.core.invoke('select_audio_device', { request: { id: deviceId } })
+      } catch (e) {
+        console.error('Failed to select microphone:', e)
+      }
+      return
+    }
     updateSettings({ microphone: deviceId })
   }
 
@@ -100,33 +550,429 @@ export function SettingsWindow({ onClose }: SettingsWindowProps) {
           </select>
         </div>
 
-        {/* API Key */}
+        {/* Whisper model */}
         <div className="settings-group">
           <label className="settings-label">
-            DeepSeek API Key
+            {settings.language === 'pl' ? 'Model rozpoznawania mowy' : 'Speech recognition model'}
           </label>
+          <select
+            className="settings-select"
+            value={modelDraft}
+            onChange={(e) => setModelDraft(e.target.value)}
+            disabled={download !== null}
+          >
+            {models.map((m) => (
+              <option key={m.id} value={m.id}>
+                {m.id} ({m.sizeMb} MB){m.downloaded ? ' ✓' : ''}
+              </option>
+            ))}
+          </select>
+          {download ? (
+            <>
+              <p className="settings-hint">
+                {settings.language === 'pl' ? 'Pobieranie' : 'Downloading'}{' '}
+                {Math.round(download.downloaded / 1048576)}
+                {download.total !== null && ` / ${Math.round(download.total / 1048576)}`} MB
+              </p>
+              <button className="save-btn" onClick={handleCancelDownload}>
+                {settings.language === 'pl' ? 'Anuluj' : 'Cancel'}
+              </button>
+            </>
+          ) : (
+            <button
+              className="save-btn"
+              onClick={handleApplyModel}
+              disabled={!selectedModel || (selectedModel.downloaded && modelDraft === settings.model)}
+            >
+              {selectedModel && !selectedModel.downloaded
+                ? settings.language === 'pl' ? 'Pobierz i użyj' : 'Download and use'
+                : settings.language === 'pl' ? 'Użyj' : 'Use'}
+            </button>
+          )}
           <div className="api-key-input">
             <input
-              type={showApiKey ? 'text' : 'password'}
-              value={localApiKey}
-              onChange={(e) => setLocalApiKey(e.target.value)}
-              placeholder="sk-..."
+              type="text"
+              value={modelPathDraft}
+              onChange={(e) => setModelPathDraft(e.target.value)}
+              placeholder={settings.language === 'pl' ? 'Własny plik modelu (.bin)' : 'Custom model file (.bin)'}
               className="settings-input"
             />
             <button
-              className="toggle-visibility"
-              onClick={() => setShowApiKey(!showApiKey)}
+              className="save-btn"
+              onClick={handleUseModelFile}
+              disabled={!modelPathDraft.trim() || modelPathDraft.trim() === settings.modelPath}
             >
-              {showApiKey ? '🙈' : '👁️'}
+              {settings.language === 'pl' ? 'Użyj pliku' : 'Use file'}
             </button>
           </div>
-          <button className="save-btn" onClick={handleSaveApiKey}>
-            {settings.language === 'pl' ? 'Zapisz' : 'Save'}
+          {modelError && <p className="settings-error">{modelError}</p>}
+          <p className="settings-hint">
+            {settings.language === 'pl'
+              ? 'Większe modele są dokładniejsze, ale wolniejsze; wersje skwantyzowane (q5) są mniejsze'
+              : 'Larger models are more accurate but slower; quantized (q5) versions are smaller'}
+          </p>
+        </div>
+
+        {/* Whisper decoding */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Język nagrań' : 'Spoken language'}
+          </label>
+          <select
+            className="settings-select"
+            value={settings.transcription.language ?? ''}
+            onChange={(e) => updateTranscription({ language: e.target.value || null })}
+          >
+            <option value="">{settings.language === 'pl' ? 'Wykrywaj automatycznie' : 'Detect automatically'}</option>
+            {languages
+              .map((code) => ({ code, name: languageName(code) }))
+              .sort((a, b) => a.name.localeCompare(b.name, settings.language))
+              .map(({ code, name }) => (
+                <option key={code} value={code}>
+                  {name} ({code})
+                </option>
+              ))}
+          </select>
+
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Szerokość wiązki' : 'Beam width'}
+          </label>
+          <select
+            className="settings-select"
+            value={settings.transcription.beamSize}
+            onChange={(e) => updateTranscription({ beamSize: Number(e.target.value) })}
+          >
+            <option value={1}>{settings.language === 'pl' ? '1 (najszybciej)' : '1 (fastest)'}</option>
+            <option value={3}>3</option>
+            <option value={5}>{settings.language === 'pl' ? '5 (najdokładniej)' : '5 (most accurate)'}</option>
+          </select>
+
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Podpowiedź dla modelu' : 'Initial prompt'}
+          </label>
+          <input
+            type="text"
+            value={promptDraft}
+            onChange={(e) => setPromptDraft(e.target.value)}
+            onBlur={() => updateTranscription({ initialPrompt: promptDraft.trim() || null })}
+            placeholder={settings.language === 'pl' ? 'Nazwy i terminy, np. ValleyFlow, Tauri' : 'Names and terms, e.g. ValleyFlow, Tauri'}
+            className="settings-input"
+          />
+
+          <label className="settings-checkbox">
+            <input
+              type="checkbox"
+              checked={settings.transcription.suppressNonSpeech}
+              onChange={(e) => updateTranscription({ suppressNonSpeech: e.target.checked })}
+            />
+            {settings.language === 'pl' ? 'Pomijaj dźwięki inne niż mowa' : 'Ignore non-speech sounds'}
+          </label>
+          <p className="settings-hint">
+            {settings.language === 'pl'
+              ? 'Wymuszenie języka i podpowiedź poprawiają rozpoznawanie; szersza wiązka jest dokładniejsza, ale wolniejsza'
+              : 'Forcing the language and a prompt improve recognition; a wider beam is more accurate but slower'}
+          </p>
+        </div>
+
+        {/* Translation */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Tłumacz na' : 'Translate into'}
+          </label>
+          <select
+            className="settings-select"
+            value={settings.translateTo ?? ''}
+            onChange={(e) => updateSettings({ translateTo: e.target.value || null })}
+          >
+            <option value="">{settings.language === 'pl' ? 'Nie tłumacz' : 'Off'}</option>
+            {languages
+              .map((code) => ({ code, name: languageName(code) }))
+              .sort((a, b) => a.name.localeCompare(b.name, settings.language))
+              .map(({ code, name }) => (
+                <option key={code} value={code}>
+                  {name} ({code})
+                </option>
+              ))}
+          </select>
+          <p className="settings-hint">
+            {settings.language === 'pl'
+              ? 'Na angielski tłumaczy Whisper; pozostałe języki wymagają poprawiania tekstu przez AI'
+              : 'Whisper translates into English itself; other languages need AI text cleanup'}
+          </p>
+        </div>
+
+        {/* Glossary */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Słownik (jedno słowo na linię)' : 'Glossary (one term per line)'}
+          </label>
+          <textarea
+            className="settings-input"
+            rows={4}
+            value={termsDraft}
+            onChange={(e) => {
+              setTermsDraft(e.target.value)
+              setGlossarySaved(false)
+            }}
+            placeholder={'ValleyFlow\nKasia Nowak'}
+          />
+          <label className="settings-checkbox">
+            <input
+              type="checkbox"
+              checked={fuzzyDraft}
+              onChange={(e) => {
+                setFuzzyDraft(e.target.checked)
+                setGlossarySaved(false)
+              }}
+            />
+            {settings.language === 'pl'
+              ? 'Poprawiaj także literówki (wyłącz, jeśli nazwy się odmieniają)'
+              : 'Also fix near-misses (turn off if names are inflected)'}
+          </label>
+
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Zamiany' : 'Replacements'}
+          </label>
+          <textarea
+            className="settings-input"
+            rows={3}
+            value={replacementsDraft}
+            onChange={(e) => {
+              setReplacementsDraft(e.target.value)
+              setGlossarySaved(false)
+            }}
+            placeholder={'kay eight s => k8s\n/pull request (\\d+)/ => PR #$1'}
+          />
+          <button className="save-btn" onClick={handleSaveGlossary}>
+            {glossarySaved
+              ? settings.language === 'pl' ? 'Zapisano' : 'Saved'
+              : settings.language === 'pl' ? 'Zapisz słownik' : 'Save glossary'}
           </button>
+          {glossaryError && <p className="settings-error">{glossaryError}</p>}
+          <p className="settings-hint">
+            {settings.language === 'pl'
+              ? 'Słowa ze słownika podpowiadają pisownię modelowi; zamiany działają przed poprawą przez AI'
+              : 'Glossary terms guide the model’s spelling; replacements run before AI cleanup'}
+          </p>
+        </div>
+
+        {/* Post-processing */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Poprawianie tekstu przez AI' : 'AI text cleanup'}
+          </label>
+          <select
+            className="settings-select"
+            value={provider}
+            onChange={(e) => updatePostProcessor({ provider: e.target.value as PostProcessorSettings['provider'] })}
+          >
+            <option value="none">{settings.language === 'pl' ? 'Bez modelu językowego' : 'No language model'}</option>
+            <option value="deepSeek">DeepSeek</option>
+            <option value="openAiCompatible">
+              {settings.language === 'pl' ? 'API zgodne z OpenAI' : 'OpenAI-compatible API'}
+            </option>
+            <option value="local">
+              {settings.language === 'pl' ? 'Model lokalny (Ollama, llama.cpp)' : 'Local model (Ollama, llama.cpp)'}
+            </option>
+          </select>
+
+          {(provider === 'openAiCompatible' || provider === 'local') && (
+            <>
+              <input
+                type="text"
+                value={provider === 'local' ? llmDraft.localBaseUrl : llmDraft.openaiBaseUrl}
+                onChange={(e) =>
+                  setLlmDraft({
+                    ...llmDraft,
+                    [provider === 'local' ? 'localBaseUrl' : 'openaiBaseUrl']: e.target.value,
+                  })
+                }
+                onBlur={() => updatePostProcessor(llmDraft)}
+                placeholder={provider === 'local' ? 'http://localhost:11434/v1' : 'https://api.openai.com/v1'}
+                className="settings-input"
+              />
+              <input
+                type="text"
+                value={provider === 'local' ? llmDraft.localModel : llmDraft.openaiModel}
+                onChange={(e) =>
+                  setLlmDraft({
+                    ...llmDraft,
+                    [provider === 'local' ? 'localModel' : 'openaiModel']: e.target.value,
+                  })
+                }
+                onBlur={() => updatePostProcessor(llmDraft)}
+                placeholder={settings.language === 'pl' ? 'Nazwa modelu' : 'Model name'}
+                className="settings-input"
+              />
+            </>
+          )}
+
+          {usesApiKey && (
+            <>
+              <div className="api-key-input">
+                <input
+                  type={showApiKey ? 'text' : 'password'}
+                  value={localApiKey}
+                  onChange={(e) => setLocalApiKey(e.target.value)}
+                  placeholder={apiKeyConfigured ? '••••••••' : 'API key (sk-...)'}
+                  className="settings-input"
+                />
+                <button
+                  className="toggle-visibility"
+                  onClick={() => setShowApiKey(!showApiKey)}
+                >
+                  {showApiKey ? '🙈' : '👁️'}
+                </button>
+              </div>
+              <button className="save-btn" onClick={handleSaveApiKey}>
+                {settings.language === 'pl' ? 'Zapisz klucz' : 'Save key'}
+              </button>
+            </>
+          )}
+          <label className="settings-checkbox">
+            <input
+              type="checkbox"
+              checked={settings.postProcessor.offlineFallback}
+              onChange={(e) => updatePostProcessor({ offlineFallback: e.target.checked })}
+            />
+            {settings.language === 'pl'
+              ? 'Bez modelu poprawiaj tekst offline (wypełniacze, powtórzenia, interpunkcja)'
+              : 'Without a model, clean up offline (fillers, repetitions, punctuation)'}
+          </label>
+          {postProcessorError && <p className="settings-error">{postProcessorError}</p>}
+          <p className="settings-hint">
+            {provider === 'deepSeek'
+              ? settings.language === 'pl'
+                ? 'Klucz API DeepSeek jest wymagany do poprawiania tekstu'
+                : 'A DeepSeek API key is required for text cleanup'
+              : provider === 'openAiCompatible'
+                ? settings.language === 'pl'
+                  ? 'Adres bazowy API, np. https://api.openai.com/v1; klucz jest opcjonalny'
+                  : 'The API base URL, e.g. https://api.openai.com/v1; the key is optional'
+                : provider === 'local'
+                  ? 'Ollama: http://localhost:11434/v1, llama.cpp: http://localhost:8080/v1'
+                  : settings.language === 'pl'
+                    ? 'Tekst poprawiany jest tylko offline, jeśli ta opcja jest włączona'
+                    : 'Text is only cleaned up offline, if that is enabled'}
+          </p>
+        </div>
+
+        {/* Output templates */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Styl tekstu' : 'Output style'}
+          </label>
+          <select
+            className="settings-select"
+            value={templateDraft.id || settings.template}
+            onChange={(e) => handleSelectTemplate(e.target.value)}
+          >
+            {templates.map((template) => (
+              <option key={template.id} value={template.id}>
+                {template.name}
+                {settings.templateHotkeys[template.id] ? ` (${settings.templateHotkeys[template.id]})` : ''}
+              </option>
+            ))}
+          </select>
+          <input
+            type="text"
+            value={templateDraft.name}
+            onChange={(e) => {
+              setTemplateDraft({ ...templateDraft, name: e.target.value })
+              setTemplateSaved(false)
+            }}
+            placeholder={settings.language === 'pl' ? 'Nazwa stylu' : 'Style name'}
+            className="settings-input"
+          />
+          <textarea
+            className="settings-input"
+            rows={8}
+            value={templateDraft.prompt}
+            onChange={(e) => {
+              setTemplateDraft({ ...templateDraft, prompt: e.target.value })
+              setTemplateSaved(false)
+            }}
+            placeholder={
+              settings.language === 'pl'
+                ? 'Instrukcje dla modelu, np. „Zamień tekst na notatki w punktach. Pisz w {{language}}.”'
+                : 'Instructions for the model, e.g. "Turn the text into bullet notes. Write in {{language}}."'
+            }
+          />
+          <input
+            type="text"
+            value={templateHotkeyDraft}
+            onKeyDown={handleTemplateHotkeyKeyDown}
+            readOnly
+            placeholder={settings.language === 'pl' ? 'Skrót (opcjonalny)' : 'Shortcut (optional)'}
+            className="settings-input"
+          />
+          <div className="language-buttons">
+            <button className="save-btn" onClick={handleSaveTemplate}>
+              {templateSaved
+                ? settings.language === 'pl' ? 'Zapisano' : 'Saved'
+                : settings.language === 'pl' ? 'Zapisz styl' : 'Save style'}
+            </button>
+            <button
+              className="lang-btn"
+              onClick={() => {
+                setTemplateDraft(newTemplate)
+                setTemplateSaved(false)
+              }}
+            >
+              {settings.language === 'pl' ? 'Nowy' : 'New'}
+            </button>
+            {templateDraft.id && templateDraft.id !== 'default' && (
+              <button className="lang-btn" onClick={handleDeleteTemplate}>
+                {settings.language === 'pl' ? 'Usuń' : 'Delete'}
+              </button>
+            )}
+          </div>
+          {templateError && <p className="settings-error">{templateError}</p>}
+          <p className="settings-hint">
+            {settings.language === 'pl'
+              ? 'Zmienne: {{input}} (transkrypcja), {{language}} (język wyniku), {{source_language}}. Styl przełączysz też z zasobnika lub skrótem; Backspace usuwa skrót.'
+              : 'Variables: {{input}} (the transcript), {{language}} (output language), {{source_language}}. Switch styles from the tray or by shortcut too; Backspace clears the shortcut.'}
+          </p>
+        </div>
+
+        {/* Per-app output styles */}
+        <div className="settings-group">
+          <label className="settings-label">
+            {settings.language === 'pl' ? 'Styl według aplikacji' : 'Style per app'}
+          </label>
+          <textarea
+            className="settings-input"
+            rows={4}
+            value={appRulesDraft}
+            onChange={(e) => {
+              setAppRulesDraft(e.target.value)
+              setAppRulesSaved(false)
+            }}
+            placeholder={'WindowsTerminal, kitty => -\nthunderbird, outlook => email\ncode, idea64 => code-comment'}
+          />
+          <div className="language-buttons">
+            <button className="save-btn" onClick={handleSaveAppRules}>
+              {appRulesSaved
+                ? settings.language === 'pl' ? 'Zapisano' : 'Saved'
+                : settings.language === 'pl' ? 'Zapisz reguły' : 'Save rules'}
+            </button>
+            <button className="lang-btn" onClick={handleDetectApp} disabled={detecting}>
+              {detecting
+                ? settings.language === 'pl' ? 'Przełącz na aplikację…' : 'Switch to the app…'
+                : settings.language === 'pl' ? 'Wykryj aplikację' : 'Detect app'}
+            </button>
+          </div>
+          {detectedApp && (
+            <p className="settings-hint">
+              {settings.language === 'pl' ? 'Wykryto: ' : 'Detected: '}
+              <code>{detectedApp}</code>
+            </p>
+          )}
+          {appRulesError && <p className="settings-error">{appRulesError}</p>}
           <p className="settings-hint">
             {settings.language === 'pl'
-              ? 'Klucz API jest wymagany do post-processingu tekstu'
-              : 'API key is required for text post-processing'}
+              ? 'Jedna reguła na linię: „aplikacje => id stylu”; „-” wkleja surową transkrypcję. Pierwsza pasująca reguła wygrywa, w pozostałych aplikacjach obowiązuje wybrany styl.'
+              : 'One rule per line: "apps => style id"; "-" pastes the raw transcript. The first matching rule wins; other apps use the selected style.'}
           </p>
         </div>
 
@@ -136,13 +982,46 @@ export function SettingsWindow({ onClose }: SettingsWindowProps) {
             {settings.language === 'pl' ? 'Skrót klawiszowy' : 'Keyboard Shortcut'}
           </label>
           <div className="hotkey-display">
-            <kbd>Ctrl</kbd> + <kbd>Shift</kbd> + <kbd>Space</kbd>
+            {settings.hotkey.split('+').map((key, i) => (
+              <span key={key}>
+                {i > 0 && ' + '}
+                <kbd>{key}</kbd>
+              </span>
+            ))}
+          </div>
+          <div className="api-key-input">
+            <input
+              type="text"
+              value={hotkeyDraft}
+              onKeyDown={handleHotkeyKeyDown}
+              readOnly
+              placeholder="Ctrl+Alt+D"
+              className="settings-input"
+            />
           </div>
+          <button className="save-btn" onClick={handleSaveHotkey}>
+            {settings.language === 'pl' ? 'Zmień skrót' : 'Change shortcut'}
+          </button>
+          {hotkeyError && <p className="settings-error">{hotkeyError}</p>}
           <p className="settings-hint">
             {settings.language === 'pl'
-              ? 'Skrót można zmienić w przyszłych wersjach'
-              : 'Shortcut can be changed in future versions'}
+              ? 'Kliknij pole i naciśnij nową kombinację klawiszy'
+              : 'Click the field and press the new key combination'}
           </p>
+          <div className="language-buttons">
+            <button
+              className={`lang-btn ${settings.recordingMode === 'toggle' ? 'active' : ''}`}
+              onClick={() => updateSettings({ recordingMode: 'toggle' })}
+            >
+              {settings.language === 'pl' ? 'Naciśnij, aby przełączyć' : 'Press to toggle'}
+            </button>
+            <button
+              className={`lang-btn ${settings.recordingMode === 'pushToTalk' ? 'active' : ''}`}
+              onClick={() => updateSettings({ recordingMode: 'pushToTalk' })}
+            >
+              {settings.language === 'pl' ? 'Przytrzymaj, aby mówić' : 'Hold to talk'}
+            </button>
+          </div>
         </div>
       </div>
 
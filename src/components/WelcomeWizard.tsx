@@ -19,8 +19,15 @@ export function WelcomeWizard({ onComplete }: WelcomeWizardProps) {
     setStep('api')
   }
 
-  const handleApiKeySave = () => {
-    updateSettings({ apiKey })
+  const handleApiKeySave = async () => {
+    if (window.__TAURI__) {
+      try {
+        await window.__TAURI__.core.invoke('set_api_key', { request: { apiKey } })
+      } catch (e) {
+        console.error('Failed to save API key:', e)
+        return
+      }
+    }
     setStep('microphone')
   }
 
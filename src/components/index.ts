@@ -1,4 +1,4 @@
-export { RecordingOverlay } from './RecordingOverlay'
+export { RecordingOverlay, type AutoStopReason } from './RecordingOverlay'
 export { SettingsWindow } from './SettingsWindow'
 export { HistoryWindow } from './HistoryWindow'
 export { WelcomeWizard } from './WelcomeWizard'
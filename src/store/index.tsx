@@ -4,16 +4,79 @@ export interface TranscriptionItem {
   id: string
   text: string
   rawText: string
-  language: 'pl' | 'en'
+  /** Whisper language code of the recording, or 'auto' if it was not recognised */
+  language: string
   timestamp: number
 }
 
+export interface TranscriptionOptions {
+  /** Language to force, or null to detect it */
+  language: string | null
+  beamSize: number
+  /** null derives it from the CPU cores */
+  threads: number | null
+  temperatureIncrement: number
+  initialPrompt: string | null
+  suppressNonSpeech: boolean
+}
+
+export type PostProcessorProvider = 'none' | 'deepSeek' | 'openAiCompatible' | 'local'
+
+export interface PostProcessorSettings {
+  provider: PostProcessorProvider
+  /** Base URL of the API, without /chat/completions */
+  openaiBaseUrl: string
+  openaiModel: string
+  /** Ollama or llama.cpp server */
+  localBaseUrl: string
+  localModel: string
+  /** Clean up offline when the model is off or unreachable */
+  offlineFallback: boolean
+}
+
+/** Named output style for AI cleanup, stored as a Markdown file */
+export interface OutputTemplate {
+  /** File name without .md; empty when creating a new template */
+  id: string
+  name: string
+  /** Language code the style always writes in; absent or null follows the translation target */
+  language?: string | null
+  /** Prompt with {{input}}, {{language}} and {{source_language}} placeholders */
+  prompt: string
+}
+
+/** Picks the output template while one of the apps is in the foreground */
+export interface AppRule {
+  /** Process or window class names, e.g. "thunderbird" or "Code" */
+  apps: string[]
+  /** Template id, or null to paste the raw transcript */
+  template: string | null
+}
+
 export interface Settings {
   language: 'pl' | 'en'
   microphone: string
-  apiKey: string
   hotkey: string
+  recordingMode: 'toggle' | 'pushToTalk'
   hasCompletedWizard: boolean
+  /** Start with the system; null until the first launch decides */
+  autostart: boolean | null
+  streaming: boolean
+  autoStopSilenceSecs: number | null
+  maxRecordingSecs: number
+  cancelOnEscape: boolean
+  model: string
+  modelPath: string | null
+  transcription: TranscriptionOptions
+  /** Language to translate the dictation into, or null to keep the spoken one */
+  translateTo: string | null
+  postProcessor: PostProcessorSettings
+  /** Id of the active output template */
+  template: string
+  /** Shortcuts switching to a template, by template id */
+  templateHotkeys: Record<string, string>
+  /** Checked in order before a recording is post-processed; the first match wins */
+  appRules: AppRule[]
 }
 
 interface AppState {
@@ -33,18 +96,45 @@ interface AppState {
 const defaultSettings: Settings = {
   language: 'pl',
   microphone: 'default',
-  apiKey: '',
   hotkey: 'Ctrl+Shift+Space',
+  recordingMode: 'toggle',
   hasCompletedWizard: false,
+  autostart: null,
+  streaming: true,
+  autoStopSilenceSecs: null,
+  maxRecordingSecs: 300,
+  cancelOnEscape: true,
+  model: 'small',
+  modelPath: null,
+  transcription: {
+    language: null,
+    beamSize: 1,
+    threads: null,
+    temperatureIncrement: 0.2,
+    initialPrompt: null,
+    suppressNonSpeech: true,
+  },
+  translateTo: null,
+  postProcessor: {
+    provider: 'deepSeek',
+    openaiBaseUrl: 'https://api.openai.com/v1',
+    openaiModel: 'gpt-4o-mini',
+    localBaseUrl: 'http://localhost:11434/v1',
+    localModel: 'llama3.2',
+    offlineFallback: true,
+  },
+  template: 'default',
+  templateHotkeys: {},
+  appRules: [],
 }
 
+// Settings used to live in localStorage; the backend owns them now
+const LEGACY_SETTINGS_KEY = 'valleyflow-settings'
+
 const AppContext = createContext<AppState | undefined>(undefined)
 
 export function AppProvider({ children }: { children: ReactNode }) {
-  const [settings, setSettings] = useState<Settings>(() => {
-    const saved = localStorage.getItem('valleyflow-settings')
-    return saved ? { ...defaultSettings, ...JSON.parse(saved) } : defaultSettings
-  })
+  const [settings, setSettings] = useState<Settings>(defaultSettings)
 
   const [history, setHistory] = useState<TranscriptionItem[]>(() => {
     const saved = localStorage.getItem('valleyflow-history')
@@ -55,9 +145,32 @@ export function AppProvider({ children }: { children: ReactNode }) {
   const [isProcessing, setIsProcessing] = useState(false)
   const [recordingTime, setRecordingTime] = useState(0)
 
+  // Load settings from the backend, migrating anything left in localStorage
   useEffect(() => {
-    localStorage.setItem('valleyflow-settings', JSON.stringify(settings))
-  }, [settings])
+    if (!window.__TAURI__) return
+    const { invoke } = window.__TAURI__.core
+
+    const load = async () => {
+      const legacy = localStorage.getItem(LEGACY_SETTINGS_KEY)
+      if (legacy) {
+        try {
+          await invoke('update_settings', { patch: JSON.parse(legacy) })
+          localStorage.removeItem(LEGACY_SETTINGS_KEY)
+        } catch (e) {
+          console.error('Failed to migrate settings:', e)
+        }
+      }
+      setSettings(await invoke<Settings>('get_settings'))
+    }
+    load().catch(console.error)
+
+    const unlisten = window.__TAURI__.event.listen<Settings>('settings-changed', (event) => {
+      setSettings(event.payload)
+    })
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
 
   useEffect(() => {
     localStorage.setItem('valleyflow-history', JSON.stringify(history))
@@ -65,6 +178,12 @@ export function AppProvider({ children }: { children: ReactNode }) {
 
   const updateSettings = (newSettings: Partial<Settings>) => {
     setSettings((prev) => ({ ...prev, ...newSettings }))
+    if (window.__TAURI__) {
+      window.__TAURI__.core
+        .invoke<Settings>('update_settings', { patch: newSettings })
+        .then(setSettings)
+        .catch((e: unknown) => console.error('Failed to save settings:', e))
+    }
   }
 
   const addToHistory = (item: Omit<TranscriptionItem, 'id' | 'timestamp'>) => {